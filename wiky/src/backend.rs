@@ -0,0 +1,888 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use pubky::{Pubky, PubkySession, PublicStorage};
+use tokio::{runtime::Runtime, sync::mpsc as async_mpsc};
+use wiki_core::{
+    attachment::{attachment_name, read_attachment, save_download},
+    cache::{Draft, PageCache, PendingWrite},
+    client::{SyncReport, UpdateConflict},
+    diff::DiffSide,
+    follow::Follow,
+    graph::LinkGraph,
+    history::Revision,
+    links::{same_title, WikiLink},
+    merge::PreparedMerge,
+    page::WikiPage,
+    profile::{self, initialize_auth, RestoredSession},
+    proposal::{self, Proposal, ReceivedProposal},
+    search::SearchIndex,
+    storage::{new_page_id, StorageError},
+    WikiClient,
+};
+
+/// How often to check if the homeserver is reachable again, while offline or while writes made
+/// when it was unreachable are queued
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Requests sent from the UI to the background worker
+pub(crate) enum Command {
+    /// Reload the titles of all own wiki pages
+    FetchFiles,
+    /// Fetch the content of a page from the homeserver of `user_pk`
+    FetchPage {
+        user_pk: String,
+        page_id: String,
+    },
+    /// List the followed users, with their profile names
+    FetchFollows,
+    /// Find the page of `user_pk` a `wiki:{title}` link points to
+    ResolveTitle {
+        user_pk: String,
+        title: String,
+        /// Heading the link points to, to scroll to once the page is open
+        heading: Option<String>,
+    },
+    /// Find which of the followed users have a version of this page
+    DiscoverForks {
+        page_id: String,
+    },
+    /// Fetch the current version of the page a fork was created from
+    FetchUpstream {
+        user_pk: String,
+        page_id: String,
+    },
+    /// List the revisions of a page
+    FetchHistory {
+        user_pk: String,
+        page_id: String,
+    },
+    FetchRevision {
+        user_pk: String,
+        page_id: String,
+        revision: Revision,
+    },
+    /// Fetch one of the versions compared in the diff view
+    FetchDiffSide {
+        side: DiffSide,
+    },
+    /// Merge the changes made upstream since the fork into `mine`, one of the own pages
+    PrepareMerge {
+        page_id: String,
+        mine: WikiPage,
+    },
+    /// Publish a proposal for the changes of an own fork to the author of the forked page
+    ProposeChanges {
+        proposal: Proposal,
+    },
+    /// Find the proposals made by the followed users to own pages
+    FetchInbox,
+    /// Merge the changes of a received proposal into the own page it targets
+    PrepareProposalMerge {
+        proposal: ReceivedProposal,
+    },
+    /// Hide a received proposal from the inbox
+    DismissProposal {
+        url: String,
+    },
+    /// Index the own pages and the pages of the followed users, for full-text search and backlinks
+    BuildIndexes,
+    CreatePage {
+        page: WikiPage,
+        filename: Option<String>,
+    },
+    /// Overwrite an own page, unless it was saved since `base_version`, if given
+    UpdatePage {
+        page_id: String,
+        page: WikiPage,
+        base_version: Option<Revision>,
+    },
+    DeletePage {
+        page_id: String,
+    },
+    /// Upload a file from disk, to attach it to the page being written
+    UploadAttachment {
+        path: PathBuf,
+    },
+    /// Save a file attached to a page in the downloads folder
+    DownloadAttachment {
+        url: String,
+    },
+    /// List the unsaved changes kept locally
+    FetchDrafts,
+    /// Keep the unsaved changes of an editor locally, replacing the previous version of the draft
+    SaveDraft {
+        draft: Draft,
+    },
+    DiscardDraft {
+        id: String,
+    },
+    /// Sign out, forget the stored session and start a new auth flow
+    Logout,
+}
+
+/// Results posted back from the background worker to the UI
+pub(crate) enum Event {
+    AuthUrl(String),
+    /// Signed in as `own_pk`. While `offline`, only cached pages are available and writes are queued.
+    Authenticated {
+        own_pk: String,
+        offline: bool,
+    },
+    AuthFailed(String),
+    LoggedOut,
+    /// Map file URL to file title
+    FilesFetched(HashMap<String, String>),
+    PageFetched {
+        user_pk: String,
+        page_id: String,
+        result: Result<WikiPage, String>,
+    },
+    UpstreamFetched {
+        user_pk: String,
+        page_id: String,
+        result: Result<WikiPage, String>,
+    },
+    /// On success, holds the revisions, newest first
+    HistoryFetched {
+        user_pk: String,
+        page_id: String,
+        result: Result<Vec<Revision>, String>,
+    },
+    RevisionFetched {
+        user_pk: String,
+        page_id: String,
+        revision: Revision,
+        result: Result<WikiPage, String>,
+    },
+    FollowsFetched(Vec<Follow>),
+    /// On success, holds the ID of the page with this title, if there is one
+    TitleResolved {
+        user_pk: String,
+        title: String,
+        heading: Option<String>,
+        result: Result<Option<String>, String>,
+    },
+    ForksDiscovered {
+        page_id: String,
+        fork_urls: Vec<String>,
+    },
+    DiffSideFetched {
+        side: DiffSide,
+        result: Result<WikiPage, String>,
+    },
+    MergePrepared {
+        page_id: String,
+        result: Result<Box<PreparedMerge>, String>,
+    },
+    ChangesProposed(Result<(), String>),
+    /// Received proposals that were not handled yet
+    InboxFetched(Vec<ReceivedProposal>),
+    ProposalDismissed {
+        url: String,
+        result: Result<(), String>,
+    },
+    IndexesBuilt {
+        search: SearchIndex,
+        links: LinkGraph,
+    },
+    /// On success, holds the path of the new page and the page as it was created
+    PageCreated(Result<(String, WikiPage), String>),
+    PageUpdated {
+        page_id: String,
+        result: Result<WikiPage, String>,
+    },
+    /// An update was refused as the page was saved elsewhere since, and is to be merged with it
+    PageConflicted {
+        page_id: String,
+        merge: Box<PreparedMerge>,
+    },
+    PageDeleted {
+        page_id: String,
+        result: Result<(), String>,
+    },
+    /// On success, holds the `pubky://` URL of the uploaded file
+    AttachmentUploaded {
+        file_name: String,
+        result: Result<String, String>,
+    },
+    /// On success, holds where the file was saved
+    AttachmentDownloaded(Result<PathBuf, String>),
+    /// On success, holds the drafts, most recently saved first
+    DraftsFetched(Result<Vec<Draft>, String>),
+    DraftSaved(Result<(), String>),
+    DraftDiscarded(Result<(), String>),
+    PendingWritesSynced(Result<SyncReport, String>),
+}
+
+/// Handle to the background worker that runs all homeserver I/O on the tokio runtime
+pub(crate) struct Backend {
+    commands: async_mpsc::UnboundedSender<Command>,
+    events: Receiver<Event>,
+    // Keeps the worker alive for as long as the UI holds the handle
+    _rt: Runtime,
+}
+
+impl Backend {
+    /// Start the worker, which begins with the auth flow and then serves commands
+    pub(crate) fn spawn(rt: Runtime, ctx: egui::Context) -> Self {
+        let (command_tx, command_rx) = async_mpsc::unbounded_channel();
+        let (event_tx, event_rx) = mpsc::channel();
+
+        rt.spawn(run(command_rx, EventSink { tx: event_tx, ctx }));
+
+        Self {
+            commands: command_tx,
+            events: event_rx,
+            _rt: rt,
+        }
+    }
+
+    pub(crate) fn send(&self, command: Command) {
+        if self.commands.send(command).is_err() {
+            log::error!("Background worker is not running");
+        }
+    }
+
+    /// Drain all events received since the last call, without blocking
+    pub(crate) fn poll(&self) -> Vec<Event> {
+        self.events.try_iter().collect()
+    }
+
+    /// Block until an event matching `done` is received, or `timeout` elapsed. The events received
+    /// in the meantime are dropped, so this is only meant for when the app closes.
+    pub(crate) fn wait_for(&self, timeout: Duration, done: impl Fn(&Event) -> bool) -> bool {
+        let deadline = Instant::now() + timeout;
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            match self.events.recv_timeout(left) {
+                Ok(event) if done(&event) => return true,
+                Ok(_) => {}
+                Err(_) => return false,
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+impl Backend {
+    /// A handle with no worker behind it. The test plays the worker through the returned channel ends.
+    pub(crate) fn detached() -> (Self, async_mpsc::UnboundedReceiver<Command>, Sender<Event>) {
+        let (command_tx, command_rx) = async_mpsc::unbounded_channel();
+        let (event_tx, event_rx) = mpsc::channel();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("failed to build the test runtime");
+
+        let backend = Self {
+            commands: command_tx,
+            events: event_rx,
+            _rt: rt,
+        };
+        (backend, command_rx, event_tx)
+    }
+}
+
+/// Sends events to the UI and wakes it up so they are processed promptly
+#[derive(Clone)]
+struct EventSink {
+    tx: Sender<Event>,
+    ctx: egui::Context,
+}
+
+impl EventSink {
+    fn send(&self, event: Event) {
+        if self.tx.send(event).is_ok() {
+            self.ctx.request_repaint();
+        }
+    }
+}
+
+async fn run(mut commands: async_mpsc::UnboundedReceiver<Command>, events: EventSink) {
+    let cache = match PageCache::open() {
+        Ok(cache) => cache,
+        Err(e) => {
+            log::error!("Failed to open the page cache, pages won't be available offline: {e}");
+            match PageCache::in_memory() {
+                Ok(cache) => cache,
+                Err(e) => {
+                    events.send(Event::AuthFailed(format!("Failed to initialize: {e}")));
+                    return;
+                }
+            }
+        }
+    };
+
+    loop {
+        let (session, pub_storage) = match authenticate(&events).await {
+            Some(Connection::Online(session, pub_storage)) => (*session, pub_storage),
+            Some(Connection::Offline(pubky, own_pk)) => {
+                match serve_offline(&mut commands, &events, &cache, &pubky, &own_pk).await {
+                    OfflineEnd::Reconnected(session, pub_storage) => (*session, pub_storage),
+                    OfflineEnd::Reauthenticate => continue,
+                    OfflineEnd::Closed => return,
+                }
+            }
+            None => return,
+        };
+
+        let client = WikiClient::new(session, pub_storage, cache.clone());
+        sync_pending_writes(&client, &events).await;
+
+        let mut sync = tokio::time::interval(RECONNECT_INTERVAL);
+        // The first tick is immediate, and the queue was just synced
+        sync.tick().await;
+        let syncing = Arc::new(AtomicBool::new(false));
+
+        loop {
+            let command = tokio::select! {
+                command = commands.recv() => match command {
+                    Some(Command::Logout) => {
+                        client.sign_out().await;
+                        events.send(Event::LoggedOut);
+                        break;
+                    }
+                    Some(command) => command,
+                    None => return,
+                },
+                // Writes are queued when the homeserver can't be reached mid-session
+                _ = sync.tick() => {
+                    let queued = client.cache().pending(&client.own_pk()).is_ok_and(|pending| !pending.is_empty());
+                    if queued && !syncing.swap(true, Ordering::AcqRel) {
+                        let client = client.clone();
+                        let events = events.clone();
+                        let syncing = syncing.clone();
+                        tokio::spawn(async move {
+                            sync_pending_writes(&client, &events).await;
+                            syncing.store(false, Ordering::Release);
+                        });
+                    }
+                    continue;
+                }
+            };
+
+            let client = client.clone();
+            let events = events.clone();
+
+            // Each command runs in its own task, so a slow homeserver doesn't hold up the others
+            tokio::spawn(async move {
+                let event = execute(command, &client).await;
+                events.send(event);
+            });
+        }
+    }
+}
+
+async fn sync_pending_writes(client: &WikiClient, events: &EventSink) {
+    let result = client
+        .sync_pending_writes()
+        .await
+        .map_err(|e| format!("Failed to sync offline changes: {e}"));
+    events.send(Event::PendingWritesSynced(result));
+}
+
+/// How the worker talks to the homeserver after authenticating
+enum Connection {
+    Online(Box<PubkySession>, PublicStorage),
+    /// The stored session of `own_pk` could not be validated, as the homeserver is unreachable
+    Offline(Pubky, String),
+}
+
+/// Why the worker stopped serving commands from the cache
+enum OfflineEnd {
+    Reconnected(Box<PubkySession>, PublicStorage),
+    /// The stored session is gone, so a new auth flow is needed
+    Reauthenticate,
+    /// The UI was closed
+    Closed,
+}
+
+async fn authenticate(events: &EventSink) -> Option<Connection> {
+    let pubky = match Pubky::new() {
+        Ok(pubky) => pubky,
+        Err(e) => {
+            events.send(Event::AuthFailed(format!("Failed to initialize: {e}")));
+            return None;
+        }
+    };
+
+    // Reuse the session from a previous launch, if the homeserver still accepts it
+    match profile::restore_session(pubky.client()).await {
+        RestoredSession::Valid(session) => {
+            let own_pk = session.info().public_key().to_string();
+            log::info!("Restored session for {own_pk}");
+            events.send(Event::Authenticated {
+                own_pk,
+                offline: false,
+            });
+            let pub_storage = pubky.public_storage();
+            return Some(Connection::Online(session, pub_storage));
+        }
+        RestoredSession::Unreachable { public_key } => {
+            log::info!("Starting offline as {public_key}");
+            events.send(Event::Authenticated {
+                own_pk: public_key.clone(),
+                offline: true,
+            });
+            return Some(Connection::Offline(pubky, public_key));
+        }
+        RestoredSession::None => {}
+    }
+
+    let (flow, auth_url) = match initialize_auth(&pubky) {
+        Ok(res) => res,
+        Err(e) => {
+            events.send(Event::AuthFailed(format!("Failed to initialize: {e}")));
+            return None;
+        }
+    };
+    events.send(Event::AuthUrl(auth_url));
+
+    match flow.await_approval().await {
+        Ok(session) => {
+            if let Err(e) = profile::save_session(&session) {
+                log::error!("Failed to store session: {e}");
+            }
+            events.send(Event::Authenticated {
+                own_pk: session.info().public_key().to_string(),
+                offline: false,
+            });
+            Some(Connection::Online(Box::new(session), pubky.public_storage()))
+        }
+        Err(e) => {
+            events.send(Event::AuthFailed(format!("Authentication failed: {e}")));
+            None
+        }
+    }
+}
+
+/// Serve commands from the cache, and try to reach the homeserver again at regular intervals
+async fn serve_offline(
+    commands: &mut async_mpsc::UnboundedReceiver<Command>,
+    events: &EventSink,
+    cache: &PageCache,
+    pubky: &Pubky,
+    own_pk: &str,
+) -> OfflineEnd {
+    let mut reconnect = tokio::time::interval(RECONNECT_INTERVAL);
+    // The first tick is immediate, and the homeserver was just found unreachable
+    reconnect.tick().await;
+
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Logout) => {
+                    if let Err(e) = profile::clear_session() {
+                        log::error!("Failed to remove stored session: {e}");
+                    }
+                    events.send(Event::LoggedOut);
+                    return OfflineEnd::Reauthenticate;
+                }
+                Some(command) => events.send(execute_offline(command, own_pk, cache)),
+                None => return OfflineEnd::Closed,
+            },
+            _ = reconnect.tick() => match profile::restore_session(pubky.client()).await {
+                RestoredSession::Valid(session) => {
+                    log::info!("Homeserver is reachable again");
+                    events.send(Event::Authenticated {
+                        own_pk: own_pk.to_string(),
+                        offline: false,
+                    });
+                    return OfflineEnd::Reconnected(session, pubky.public_storage());
+                }
+                RestoredSession::Unreachable { .. } => {}
+                RestoredSession::None => return OfflineEnd::Reauthenticate,
+            },
+        }
+    }
+}
+
+async fn execute(command: Command, client: &WikiClient) -> Event {
+    match command {
+        Command::FetchFiles => Event::FilesFetched(client.own_page_titles().await),
+        Command::FetchPage { user_pk, page_id } => {
+            let result = client
+                .fetch_page(&user_pk, &page_id)
+                .await
+                .map_err(|e| e.to_string());
+            Event::PageFetched {
+                user_pk,
+                page_id,
+                result,
+            }
+        }
+        Command::FetchUpstream { user_pk, page_id } => {
+            let result = client
+                .fetch_page(&user_pk, &page_id)
+                .await
+                .map_err(|e| e.to_string());
+            Event::UpstreamFetched {
+                user_pk,
+                page_id,
+                result,
+            }
+        }
+        Command::FetchFollows => Event::FollowsFetched(client.followed_users().await),
+        Command::ResolveTitle { user_pk, title, heading } => {
+            let result = client
+                .find_page_by_title(&user_pk, &title)
+                .await
+                .map_err(|e| format!("Failed to find the page \"{title}\": {e}"));
+            Event::TitleResolved {
+                user_pk,
+                title,
+                heading,
+                result,
+            }
+        }
+        Command::DiscoverForks { page_id } => {
+            let fork_urls = client.discover_forks(&page_id).await;
+            Event::ForksDiscovered { page_id, fork_urls }
+        }
+        Command::FetchHistory { user_pk, page_id } => {
+            let result = client
+                .list_revisions(&user_pk, &page_id)
+                .await
+                .map_err(|e| format!("Failed to list revisions: {e}"));
+            Event::HistoryFetched {
+                user_pk,
+                page_id,
+                result,
+            }
+        }
+        Command::FetchRevision {
+            user_pk,
+            page_id,
+            revision,
+        } => {
+            let result = client
+                .fetch_revision(&user_pk, &page_id, &revision)
+                .await
+                .map_err(|e| format!("Failed to fetch revision: {e}"));
+            Event::RevisionFetched {
+                user_pk,
+                page_id,
+                revision,
+                result,
+            }
+        }
+        Command::FetchDiffSide { side } => {
+            let result = match &side {
+                DiffSide::Page { user_pk, page_id } => client
+                    .fetch_page(user_pk, page_id)
+                    .await
+                    .map_err(|e| e.to_string()),
+                DiffSide::Revision {
+                    user_pk,
+                    page_id,
+                    revision,
+                } => client
+                    .fetch_revision(user_pk, page_id, revision)
+                    .await
+                    .map_err(|e| format!("Failed to fetch revision: {e}")),
+            };
+            Event::DiffSideFetched { side, result }
+        }
+        Command::PrepareMerge { page_id, mine } => {
+            let result = client
+                .prepare_merge(&page_id, &mine)
+                .await
+                .map(Box::new)
+                .map_err(|e| format!("Failed to merge upstream changes: {e}"));
+            Event::MergePrepared { page_id, result }
+        }
+        Command::ProposeChanges { proposal } => {
+            let result = client
+                .propose_changes(&proposal)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("Failed to propose changes: {e}"));
+            Event::ChangesProposed(result)
+        }
+        Command::FetchInbox => Event::InboxFetched(client.inbox().await),
+        Command::PrepareProposalMerge { proposal } => {
+            let page_id = proposal.proposal.target_page_id.clone();
+            let result = client
+                .prepare_proposal_merge(proposal)
+                .await
+                .map(Box::new)
+                .map_err(|e| format!("Failed to merge proposed changes: {e}"));
+            Event::MergePrepared { page_id, result }
+        }
+        Command::DismissProposal { url } => {
+            let result = proposal::mark_proposal_handled(&url)
+                .map_err(|e| format!("Failed to dismiss proposal: {e}"));
+            Event::ProposalDismissed { url, result }
+        }
+        Command::BuildIndexes => {
+            let (search, links) = client.build_indexes().await;
+            Event::IndexesBuilt { search, links }
+        }
+        Command::CreatePage { page, filename } => match client.create_page(&page, filename.as_deref()).await {
+            Ok(path) => Event::PageCreated(Ok((path, page))),
+            Err(e) if StorageError::is_unreachable(&e) => queue_write(Command::CreatePage { page, filename }, client),
+            Err(e) => Event::PageCreated(Err(format!("Failed to create wiki post: {e}"))),
+        },
+        Command::UpdatePage {
+            page_id,
+            page,
+            base_version,
+        } => match client.update_page(&page_id, &page, base_version.clone()).await {
+            Ok(()) => Event::PageUpdated {
+                page_id,
+                result: Ok(page),
+            },
+            Err(e) if StorageError::is_unreachable(&e) => queue_write(
+                Command::UpdatePage {
+                    page_id,
+                    page,
+                    base_version,
+                },
+                client,
+            ),
+            Err(e) => match e.downcast::<UpdateConflict>() {
+                Ok(conflict) => {
+                    let merge = client.prepare_conflict_merge(&page_id, &page, conflict).await;
+                    Event::PageConflicted {
+                        page_id,
+                        merge: Box::new(merge),
+                    }
+                }
+                Err(e) => Event::PageUpdated {
+                    page_id,
+                    result: Err(format!("Failed to update wiki post: {e}")),
+                },
+            },
+        },
+        Command::DeletePage { page_id } => match client.delete_page(&page_id).await {
+            Ok(()) => Event::PageDeleted {
+                page_id,
+                result: Ok(()),
+            },
+            Err(e) if StorageError::is_unreachable(&e) => queue_write(Command::DeletePage { page_id }, client),
+            Err(e) => Event::PageDeleted {
+                page_id,
+                result: Err(format!("Failed to delete wiki post: {e}")),
+            },
+        },
+        Command::UploadAttachment { path } => {
+            let file_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let result = match read_attachment(&path) {
+                Ok(content) => client.upload_attachment(&file_name, content).await,
+                Err(e) => Err(e),
+            }
+            .map_err(|e| format!("Failed to attach {file_name}: {e}"));
+            Event::AttachmentUploaded { file_name, result }
+        }
+        Command::DownloadAttachment { url } => {
+            let result = match client.fetch_attachment(&url).await {
+                Ok(content) => save_download(attachment_name(&url), &content),
+                Err(e) => Err(e),
+            }
+            .map_err(|e| format!("Failed to download {url}: {e}"));
+            Event::AttachmentDownloaded(result)
+        }
+        command @ (Command::FetchDrafts | Command::SaveDraft { .. } | Command::DiscardDraft { .. }) => {
+            execute_local(command, &client.own_pk(), client.cache())
+        }
+        // Handled by the worker loop, as it ends the session
+        Command::Logout => unreachable!(),
+    }
+}
+
+/// Queue a write the homeserver could not be reached for, as if offline. It is synced with the
+/// other queued writes once the homeserver is reachable again.
+fn queue_write(command: Command, client: &WikiClient) -> Event {
+    log::warn!("Homeserver is unreachable, queueing the change to sync it later");
+    execute_offline(command, &client.own_pk(), client.cache())
+}
+
+/// Serve a command from the cache, queueing the writes until the homeserver is reachable again
+fn execute_offline(command: Command, own_pk: &str, cache: &PageCache) -> Event {
+    let cached_page = |user_pk: &str, page_id: &str| -> Result<WikiPage, String> {
+        match cache.get(user_pk, page_id) {
+            Ok(Some(cached)) => Ok(cached.page),
+            Ok(None) => Err(format!("{user_pk}/{page_id} is not available offline")),
+            Err(e) => Err(format!("Failed to read cached page: {e}")),
+        }
+    };
+    let unavailable = |what: &str| format!("{what} is not available offline");
+
+    match command {
+        Command::FetchFiles => Event::FilesFetched(cache.page_titles(own_pk)),
+        Command::FetchPage { user_pk, page_id } => {
+            let result = cached_page(&user_pk, &page_id);
+            Event::PageFetched {
+                user_pk,
+                page_id,
+                result,
+            }
+        }
+        Command::FetchUpstream { user_pk, page_id } => {
+            let result = cached_page(&user_pk, &page_id);
+            Event::UpstreamFetched {
+                user_pk,
+                page_id,
+                result,
+            }
+        }
+        // Follows are not cached, so `[[@alias/…]]` links stay unresolved offline
+        Command::FetchFollows => Event::FollowsFetched(vec![]),
+        Command::ResolveTitle { user_pk, title, heading } => {
+            let page_id = cache
+                .page_titles(&user_pk)
+                .into_iter()
+                .find(|(_, page_title)| same_title(page_title, &title))
+                .and_then(|(file_url, _)| file_url.split('/').next_back().map(str::to_string));
+            Event::TitleResolved {
+                user_pk,
+                title,
+                heading,
+                result: Ok(page_id),
+            }
+        }
+        Command::DiscoverForks { page_id } => {
+            let fork_urls = cache
+                .page_authors(&page_id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|user_pk| WikiLink::page_url(&user_pk, &page_id))
+                .collect();
+            Event::ForksDiscovered { page_id, fork_urls }
+        }
+        // Revisions are not cached, so the history is empty rather than failing on every page
+        Command::FetchHistory { user_pk, page_id } => Event::HistoryFetched {
+            user_pk,
+            page_id,
+            result: Ok(vec![]),
+        },
+        Command::FetchRevision {
+            user_pk,
+            page_id,
+            revision,
+        } => Event::RevisionFetched {
+            user_pk,
+            page_id,
+            revision,
+            result: Err(unavailable("History")),
+        },
+        Command::FetchDiffSide { side } => {
+            let result = match &side {
+                DiffSide::Page { user_pk, page_id } => cached_page(user_pk, page_id),
+                DiffSide::Revision { .. } => Err(unavailable("History")),
+            };
+            Event::DiffSideFetched { side, result }
+        }
+        Command::PrepareMerge { page_id, .. } => Event::MergePrepared {
+            page_id,
+            result: Err(unavailable("Merging")),
+        },
+        Command::PrepareProposalMerge { proposal } => Event::MergePrepared {
+            page_id: proposal.proposal.target_page_id,
+            result: Err(unavailable("Merging")),
+        },
+        Command::ProposeChanges { .. } => Event::ChangesProposed(Err(unavailable("Proposing changes"))),
+        Command::FetchInbox => Event::InboxFetched(vec![]),
+        Command::DismissProposal { url } => {
+            let result = proposal::mark_proposal_handled(&url)
+                .map_err(|e| format!("Failed to dismiss proposal: {e}"));
+            Event::ProposalDismissed { url, result }
+        }
+        Command::BuildIndexes => {
+            let mut search = SearchIndex::default();
+            let mut links = LinkGraph::default();
+            for (user_pk, page_id, page) in cache.all_pages().unwrap_or_default() {
+                search.insert(&user_pk, &page_id, &page);
+                links.insert(&user_pk, &page_id, &page);
+            }
+            Event::IndexesBuilt { search, links }
+        }
+        Command::CreatePage { page, filename } => {
+            let page_id = filename.unwrap_or_else(new_page_id);
+            let path = format!("/pub/wiki.app/{page_id}");
+            let result = cache
+                .queue(own_pk, &PendingWrite::Create { page_id, page: page.clone() })
+                .map(|_| (path, page))
+                .map_err(|e| format!("Failed to save wiki post offline: {e}"));
+            Event::PageCreated(result)
+        }
+        // Conflicts are checked against the base version once synced
+        Command::UpdatePage {
+            page_id,
+            page,
+            base_version,
+        } => {
+            let write = PendingWrite::Update {
+                page_id: page_id.clone(),
+                page: page.clone(),
+                base_version,
+            };
+            let result = cache
+                .queue(own_pk, &write)
+                .map(|_| page)
+                .map_err(|e| format!("Failed to save wiki post offline: {e}"));
+            Event::PageUpdated { page_id, result }
+        }
+        Command::DeletePage { page_id } => {
+            let write = PendingWrite::Delete {
+                page_id: page_id.clone(),
+            };
+            let result = cache
+                .queue(own_pk, &write)
+                .map_err(|e| format!("Failed to delete wiki post offline: {e}"));
+            Event::PageDeleted { page_id, result }
+        }
+        Command::UploadAttachment { path } => Event::AttachmentUploaded {
+            file_name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            result: Err(unavailable("Uploading files")),
+        },
+        Command::DownloadAttachment { .. } => {
+            Event::AttachmentDownloaded(Err(unavailable("Downloading files")))
+        }
+        command @ (Command::FetchDrafts | Command::SaveDraft { .. } | Command::DiscardDraft { .. }) => {
+            execute_local(command, own_pk, cache)
+        }
+        // Handled by the offline loop, as it ends the session
+        Command::Logout => unreachable!(),
+    }
+}
+
+/// Serve a command about the drafts, which are only kept locally, online and offline alike
+fn execute_local(command: Command, own_pk: &str, cache: &PageCache) -> Event {
+    match command {
+        Command::FetchDrafts => {
+            let result = cache
+                .drafts(own_pk)
+                .map_err(|e| format!("Failed to read drafts: {e}"));
+            Event::DraftsFetched(result)
+        }
+        Command::SaveDraft { draft } => {
+            let result = cache
+                .save_draft(own_pk, &draft)
+                .map_err(|e| format!("Failed to save draft: {e}"));
+            Event::DraftSaved(result)
+        }
+        Command::DiscardDraft { id } => {
+            let result = cache
+                .discard_draft(own_pk, &id)
+                .map_err(|e| format!("Failed to discard draft: {e}"));
+            Event::DraftDiscarded(result)
+        }
+        _ => unreachable!("Not a command about the drafts"),
+    }
+}
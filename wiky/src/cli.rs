@@ -0,0 +1,193 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use pubky::{Pubky, PubkySession};
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use wiki_core::{
+    cache::PageCache,
+    page::{parse_tags, WikiPage},
+    profile::{self, initialize_auth, RestoredSession},
+    storage::StorageError,
+    WikiClient,
+};
+
+/// Pubky Wiki. Starts the GUI when run without a subcommand.
+#[derive(Parser)]
+#[command(version)]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub(crate) command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum CliCommand {
+    /// List the pages of a user, the own pages by default
+    List {
+        /// Public key of the user
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Print the markdown body of a page
+    Get {
+        page_id: String,
+        /// Public key of the author, the own page by default
+        #[arg(long)]
+        user: Option<String>,
+        /// Print the page as stored, with its front-matter
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Create or update an own page, and print its ID
+    Put {
+        /// ID of the page to update or create, a new ID by default
+        page_id: Option<String>,
+        /// Markdown file to publish, stdin by default
+        #[arg(long)]
+        file: Option<PathBuf>,
+        /// Comma-separated tags
+        #[arg(long)]
+        tags: Option<String>,
+        /// Language of the page, like `en`
+        #[arg(long)]
+        language: Option<String>,
+    },
+    /// Delete an own page, along with its history
+    Delete { page_id: String },
+    /// List the followed users who have a version of a page
+    Forks { page_id: String },
+    /// Write the pages of a user to a folder, one `{page_id}.md` file per page
+    Export {
+        dir: PathBuf,
+        /// Public key of the user, the own pages by default
+        #[arg(long)]
+        user: Option<String>,
+    },
+}
+
+/// Run a subcommand, signing in first
+pub(crate) async fn run(command: CliCommand) -> Result<()> {
+    let pubky = Pubky::new()?;
+    let session = sign_in(&pubky).await?;
+    let cache = PageCache::open().or_else(|e| {
+        log::warn!("Failed to open the page cache: {e}");
+        PageCache::in_memory()
+    })?;
+    let client = WikiClient::new(session, pubky.public_storage(), cache);
+    let own_pk = client.own_pk();
+
+    match command {
+        CliCommand::List { user } => {
+            let user_pk = user.unwrap_or(own_pk);
+            for page_id in client.list_pages(&user_pk).await? {
+                let page = client.fetch_page(&user_pk, &page_id).await?;
+                println!("{page_id}\t{}", page.title());
+            }
+        }
+        CliCommand::Get { page_id, user, raw } => {
+            let user_pk = user.unwrap_or(own_pk);
+            let page = client.fetch_page(&user_pk, &page_id).await?;
+            if raw {
+                print!("{}", page.to_markdown()?);
+            } else {
+                print!("{}", page.body);
+            }
+        }
+        CliCommand::Put {
+            page_id,
+            file,
+            tags,
+            language,
+        } => {
+            let body = match file {
+                Some(path) => fs::read_to_string(path)?,
+                None => {
+                    let mut body = String::new();
+                    io::stdin().read_to_string(&mut body)?;
+                    body
+                }
+            };
+
+            let existing = match &page_id {
+                Some(page_id) => match client.fetch_page(&own_pk, page_id).await {
+                    Ok(page) => Some(page),
+                    // Only a page that doesn't exist yet is created under the given ID
+                    Err(e) if StorageError::is_not_found(&e) => None,
+                    Err(e) => return Err(e),
+                },
+                None => None,
+            };
+            let mut page = match &existing {
+                Some(existing) => {
+                    let mut page = existing.clone();
+                    page.set_body(body);
+                    page
+                }
+                None => WikiPage::new(body),
+            };
+            if let Some(tags) = tags {
+                page.meta.tags = parse_tags(&tags);
+            }
+            if let Some(language) = language {
+                let language = language.trim();
+                page.meta.language = (!language.is_empty()).then(|| language.to_string());
+            }
+
+            match (page_id, existing) {
+                (Some(page_id), Some(_)) => {
+                    client.update_page(&page_id, &page, None).await?;
+                    println!("{page_id}");
+                }
+                (page_id, _) => {
+                    let path = client.create_page(&page, page_id.as_deref()).await?;
+                    println!("{}", path.split('/').next_back().unwrap_or(&path));
+                }
+            }
+        }
+        CliCommand::Delete { page_id } => {
+            client.delete_page(&page_id).await?;
+        }
+        CliCommand::Forks { page_id } => {
+            for fork_url in client.discover_forks(&page_id).await {
+                println!("{fork_url}");
+            }
+        }
+        CliCommand::Export { dir, user } => {
+            let user_pk = user.unwrap_or(own_pk);
+            fs::create_dir_all(&dir)?;
+
+            let page_ids = client.list_pages(&user_pk).await?;
+            for page_id in &page_ids {
+                let page = client.fetch_page(&user_pk, page_id).await?;
+                fs::write(dir.join(format!("{page_id}.md")), page.to_markdown()?)?;
+            }
+            eprintln!("Exported {} pages to {}", page_ids.len(), dir.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Restore the stored session, or sign in by showing the auth URL and its QR code in the terminal
+async fn sign_in(pubky: &Pubky) -> Result<PubkySession> {
+    match profile::restore_session(pubky.client()).await {
+        RestoredSession::Valid(session) => return Ok(*session),
+        RestoredSession::Unreachable { .. } => {
+            return Err(anyhow!("The homeserver can't be reached"));
+        }
+        RestoredSession::None => {}
+    }
+
+    let (flow, auth_url) = initialize_auth(pubky)?;
+    let qr = QrCode::new(auth_url.as_bytes())?.render::<Dense1x2>().quiet_zone(true).build();
+    eprintln!("Scan this QR code with your Pubky app to login:\n\n{qr}\n\nor open: {auth_url}\n");
+
+    let session = flow.await_approval().await?;
+    profile::save_session(&session)?;
+
+    Ok(session)
+}
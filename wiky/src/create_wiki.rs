@@ -1,67 +1,30 @@
-use crate::{create_wiki_post, utils::extract_title, AuthState, PubkyApp, ViewState};
+use crate::{backend::Command, editor::show_editor, utils::show_metadata_fields, PubkyApp};
 
 use eframe::egui::{Context, Ui};
-use pubky::PubkySession;
 
-pub(crate) fn update(app: &mut PubkyApp, session: &PubkySession, _ctx: &Context, ui: &mut Ui) {
+pub(crate) fn update(app: &mut PubkyApp, own_pk: &str, _ctx: &Context, ui: &mut Ui) {
     ui.label(egui::RichText::new("Create New Wiki Page").size(20.0).strong());
     ui.add_space(25.0);
 
-    // Textarea for wiki content
-    ui.label(egui::RichText::new("Content:").size(16.0));
-    ui.add_space(12.0);
+    show_editor(app, own_pk, ui);
 
-    egui::ScrollArea::vertical()
-        .max_height(400.0)
-        .show(ui, |ui| {
-            ui.add(
-                egui::TextEdit::multiline(&mut app.edit_wiki_content)
-                    .desired_width(f32::INFINITY)
-                    .desired_rows(15)
-                    .font(egui::TextStyle::Monospace),
-            );
-        });
+    ui.add_space(12.0);
+    show_metadata_fields(ui, &mut app.edit_wiki_tags, &mut app.edit_wiki_language);
 
     ui.add_space(25.0);
 
     ui.horizontal(|ui| {
+        if app.saving {
+            ui.disable();
+        }
+
         // Save button for creating new page
         let save_button = ui.add_sized(
             [120.0, 35.0],
             egui::Button::new(egui::RichText::new("💾 Save").size(15.0))
         );
         if save_button.clicked() {
-            let session_clone = session.clone();
-            let content = app.edit_wiki_content.clone();
-            let state_clone = app.state.clone();
-            let filename = app.forked_from_page_id.as_deref();
-
-            let create_wiki_post_fut = create_wiki_post(&session_clone, &content, filename);
-            match app.rt.block_on(create_wiki_post_fut) {
-                Ok(wiki_page_path) => {
-                    log::info!("Created wiki post at: {}", wiki_page_path);
-
-                    // Convert path to pubky URL format for the file_cache list
-                    if let Ok(mut state) = state_clone.lock() {
-                        if let AuthState::Authenticated {
-                            ref session,
-                            ref mut file_cache,
-                            ..
-                        } = *state
-                        {
-                            let own_user_pk = session.info().public_key().to_string();
-                            let file_url = format!("pubky://{own_user_pk}{wiki_page_path}");
-                            let file_title = extract_title(&content);
-                            file_cache.insert(file_url, file_title.into());
-                        }
-                    }
-                }
-                Err(e) => log::error!("Failed to create wiki post: {e}"),
-            }
-
-            app.edit_wiki_content.clear();
-            app.forked_from_page_id = None;
-            app.view_state = ViewState::WikiList;
+            save(app);
         }
 
         ui.add_space(10.0);
@@ -70,9 +33,37 @@ pub(crate) fn update(app: &mut PubkyApp, session: &PubkySession, _ctx: &Context,
             egui::Button::new(egui::RichText::new("Cancel").size(15.0))
         );
         if cancel_button.clicked() {
-            app.edit_wiki_content.clear();
-            app.forked_from_page_id = None;
-            app.view_state = ViewState::WikiList;
+            // The changes stay in Drafts
+            app.save_draft();
+            app.navigate_to_wiki_list();
+        }
+
+        if app.saving {
+            ui.add_space(10.0);
+            ui.spinner();
         }
     });
+
+    if app.save_failed && !app.saving {
+        ui.add_space(10.0);
+        ui.horizontal(|ui| {
+            ui.colored_label(egui::Color32::from_rgb(200, 80, 80), "⚠ Not saved, your changes are kept in Drafts");
+            if ui.button("⟳ Retry").clicked() {
+                save(app);
+            }
+        });
+    }
+}
+
+fn save(app: &mut PubkyApp) {
+    let page = app.editor_page();
+
+    // A fork keeps the page id of the original, so it can be discovered as a fork
+    let filename = app.forked_from.as_ref().map(|f| f.page_id.clone());
+
+    // Kept until the page is created, in case the app closes before
+    app.save_draft();
+    app.save_failed = false;
+    app.saving = true;
+    app.backend.send(Command::CreatePage { page, filename });
 }
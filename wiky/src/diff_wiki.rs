@@ -0,0 +1,114 @@
+use crate::{PubkyApp, ViewState};
+
+use eframe::egui::{Context, Ui};
+use egui::{text::LayoutJob, Color32, FontId, TextFormat};
+use wiki_core::diff::{line_stats, side_by_side, Segment};
+
+const DELETED_COLOR: Color32 = Color32::from_rgb(200, 80, 80);
+const ADDED_COLOR: Color32 = Color32::from_rgb(80, 160, 80);
+
+pub(crate) fn update(app: &mut PubkyApp, _own_pk: &str, _ctx: &Context, ui: &mut Ui) {
+    ui.label(egui::RichText::new("Compare Versions").size(20.0).strong());
+    ui.add_space(25.0);
+
+    if let [(left_side, left_page), (right_side, right_page)] = app.diff_sides.as_slice() {
+        ui.columns(2, |columns| {
+            columns[0].label(egui::RichText::new(left_side.label()).monospace().color(DELETED_COLOR));
+            columns[1].label(egui::RichText::new(right_side.label()).monospace().color(ADDED_COLOR));
+        });
+        ui.add_space(10.0);
+
+        match (left_page, right_page) {
+            (Some(left), Some(right)) => {
+                let (added, deleted) = line_stats(&left.body, &right.body);
+                ui.label(format!("{added} line(s) added, {deleted} line(s) deleted"));
+                ui.add_space(10.0);
+                ui.separator();
+
+                show_rows(ui, &left.body, &right.body);
+            }
+            _ => {
+                ui.spinner();
+            }
+        }
+    }
+
+    ui.add_space(25.0);
+
+    ui.horizontal(|ui| {
+        let swap_button = ui.add_sized(
+            [120.0, 35.0],
+            egui::Button::new(egui::RichText::new("⇄ Swap").size(15.0))
+        );
+        if swap_button.clicked() {
+            app.diff_sides.reverse();
+        }
+
+        ui.add_space(10.0);
+        let back_button = ui.add_sized(
+            [120.0, 35.0],
+            egui::Button::new(egui::RichText::new("← Back").size(15.0))
+        );
+        if back_button.clicked() {
+            app.diff_sides.clear();
+            app.view_state = ViewState::ViewWiki;
+        }
+    });
+}
+
+/// Both versions side by side, with their changes highlighted
+pub(crate) fn show_rows(ui: &mut Ui, old: &str, new: &str) {
+    let rows = side_by_side(old, new);
+    let column_width = (ui.available_width() - ui.spacing().item_spacing.x) / 2.0;
+
+    egui::ScrollArea::vertical()
+        .max_height(450.0)
+        .show(ui, |ui| {
+            egui::Grid::new("diff_rows")
+                .num_columns(2)
+                .min_col_width(column_width)
+                .max_col_width(column_width)
+                .show(ui, |ui| {
+                    for row in rows {
+                        show_side(ui, row.left.as_deref(), DELETED_COLOR, column_width);
+                        show_side(ui, row.right.as_deref(), ADDED_COLOR, column_width);
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+/// One side of a row. Changed lines get a light background, changed words a strong one.
+fn show_side(ui: &mut Ui, segments: Option<&[Segment]>, highlight: Color32, width: f32) {
+    let Some(segments) = segments else {
+        ui.label("");
+        return;
+    };
+
+    let line_changed = segments.iter().any(|segment| segment.changed);
+    let mut job = LayoutJob::default();
+    job.wrap.max_width = width;
+
+    for segment in segments {
+        let background = if segment.changed {
+            highlight.gamma_multiply(0.6)
+        } else if line_changed {
+            highlight.gamma_multiply(0.2)
+        } else {
+            Color32::TRANSPARENT
+        };
+
+        job.append(
+            &segment.text,
+            0.0,
+            TextFormat {
+                font_id: FontId::monospace(13.0),
+                color: ui.visuals().text_color(),
+                background,
+                ..Default::default()
+            },
+        );
+    }
+
+    ui.label(job);
+}
@@ -0,0 +1,201 @@
+use std::time::{Duration, Instant};
+
+use crate::{backend::Command, PubkyApp, ViewState};
+
+use eframe::egui::{Context, Ui};
+use egui::CollapsingHeader;
+use wiki_core::{
+    cache::Draft,
+    page::{format_timestamp, now},
+    storage::new_page_id,
+};
+
+/// How often the editor is checked for changes to save in its draft
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+impl PubkyApp {
+    /// Start a draft when the editor opens, and save it every few seconds while it changes
+    pub(crate) fn autosave_draft(&mut self, ctx: &Context) {
+        if self.draft_id.is_none() {
+            self.draft_id = Some(new_page_id());
+            self.draft_snapshot = self.editor_snapshot();
+            self.draft_checked_at = Instant::now();
+        }
+
+        // Saving is held back while a save is in flight, so the draft is not written after it is discarded
+        if !self.saving && self.draft_checked_at.elapsed() >= AUTOSAVE_INTERVAL {
+            self.save_draft();
+        }
+        ctx.request_repaint_after(AUTOSAVE_INTERVAL);
+    }
+
+    /// Save the draft of the editor, if it changed since it was last saved. Returns whether it was saved.
+    pub(crate) fn save_draft(&mut self) -> bool {
+        self.draft_checked_at = Instant::now();
+        let Some(id) = self.draft_id.clone() else {
+            return false;
+        };
+        let snapshot = self.editor_snapshot();
+        if snapshot == self.draft_snapshot {
+            return false;
+        }
+
+        let editing = self.view_state == ViewState::EditWiki;
+        let draft = Draft {
+            id,
+            page_id: editing.then(|| self.selected_wiki_page_id.clone()),
+            base_version: self.edit_base_version.clone().filter(|_| editing),
+            page: self.editor_page(),
+            saved_at: now(),
+        };
+        self.draft_snapshot = snapshot;
+        self.drafts.retain(|saved| saved.id != draft.id);
+        self.drafts.insert(0, draft.clone());
+        self.backend.send(Command::SaveDraft { draft });
+
+        true
+    }
+
+    pub(crate) fn discard_draft(&mut self, id: &str) {
+        self.drafts.retain(|draft| draft.id != id);
+        self.backend.send(Command::DiscardDraft { id: id.to_string() });
+    }
+
+    /// Open a draft in the editor it was written in
+    pub(crate) fn restore_draft(&mut self, own_pk: &str, draft: &Draft) {
+        self.clear_editor();
+        self.clear_selected_wiki_page();
+
+        let meta = &draft.page.meta;
+        self.edit_wiki_content = draft.page.body.clone();
+        self.edit_wiki_tags = meta.tags.join(", ");
+        self.edit_wiki_language = meta.language.clone().unwrap_or_default();
+        match &draft.page_id {
+            Some(page_id) => {
+                self.selected_wiki_user_id = own_pk.to_string();
+                self.selected_wiki_page_id = page_id.clone();
+                self.selected_wiki_page = draft.page.clone();
+                self.edit_base_version = draft.base_version.clone();
+                self.view_state = ViewState::EditWiki;
+            }
+            None => {
+                self.forked_from = meta.forked_from.clone();
+                self.view_state = ViewState::CreateWiki;
+            }
+        }
+
+        // Saving again goes to the same draft
+        self.draft_id = Some(draft.id.clone());
+        self.draft_snapshot = self.editor_snapshot();
+        self.draft_checked_at = Instant::now();
+        self.offer_draft_recovery = false;
+    }
+
+    /// What is compared to tell if the editor changed since its draft was saved
+    fn editor_snapshot(&self) -> (String, String, String) {
+        (
+            self.edit_wiki_content.clone(),
+            self.edit_wiki_tags.clone(),
+            self.edit_wiki_language.clone(),
+        )
+    }
+}
+
+fn draft_title(draft: &Draft) -> &str {
+    match draft.page.title() {
+        "" => "Untitled",
+        title => title,
+    }
+}
+
+/// Unsaved changes kept locally, with ways to go on writing or to throw them away
+pub(crate) fn show_drafts(app: &mut PubkyApp, ui: &mut Ui, own_pk: &str) {
+    if app.drafts.is_empty() {
+        return;
+    }
+
+    let drafts = app.drafts.clone();
+    CollapsingHeader::new(egui::RichText::new(format!("📝 Drafts ({})", drafts.len())).size(15.0))
+        .default_open(true)
+        .show(ui, |ui| {
+            for draft in drafts {
+                egui::Frame::group(ui.style()).show(ui, |ui| {
+                    let kind = match (&draft.page_id, &draft.page.meta.forked_from) {
+                        (Some(_), _) => "Changes to a page",
+                        (None, Some(_)) => "New fork",
+                        (None, None) => "New page",
+                    };
+                    ui.label(egui::RichText::new(draft_title(&draft)).strong());
+                    ui.label(format!("{kind}, saved on {}", format_timestamp(draft.saved_at)));
+
+                    ui.horizontal(|ui| {
+                        if ui.button("✏ Restore").clicked() {
+                            app.restore_draft(own_pk, &draft);
+                        }
+                        if ui.button("🗑 Discard").clicked() {
+                            app.discard_draft(&draft.id);
+                        }
+                    });
+                });
+            }
+        });
+}
+
+/// Other drafts of the page being edited, which saving the editor would overwrite
+pub(crate) fn show_page_drafts(app: &mut PubkyApp, ui: &mut Ui, own_pk: &str) {
+    let page_drafts: Vec<Draft> = app
+        .drafts
+        .iter()
+        .filter(|draft| {
+            draft.page_id.as_ref() == Some(&app.selected_wiki_page_id) && app.draft_id.as_ref() != Some(&draft.id)
+        })
+        .cloned()
+        .collect();
+
+    for draft in page_drafts {
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "📝 This page has unsaved changes from {}",
+                format_timestamp(draft.saved_at)
+            ));
+            if ui.small_button("✏ Restore").clicked() {
+                app.restore_draft(own_pk, &draft);
+            }
+            if ui.small_button("🗑 Discard").clicked() {
+                app.discard_draft(&draft.id);
+            }
+        });
+        ui.add_space(10.0);
+    }
+}
+
+/// Offer to go on with the drafts left by a previous session, which may have ended in a crash
+pub(crate) fn show_draft_recovery(app: &mut PubkyApp, ctx: &Context, own_pk: &str) {
+    let Some(latest) = app.drafts.first().cloned() else {
+        app.offer_draft_recovery = false;
+        return;
+    };
+
+    egui::Window::new("Restore unsaved changes?")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            let count = app.drafts.len();
+            ui.label(match count {
+                1 => "Changes from your last session were not saved.".to_string(),
+                _ => format!("Changes to {count} pages from your last sessions were not saved."),
+            });
+            ui.label(egui::RichText::new("They are kept in Drafts until you save or discard them.").small());
+            ui.add_space(10.0);
+
+            ui.horizontal(|ui| {
+                if ui.button(format!("✏ Restore \"{}\"", draft_title(&latest))).clicked() {
+                    app.restore_draft(own_pk, &latest);
+                }
+                if ui.button("Later").clicked() {
+                    app.offer_draft_recovery = false;
+                }
+            });
+        });
+}
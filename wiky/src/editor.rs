@@ -0,0 +1,431 @@
+use std::{ops::Range, path::PathBuf};
+
+use crate::{backend::Command, AuthState, PubkyApp};
+
+use eframe::egui::{Context, Ui};
+use egui::{
+    scroll_area::ScrollAreaOutput,
+    text::{CCursor, CCursorRange},
+    Key, KeyboardShortcut, Modifiers,
+};
+use egui_commonmark::CommonMarkViewer;
+use wiki_core::{
+    attachment::MAX_ATTACHMENT_SIZE,
+    format::{apply_format, insert_text, Format},
+    links::WikiLink,
+};
+
+const EDITOR_HEIGHT: f32 = 400.0;
+/// Results listed by the "Link to page" picker
+const PICKER_RESULTS: usize = 20;
+/// Scrolling less than this, as a fraction of the scrollable height, is not passed on
+const SYNC_TOLERANCE: f32 = 0.001;
+
+/// What the create and edit views show of the page being written
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum EditorMode {
+    #[default]
+    Source,
+    /// Source and rendered page side by side, scrolled together
+    Split,
+    Preview,
+}
+
+/// Scroll positions of the source and the preview, as fractions of how far they can scroll, to
+/// keep them in step in the split mode
+#[derive(Default)]
+pub(crate) struct ScrollSync {
+    source: f32,
+    preview: f32,
+    /// Offsets to apply on the next frame, after the other side was scrolled
+    source_offset: Option<f32>,
+    preview_offset: Option<f32>,
+}
+
+impl ScrollSync {
+    /// Follow whichever side was scrolled since the last frame
+    fn update<R>(&mut self, source: &ScrollAreaOutput<R>, preview: &ScrollAreaOutput<R>) {
+        let source_fraction = scroll_fraction(source);
+        let preview_fraction = scroll_fraction(preview);
+
+        // A side that can't scroll stays where it is, without dragging the other one back
+        if (source_fraction - self.source).abs() > SYNC_TOLERANCE && max_offset(preview) > 0.0 {
+            self.preview_offset = Some(source_fraction * max_offset(preview));
+            self.preview = source_fraction;
+        } else if (preview_fraction - self.preview).abs() > SYNC_TOLERANCE && max_offset(source) > 0.0 {
+            self.source_offset = Some(preview_fraction * max_offset(source));
+            self.source = preview_fraction;
+            return;
+        } else {
+            self.preview = preview_fraction;
+        }
+        self.source = source_fraction;
+    }
+}
+
+fn max_offset<R>(output: &ScrollAreaOutput<R>) -> f32 {
+    (output.content_size.y - output.inner_rect.height()).max(0.0)
+}
+
+fn scroll_fraction<R>(output: &ScrollAreaOutput<R>) -> f32 {
+    let max = max_offset(output);
+    if max <= 0.0 {
+        return 0.0;
+    }
+
+    (output.state.offset.y / max).clamp(0.0, 1.0)
+}
+
+/// Content of the page being written, in the mode picked above it, shared by the create and edit views
+pub(crate) fn show_editor(app: &mut PubkyApp, own_pk: &str, ui: &mut Ui) {
+    app.autosave_draft(ui.ctx());
+
+    ui.horizontal(|ui| {
+        ui.label(egui::RichText::new("Content:").size(16.0));
+        ui.add_space(10.0);
+        ui.selectable_value(&mut app.editor_mode, EditorMode::Source, "Source");
+        ui.selectable_value(&mut app.editor_mode, EditorMode::Split, "Split");
+        ui.selectable_value(&mut app.editor_mode, EditorMode::Preview, "Preview");
+    });
+    ui.add_space(12.0);
+
+    if app.editor_mode != EditorMode::Preview {
+        let format = show_toolbar(app, ui).or_else(|| format_shortcut(app, ui.ctx()));
+        if let Some(format) = format {
+            let selection = app.source_selection();
+            let selection = apply_format(&mut app.edit_wiki_content, selection, format);
+            select(app, ui.ctx(), selection);
+        }
+        ui.add_space(8.0);
+    }
+
+    attach_dropped_files(app, ui);
+    insert_uploaded_attachments(app, ui.ctx());
+
+    match app.editor_mode {
+        EditorMode::Source => {
+            show_source(app, ui);
+        }
+        EditorMode::Preview => {
+            show_preview(app, own_pk, ui);
+        }
+        EditorMode::Split => {
+            ui.columns(2, |columns| {
+                let source = show_source(app, &mut columns[0]);
+                let preview = show_preview(app, own_pk, &mut columns[1]);
+                app.editor_scroll.update(&source, &preview);
+            });
+        }
+    }
+
+    show_link_picker(app, own_pk, ui.ctx());
+    show_attach_file(app, ui.ctx());
+}
+
+impl PubkyApp {
+    /// Upload a file to the homeserver, its markdown is inserted once it is uploaded
+    fn attach_file(&mut self, path: PathBuf) {
+        self.attachments_uploading += 1;
+        self.backend.send(Command::UploadAttachment { path });
+    }
+
+    /// Last selection of the source, or the end of it if it was never focused
+    fn source_selection(&self) -> Range<usize> {
+        self.editor_selection.clone().unwrap_or_else(|| {
+            let end = self.edit_wiki_content.chars().count();
+            end..end
+        })
+    }
+}
+
+fn source_id() -> egui::Id {
+    egui::Id::new("editor_source_text")
+}
+
+/// Buttons for the formatting, with their shortcuts on hover
+fn show_toolbar(app: &mut PubkyApp, ui: &mut Ui) -> Option<Format> {
+    let mut format = None;
+    ui.horizontal_wrapped(|ui| {
+        let mut button = |ui: &mut Ui, text: egui::RichText, hint: &str, button_format: Format| {
+            if ui.button(text).on_hover_text(hint).clicked() {
+                format = Some(button_format);
+            }
+        };
+
+        button(ui, egui::RichText::new("H1").strong(), "Heading (Ctrl+1)", Format::Heading(1));
+        button(ui, egui::RichText::new("H2").strong(), "Heading (Ctrl+2)", Format::Heading(2));
+        button(ui, egui::RichText::new("H3").strong(), "Heading (Ctrl+3)", Format::Heading(3));
+        ui.separator();
+        button(ui, egui::RichText::new("B").strong(), "Bold (Ctrl+B)", Format::Bold);
+        button(ui, egui::RichText::new("I").italics(), "Italic (Ctrl+I)", Format::Italic);
+        button(ui, egui::RichText::new("</>").monospace(), "Inline code (Ctrl+E)", Format::InlineCode);
+        ui.separator();
+        button(ui, egui::RichText::new("• List"), "Bulleted list", Format::BulletList);
+        button(ui, egui::RichText::new("1. List"), "Numbered list", Format::NumberedList);
+        button(ui, egui::RichText::new("Code block"), "Code block", Format::CodeBlock);
+        button(ui, egui::RichText::new("Table"), "Table", Format::Table);
+        ui.separator();
+        button(ui, egui::RichText::new("🔗 Link"), "Link (Ctrl+K)", Format::Link);
+
+        if ui.button("📄 Link to page").on_hover_text("Link to one of your pages or your follows' pages (Ctrl+Shift+K)").clicked() {
+            app.link_picker_query = Some(String::new());
+        }
+        if ui.button("📎 Attach file").on_hover_text("Upload an image or a file, or drop it on the window").clicked() {
+            match (app.pick_attachment)() {
+                Some(path) => app.attach_file(path),
+                // Cancelled, or no file dialog could be shown, so the path can be typed instead
+                None => app.attach_file_path = Some(String::new()),
+            }
+        }
+    });
+
+    format
+}
+
+/// Formatting shortcuts, while the source has the focus
+fn format_shortcut(app: &mut PubkyApp, ctx: &Context) -> Option<Format> {
+    if !ctx.memory(|m| m.has_focus(source_id())) {
+        return None;
+    }
+
+    // Checked before Ctrl+K, which it includes
+    let picker = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::K);
+    if ctx.input_mut(|i| i.consume_shortcut(&picker)) {
+        app.link_picker_query = Some(String::new());
+        return None;
+    }
+
+    let shortcuts = [
+        (Key::Num1, Format::Heading(1)),
+        (Key::Num2, Format::Heading(2)),
+        (Key::Num3, Format::Heading(3)),
+        (Key::B, Format::Bold),
+        (Key::I, Format::Italic),
+        (Key::E, Format::InlineCode),
+        (Key::K, Format::Link),
+    ];
+    shortcuts.into_iter().find_map(|(key, format)| {
+        let shortcut = KeyboardShortcut::new(Modifiers::COMMAND, key);
+        ctx.input_mut(|i| i.consume_shortcut(&shortcut)).then_some(format)
+    })
+}
+
+/// Select characters of the source, and give it the focus back
+fn select(app: &mut PubkyApp, ctx: &Context, selection: Range<usize>) {
+    let mut state = egui::TextEdit::load_state(ctx, source_id()).unwrap_or_default();
+    state.cursor.set_char_range(Some(CCursorRange::two(
+        CCursor::new(selection.start),
+        CCursor::new(selection.end),
+    )));
+    state.store(ctx, source_id());
+    ctx.memory_mut(|m| m.request_focus(source_id()));
+
+    app.editor_selection = Some(selection);
+}
+
+/// Pages to link to, searched among the own pages and the pages of the follows, inserting a
+/// link to the page picked at the selection
+fn show_link_picker(app: &mut PubkyApp, own_pk: &str, ctx: &Context) {
+    let Some(mut query) = app.link_picker_query.take() else {
+        return;
+    };
+
+    // Own pages until something is typed, then the search results
+    let candidates: Vec<(String, String, String)> = if query.trim().is_empty() {
+        let mut own_pages: Vec<(String, String, String)> = match &app.state {
+            AuthState::Authenticated { file_cache, .. } => file_cache
+                .iter()
+                .filter_map(|(file_url, title)| match WikiLink::parse(file_url) {
+                    Some(WikiLink::Page { user_pk, page_id, .. }) => Some((user_pk, page_id, title.clone())),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+        own_pages.sort_by_key(|(_, _, title)| title.to_lowercase());
+        own_pages
+    } else {
+        app.search_index
+            .search(&query)
+            .into_iter()
+            .map(|hit| (hit.user_pk, hit.page_id, hit.title))
+            .collect()
+    };
+
+    let mut open = true;
+    let mut picked = None;
+    egui::Window::new("Link to page")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            let search = ui.add(
+                egui::TextEdit::singleline(&mut query)
+                    .hint_text("Search your pages and your follows' pages")
+                    .desired_width(350.0),
+            );
+            if !search.has_focus() && query.is_empty() {
+                search.request_focus();
+            }
+            ui.add_space(8.0);
+
+            if candidates.is_empty() {
+                ui.label(egui::RichText::new("No pages found").italics().color(egui::Color32::GRAY));
+            }
+            egui::ScrollArea::vertical().max_height(250.0).show(ui, |ui| {
+                for (user_pk, page_id, title) in candidates.iter().take(PICKER_RESULTS) {
+                    ui.horizontal(|ui| {
+                        if ui.link(title).clicked() {
+                            picked = Some((user_pk.clone(), page_id.clone(), title.clone()));
+                        }
+                        ui.label(egui::RichText::new(app.author_name(own_pk, user_pk)).small().weak());
+                    });
+                }
+            });
+        });
+
+    match picked {
+        Some((user_pk, page_id, title)) => {
+            // The selected text, if any, becomes the text of the link
+            let selection = app.source_selection();
+            let selected: String = app
+                .edit_wiki_content
+                .chars()
+                .skip(selection.start)
+                .take(selection.end - selection.start)
+                .collect();
+            let label = if selected.is_empty() { title } else { selected };
+            let link = format!("[{label}]({})", WikiLink::page_url(&user_pk, &page_id));
+
+            let selection = insert_text(&mut app.edit_wiki_content, selection, &link);
+            select(app, ctx, selection);
+        }
+        None if open => app.link_picker_query = Some(query),
+        None => {}
+    }
+}
+
+/// Upload the files dropped on the window, with a hint while they are dragged over it
+fn attach_dropped_files(app: &mut PubkyApp, ui: &mut Ui) {
+    let (hovered, dropped) = ui.ctx().input(|i| (!i.raw.hovered_files.is_empty(), i.raw.dropped_files.clone()));
+
+    for file in dropped {
+        match file.path {
+            Some(path) => app.attach_file(path),
+            None => log::warn!("Dropped file {} has no path, it can't be attached", file.name),
+        }
+    }
+
+    if hovered {
+        ui.label(egui::RichText::new("📎 Drop to attach to the page").strong());
+    }
+    if app.attachments_uploading > 0 {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label(format!("Uploading {} file(s)…", app.attachments_uploading));
+        });
+    }
+}
+
+/// Insert the markdown of the files uploaded since the last frame at the selection
+fn insert_uploaded_attachments(app: &mut PubkyApp, ctx: &Context) {
+    for markdown in std::mem::take(&mut app.uploaded_attachments) {
+        let selection = app.source_selection();
+        let selection = insert_text(&mut app.edit_wiki_content, selection, &markdown);
+        select(app, ctx, selection);
+    }
+}
+
+/// Ask for the file to attach with the native file dialog of the system, which blocks the UI until
+/// it is closed. `None` if it was cancelled or could not be shown.
+pub(crate) fn pick_attachment() -> Option<PathBuf> {
+    rfd::FileDialog::new().set_title("Attach file").pick_file()
+}
+
+/// Fallback for [`pick_attachment`], to type the path of the file to attach
+fn show_attach_file(app: &mut PubkyApp, ctx: &Context) {
+    let Some(mut path) = app.attach_file_path.take() else {
+        return;
+    };
+
+    let mut open = true;
+    let mut attach = false;
+    egui::Window::new("Attach file")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            let field = ui.add(
+                egui::TextEdit::singleline(&mut path)
+                    .hint_text("Path of the image or file")
+                    .desired_width(350.0),
+            );
+            if !field.has_focus() && path.is_empty() {
+                field.request_focus();
+            }
+            let entered = field.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+            ui.label(
+                egui::RichText::new(format!(
+                    "Images are shown in the page, other files are linked. Up to {} MB.",
+                    MAX_ATTACHMENT_SIZE / (1024 * 1024)
+                ))
+                .small(),
+            );
+            ui.add_space(8.0);
+
+            let path_given = !path.trim().is_empty();
+            if ui.add_enabled(path_given, egui::Button::new("📎 Attach")).clicked() || (entered && path_given) {
+                attach = true;
+            }
+        });
+
+    if attach {
+        app.attach_file(PathBuf::from(path.trim()));
+    } else if open {
+        app.attach_file_path = Some(path);
+    }
+}
+
+fn show_source(app: &mut PubkyApp, ui: &mut Ui) -> ScrollAreaOutput<()> {
+    let mut area = egui::ScrollArea::vertical().id_salt("editor_source").max_height(EDITOR_HEIGHT);
+    if let Some(offset) = app.editor_scroll.source_offset.take() {
+        area = area.vertical_scroll_offset(offset);
+    }
+
+    area.show(ui, |ui| {
+        let output = egui::TextEdit::multiline(&mut app.edit_wiki_content)
+            .id(source_id())
+            .desired_width(f32::INFINITY)
+            .desired_rows(15)
+            .font(egui::TextStyle::Monospace)
+            .show(ui);
+        if let Some(range) = output.cursor_range {
+            app.editor_selection = Some(range.as_sorted_char_range());
+        }
+    })
+}
+
+fn show_preview(app: &mut PubkyApp, own_pk: &str, ui: &mut Ui) -> ScrollAreaOutput<()> {
+    let mut area = egui::ScrollArea::vertical()
+        .id_salt("editor_preview")
+        .max_height(EDITOR_HEIGHT)
+        .auto_shrink([false, true]);
+    if let Some(offset) = app.editor_scroll.preview_offset.take() {
+        area = area.vertical_scroll_offset(offset);
+    }
+
+    let body = app.expand_title_links(own_pk, &app.edit_wiki_content);
+    let output = area.show(ui, |ui| {
+        CommonMarkViewer::new().max_image_width(Some(512)).show(ui, &mut app.cache, &body);
+    });
+
+    // Links are not followed while writing, which would leave the editor
+    ui.ctx().output_mut(|o| {
+        o.commands
+            .retain(|cmd| !matches!(cmd, egui::output::OutputCommand::OpenUrl(_)));
+    });
+
+    output
+}
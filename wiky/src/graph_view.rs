@@ -0,0 +1,268 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::PubkyApp;
+
+use eframe::egui::{Context, Ui};
+use egui::{ecolor::Hsva, Align2, Color32, FontId, Pos2, Rect, Sense, Stroke, Vec2};
+use wiki_core::graph::PageKey;
+
+/// Ideal distance between linked pages, in layout units
+const SPRING_LENGTH: f32 = 1.0;
+/// Pull towards the center, which keeps the pages without links from drifting away
+const GRAVITY: f32 = 0.1;
+/// Steps after which the layout stops moving
+const COOLING_STEPS: u32 = 300;
+const NODE_RADIUS: f32 = 8.0;
+const CANVAS_HEIGHT: f32 = 450.0;
+
+/// Force-directed layout of the pages shown in the graph, kept across frames so it can settle
+#[derive(Default)]
+pub(crate) struct GraphLayout {
+    positions: HashMap<PageKey, Vec2>,
+    /// Steps left before the layout stops moving
+    steps_left: u32,
+}
+
+impl GraphLayout {
+    /// Keep the positions of the pages still shown, and place the new ones on a circle
+    fn sync(&mut self, nodes: &[PageKey]) {
+        let unchanged =
+            nodes.len() == self.positions.len() && nodes.iter().all(|key| self.positions.contains_key(key));
+        if unchanged {
+            return;
+        }
+
+        self.positions.retain(|key, _| nodes.contains(key));
+        let count = nodes.len() as f32;
+        for (i, key) in nodes.iter().enumerate() {
+            self.positions.entry(key.clone()).or_insert_with(|| {
+                Vec2::angled(i as f32 / count * std::f32::consts::TAU) * count.sqrt()
+            });
+        }
+        self.steps_left = COOLING_STEPS;
+    }
+
+    /// Move the pages one step: all pages repel each other, linked pages attract each other, and
+    /// the moves get smaller as the layout cools down
+    fn step(&mut self, nodes: &[PageKey], edges: &[Edge]) {
+        if self.steps_left == 0 {
+            return;
+        }
+
+        let mut positions: Vec<Vec2> = nodes.iter().map(|key| self.positions[key]).collect();
+        let mut forces = vec![Vec2::ZERO; positions.len()];
+
+        for i in 0..positions.len() {
+            for j in i + 1..positions.len() {
+                let delta = positions[i] - positions[j];
+                let distance = delta.length().max(0.01);
+                let force = delta / distance * (SPRING_LENGTH * SPRING_LENGTH / distance);
+                forces[i] += force;
+                forces[j] -= force;
+            }
+        }
+        for edge in edges {
+            let delta = positions[edge.from] - positions[edge.to];
+            let force = delta * (delta.length() / SPRING_LENGTH);
+            forces[edge.from] -= force;
+            forces[edge.to] += force;
+        }
+
+        let temperature = 0.1 * self.steps_left as f32 / COOLING_STEPS as f32;
+        for ((position, force), key) in positions.iter_mut().zip(forces).zip(nodes) {
+            let force = force - *position * GRAVITY;
+            let length = force.length();
+            if length > 0.0 {
+                *position += force / length * length.min(temperature);
+            }
+            self.positions.insert(key.clone(), *position);
+        }
+
+        self.steps_left -= 1;
+    }
+
+    fn is_settled(&self) -> bool {
+        self.steps_left == 0
+    }
+}
+
+/// A link between two of the pages shown, by index
+struct Edge {
+    from: usize,
+    to: usize,
+    /// `to` is the page `from` was forked from
+    fork: bool,
+}
+
+pub(crate) fn update(app: &mut PubkyApp, own_pk: &str, ctx: &Context, ui: &mut Ui) {
+    ui.label(egui::RichText::new("Knowledge Graph").size(20.0).strong());
+    ui.add_space(15.0);
+
+    show_filters(app, own_pk, ui);
+    ui.add_space(10.0);
+
+    // Pages matching the filters, and the links between them
+    let nodes: Vec<PageKey> = app
+        .link_graph
+        .pages()
+        .filter(|((user_pk, _), page)| {
+            app.graph_author_filter.as_ref().is_none_or(|author| author == user_pk)
+                && app.graph_tag_filter.as_ref().is_none_or(|tag| page.tags.contains(tag))
+        })
+        .map(|(key, _)| key.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let index: HashMap<&PageKey, usize> = nodes.iter().enumerate().map(|(i, key)| (key, i)).collect();
+
+    let mut edges = vec![];
+    for (from, key) in nodes.iter().enumerate() {
+        for target in app.link_graph.links_of(key) {
+            if let Some(&to) = index.get(&target) {
+                edges.push(Edge { from, to, fork: false });
+            }
+        }
+        let forked_from = app.link_graph.page(key).and_then(|page| page.forked_from.as_ref());
+        if let Some(&to) = forked_from.and_then(|upstream| index.get(upstream)) {
+            edges.push(Edge { from, to, fork: true });
+        }
+    }
+
+    if nodes.is_empty() {
+        if app.search_indexing {
+            ui.spinner();
+        } else {
+            ui.label(egui::RichText::new("No pages to show").italics().color(Color32::GRAY));
+        }
+    } else {
+        app.graph_layout.sync(&nodes);
+        app.graph_layout.step(&nodes, &edges);
+        if !app.graph_layout.is_settled() {
+            ctx.request_repaint();
+        }
+
+        show_canvas(app, own_pk, ui, &nodes, &edges);
+    }
+
+    ui.add_space(15.0);
+    let back_button = ui.add_sized(
+        [120.0, 35.0],
+        egui::Button::new(egui::RichText::new("← Back").size(15.0))
+    );
+    if back_button.on_hover_text("Alt+←").clicked() {
+        app.navigate_back();
+    }
+}
+
+/// Author and tag of the pages to show
+fn show_filters(app: &mut PubkyApp, own_pk: &str, ui: &mut Ui) {
+    let authors: BTreeSet<String> = app.link_graph.pages().map(|((user_pk, _), _)| user_pk.clone()).collect();
+    let tags: BTreeSet<String> = app.link_graph.pages().flat_map(|(_, page)| page.tags.clone()).collect();
+
+    ui.horizontal(|ui| {
+        let selected_author = match &app.graph_author_filter {
+            Some(author) => app.author_name(own_pk, author),
+            None => "All authors".to_string(),
+        };
+        egui::ComboBox::from_id_salt("graph_author")
+            .selected_text(selected_author)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut app.graph_author_filter, None, "All authors");
+                for author in authors {
+                    let name = app.author_name(own_pk, &author);
+                    ui.selectable_value(&mut app.graph_author_filter, Some(author), name);
+                }
+            });
+
+        egui::ComboBox::from_id_salt("graph_tag")
+            .selected_text(app.graph_tag_filter.clone().unwrap_or_else(|| "All tags".to_string()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut app.graph_tag_filter, None, "All tags");
+                for tag in tags {
+                    ui.selectable_value(&mut app.graph_tag_filter, Some(tag.clone()), tag);
+                }
+            });
+
+        if app.search_indexing {
+            ui.spinner();
+        } else if ui.small_button("⟳").on_hover_text("Rebuild the graph").clicked() {
+            app.refresh_indexes();
+        }
+    });
+}
+
+/// Pages as dots colored by author, with their titles, and the links between them
+fn show_canvas(app: &mut PubkyApp, own_pk: &str, ui: &mut Ui, nodes: &[PageKey], edges: &[Edge]) {
+    let (response, painter) = ui.allocate_painter(Vec2::new(ui.available_width(), CANVAS_HEIGHT), Sense::click());
+    let canvas = response.rect;
+    painter.rect_filled(canvas, 4.0, ui.visuals().extreme_bg_color);
+
+    // Fit the layout in the canvas, leaving room for the titles
+    let positions: Vec<Vec2> = nodes.iter().map(|key| app.graph_layout.positions[key]).collect();
+    let bounds = Rect::from_points(&positions.iter().map(|p| p.to_pos2()).collect::<Vec<_>>());
+    let area = canvas.shrink2(Vec2::new(120.0, 30.0));
+    let scale = (area.width() / bounds.width().max(0.01)).min(area.height() / bounds.height().max(0.01)).min(150.0);
+    let to_screen = |position: Vec2| area.center() + (position - bounds.center().to_vec2()) * scale;
+    let points: Vec<Pos2> = positions.iter().map(|p| to_screen(*p)).collect();
+
+    for edge in edges {
+        let line = [points[edge.from], points[edge.to]];
+        if edge.fork {
+            painter.extend(egui::Shape::dashed_line(&line, Stroke::new(1.5, Color32::from_rgb(230, 140, 40)), 6.0, 4.0));
+        } else {
+            painter.line_segment(line, Stroke::new(1.0, Color32::GRAY));
+        }
+    }
+
+    let pointer = response.hover_pos();
+    let mut hovered = None;
+    for (i, (key, point)) in nodes.iter().zip(&points).enumerate() {
+        let (user_pk, page_id) = key;
+        let title = app.link_graph.page(key).map(|page| page.title.clone()).unwrap_or_default();
+
+        let label = painter.text(
+            *point + Vec2::new(NODE_RADIUS + 4.0, 0.0),
+            Align2::LEFT_CENTER,
+            &title,
+            FontId::proportional(12.0),
+            ui.visuals().text_color(),
+        );
+        let hit = pointer.is_some_and(|pos| pos.distance(*point) <= NODE_RADIUS + 2.0 || label.contains(pos));
+        if hit {
+            hovered = Some(i);
+        }
+
+        let radius = if hit { NODE_RADIUS + 2.0 } else { NODE_RADIUS };
+        painter.circle_filled(*point, radius, author_color(user_pk));
+        if user_pk == &app.selected_wiki_user_id && page_id == &app.selected_wiki_page_id {
+            painter.circle_stroke(*point, radius + 2.0, Stroke::new(2.0, ui.visuals().strong_text_color()));
+        }
+    }
+
+    if let Some(i) = hovered {
+        let (user_pk, page_id) = &nodes[i];
+        let author = app.author_name(own_pk, user_pk);
+        let response = response.on_hover_cursor(egui::CursorIcon::PointingHand).on_hover_text(format!("by {author}"));
+        if response.clicked() {
+            app.navigate_to_view_wiki_page(user_pk, page_id);
+        }
+    }
+
+    // Legend of the authors shown
+    ui.add_space(5.0);
+    let authors: BTreeSet<&String> = nodes.iter().map(|(user_pk, _)| user_pk).collect();
+    ui.horizontal_wrapped(|ui| {
+        for author in authors {
+            ui.label(egui::RichText::new("●").color(author_color(author)));
+            ui.label(egui::RichText::new(app.author_name(own_pk, author)).small());
+            ui.add_space(10.0);
+        }
+        ui.label(egui::RichText::new("- - fork").small().color(Color32::from_rgb(230, 140, 40)));
+    });
+}
+
+/// Stable color of an author, picked from their public key
+fn author_color(user_pk: &str) -> Color32 {
+    let hash = user_pk.bytes().fold(0u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32));
+    Hsva::new((hash % 360) as f32 / 360.0, 0.6, 0.85, 1.0).into()
+}
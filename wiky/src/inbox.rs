@@ -0,0 +1,48 @@
+use crate::{backend::Command, PubkyApp};
+
+use egui::{CollapsingHeader, Ui};
+use wiki_core::page::format_timestamp;
+
+/// Changes proposed by the follows to own pages, with ways to review, accept or dismiss them
+pub(crate) fn show_inbox(app: &mut PubkyApp, ui: &mut Ui, own_pk: &str) {
+    let inbox = app.inbox.clone();
+    CollapsingHeader::new(egui::RichText::new(format!("📥 Proposed Changes ({})", inbox.len())).size(15.0)).show(ui, |ui| {
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            if app.inbox_loading {
+                ui.spinner();
+            } else if ui.small_button("⟳ Refresh").clicked() {
+                app.refresh_inbox();
+            }
+        });
+
+        if inbox.is_empty() && !app.inbox_loading {
+            ui.label(egui::RichText::new("No proposals from the users you follow").italics().color(egui::Color32::GRAY));
+        }
+
+        for received in inbox {
+            let proposal = &received.proposal;
+            egui::Frame::group(ui.style()).show(ui, |ui| {
+                ui.label(egui::RichText::new(format!("{} → {}", received.author_pk, proposal.target_page_id)).monospace().small());
+                ui.label(format!("Proposed on {}", format_timestamp(proposal.created_at)));
+                if !proposal.message.is_empty() {
+                    ui.label(egui::RichText::new(&proposal.message).italics());
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("🔍 View diff").clicked() {
+                        app.view_proposal_diff(own_pk, &received);
+                    }
+                    if ui.add_enabled(!app.merging, egui::Button::new("✓ Accept")).clicked() {
+                        app.accept_proposal(own_pk, &received);
+                    }
+                    if ui.button("✖ Dismiss").clicked() {
+                        app.backend.send(Command::DismissProposal {
+                            url: received.url.clone(),
+                        });
+                    }
+                });
+            });
+        }
+    });
+}
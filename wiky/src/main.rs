@@ -1,29 +1,68 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-};
+use std::{collections::HashMap, ops::Range, path::PathBuf, time::{Duration, Instant}};
 
 use anyhow::{anyhow, Result};
+use clap::Parser;
 use eframe::egui;
 use egui_commonmark::*;
-use pubky::{Capabilities, Pubky, PubkyAuthFlow, PubkySession, PublicStorage};
 use tokio::runtime::Runtime;
-use uuid::Uuid;
+use wiki_core::{
+    attachment::markdown_reference,
+    cache::Draft,
+    client::SyncConflict,
+    diff::DiffSide,
+    follow::Follow,
+    graph::LinkGraph,
+    history::Revision,
+    links::{expand_title_links, same_title, TitleLink, WikiLink},
+    merge::{MergeSource, PreparedMerge},
+    page::{parse_tags, ForkedFrom, WikiPage},
+    proposal::{Proposal, ReceivedProposal},
+    search::{SearchHit, SearchIndex},
+};
 
-use crate::utils::{extract_title, generate_qr_image, get_list};
+use crate::{
+    backend::{Backend, Command, Event},
+    cli::Cli,
+    editor::{EditorMode, ScrollSync},
+    graph_view::GraphLayout,
+    navigation::{Location, Navigation},
+    utils::generate_qr_image,
+};
 
+mod backend;
+mod cli;
 mod create_wiki;
+mod diff_wiki;
+mod drafts;
 mod edit_wiki;
+mod editor;
+mod graph_view;
+mod inbox;
+mod merge_wiki;
+mod navigation;
+mod pubky_loader;
 mod utils;
 mod view_wiki;
 
+#[cfg(test)]
+mod ui_tests;
+
 const APP_NAME: &str = "Pubky Wiki";
 
 fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-
+    let cli = Cli::parse();
     let rt = Runtime::new()?;
-    let app = PubkyApp::new(rt);
+
+    if let Some(command) = cli.command {
+        // Keep stdout for the output of the command, so it can be piped
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .with_max_level(tracing_subscriber::filter::LevelFilter::WARN)
+            .init();
+        return rt.block_on(cli::run(command));
+    }
+
+    tracing_subscriber::fmt::init();
 
     // Load icon
     let icon = load_icon()?;
@@ -36,8 +75,12 @@ fn main() -> Result<()> {
         ..Default::default()
     };
 
-    eframe::run_native(APP_NAME, options, Box::new(|_cc| Ok(Box::new(app))))
-        .map_err(|e| anyhow!("{e}"))
+    eframe::run_native(
+        APP_NAME,
+        options,
+        Box::new(|cc| Ok(Box::new(PubkyApp::new(rt, cc.egui_ctx.clone())))),
+    )
+    .map_err(|e| anyhow!("{e}"))
 }
 
 fn load_icon() -> Result<egui::IconData> {
@@ -51,8 +94,8 @@ fn load_icon() -> Result<egui::IconData> {
 
     Ok(egui::IconData {
         rgba,
-        width: width as u32,
-        height: height as u32,
+        width,
+        height,
     })
 }
 
@@ -72,8 +115,7 @@ pub(crate) enum AuthState {
         auth_url: String,
     },
     Authenticated {
-        session: PubkySession,
-        pub_storage: PublicStorage,
+        own_pk: String,
         /// Map file URL to file title
         file_cache: HashMap<String, String>,
     },
@@ -86,192 +128,1128 @@ pub(crate) enum ViewState {
     CreateWiki,
     ViewWiki,
     EditWiki,
+    DiffWiki,
+    MergeWiki,
+    Graph,
 }
 
 pub(crate) struct PubkyApp {
-    pub(crate) state: Arc<Mutex<AuthState>>,
+    pub(crate) state: AuthState,
+    pub(crate) backend: Backend,
     qr_texture: Option<egui::TextureHandle>,
     logo_texture: Option<egui::TextureHandle>,
     logo_image: Option<egui::ColorImage>,
     pub(crate) view_state: ViewState,
     /// Content for the Edit Wiki view
     pub(crate) edit_wiki_content: String,
+    /// Comma-separated tags for the Edit Wiki view
+    pub(crate) edit_wiki_tags: String,
+    /// Language for the Edit Wiki view
+    pub(crate) edit_wiki_language: String,
+    /// Version of the page the Edit Wiki view started from, checked before saving
+    pub(crate) edit_base_version: Option<Revision>,
+    /// Whether the editors show the source, the preview or both
+    pub(crate) editor_mode: EditorMode,
+    /// Keeps the source and the preview scrolled together
+    pub(crate) editor_scroll: ScrollSync,
+    /// Characters selected in the source, kept for the toolbar which takes the focus away
+    pub(crate) editor_selection: Option<Range<usize>>,
+    /// Query of the "Link to page" picker, while it is open
+    pub(crate) link_picker_query: Option<String>,
+    /// Asks for the file to attach, with the file dialog of the system outside of the tests
+    pub(crate) pick_attachment: fn() -> Option<PathBuf>,
+    /// Path typed in the "Attach file" window, while it is open
+    pub(crate) attach_file_path: Option<String>,
+    /// Number of files being uploaded to attach to the page being written
+    pub(crate) attachments_uploading: usize,
+    /// Markdown for the uploaded files, inserted at the selection of the source on the next frame
+    pub(crate) uploaded_attachments: Vec<String>,
+    /// Unsaved changes of the own pages, most recently saved first
+    pub(crate) drafts: Vec<Draft>,
+    /// Draft the editor is saved in, from the first frame it is shown
+    pub(crate) draft_id: Option<String>,
+    /// Content, tags and language of the editor when its draft was last saved
+    pub(crate) draft_snapshot: (String, String, String),
+    /// When the editor was last checked for changes to save in its draft
+    pub(crate) draft_checked_at: Instant,
+    /// The drafts left by a previous session are offered for restoring, until dismissed
+    pub(crate) offer_draft_recovery: bool,
+    /// The drafts have not been fetched yet since signing in, so the next ones fetched are from a previous session
+    pub(crate) first_drafts_fetch: bool,
+    /// The last create or update failed, its changes are kept in a draft until it is retried
+    pub(crate) save_failed: bool,
     pub(crate) selected_wiki_fork_urls: Vec<String>,
     pub(crate) selected_wiki_page_id: String,
-    pub(crate) selected_wiki_content: String,
+    pub(crate) selected_wiki_page: WikiPage,
+    /// Current version of the page the selected page was forked from, once fetched
+    pub(crate) selected_wiki_upstream: Option<WikiPage>,
+    /// Revisions of the selected page, newest first
+    pub(crate) selected_wiki_history: Vec<Revision>,
+    /// Revision of the selected page shown instead of its current version
+    pub(crate) selected_wiki_revision: Option<Revision>,
+    /// Content of `selected_wiki_revision`, once fetched
+    pub(crate) selected_wiki_revision_page: Option<WikiPage>,
     pub(crate) selected_wiki_user_id: String,
-    pub(crate) needs_refresh: bool,
+    /// Pages visited before and after the current one
+    pub(crate) navigation: Navigation,
+    /// Scroll position of the content of the selected page
+    pub(crate) page_scroll_offset: f32,
+    /// Scroll position to apply to the content of the selected page, once it is shown
+    pub(crate) restore_page_scroll: Option<f32>,
+    /// Heading of the selected page to scroll to once it is shown, from the `#heading` of a link
+    pub(crate) scroll_to_heading: Option<String>,
+    /// Versions ticked for comparison in the View Wiki view, at most two
+    pub(crate) diff_selection: Vec<DiffSide>,
+    /// Versions compared in the Diff Wiki view, with their content once fetched
+    pub(crate) diff_sides: Vec<(DiffSide, Option<WikiPage>)>,
+    /// Merge reviewed in the Merge Wiki view
+    pub(crate) merge: Option<PreparedMerge>,
+    /// True while upstream or proposed changes are being merged
+    pub(crate) merging: bool,
+    /// Message sent along with a proposal for the changes of the selected fork
+    pub(crate) proposal_message: String,
+    /// True while a proposal is being published
+    pub(crate) proposing: bool,
+    /// True once the changes of the selected fork were proposed
+    pub(crate) proposal_sent: bool,
+    /// Followed users, to resolve the `[[@alias/Title]]` links
+    pub(crate) follows: Vec<Follow>,
+    /// Proposals received from the follows, newest first
+    pub(crate) inbox: Vec<ReceivedProposal>,
+    /// True while the received proposals are being fetched
+    pub(crate) inbox_loading: bool,
+    /// Full-text index of the own pages and the pages of the follows
+    pub(crate) search_index: SearchIndex,
+    /// Links between the own pages and the pages of the follows
+    pub(crate) link_graph: LinkGraph,
+    /// Positions of the pages in the Graph view
+    pub(crate) graph_layout: GraphLayout,
+    /// Author whose pages are shown in the Graph view, all authors if `None`
+    pub(crate) graph_author_filter: Option<String>,
+    /// Tag of the pages shown in the Graph view, all pages if `None`
+    pub(crate) graph_tag_filter: Option<String>,
+    /// Query typed in the search box of the Wiki List view
+    pub(crate) search_query: String,
+    /// Matches of `search_query`, best first
+    pub(crate) search_results: Vec<SearchHit>,
+    /// True while the search index is being built
+    pub(crate) search_indexing: bool,
+    /// True while the list of own pages is being fetched
+    pub(crate) files_loading: bool,
+    /// True while the content of the selected page is being fetched
+    pub(crate) page_loading: bool,
+    /// True while the page a `wiki:{title}` link points to is being looked up
+    pub(crate) resolving_link: bool,
+    /// Link out of the wiki clicked in the selected page, opened in the browser once confirmed
+    pub(crate) pending_external_link: Option<String>,
+    /// True while a file linked from the selected page is being downloaded
+    pub(crate) downloading: bool,
+    /// Where the last file downloaded from a page was saved
+    pub(crate) last_download: Option<PathBuf>,
+    /// True while the forks of the selected page are being discovered
+    pub(crate) forks_loading: bool,
+    /// True while the revisions of the selected page are being listed
+    pub(crate) history_loading: bool,
+    /// True while a create, update or delete is in flight
+    pub(crate) saving: bool,
+    /// True while the homeserver is unreachable and the app works from the cache
+    pub(crate) offline: bool,
+    /// Last failed operation, shown above the current view until dismissed
+    pub(crate) last_error: Option<String>,
     cache: CommonMarkCache,
-    rt: Arc<Runtime>,
     pub(crate) show_copy_tooltip: bool,
-    /// Page ID from which content is being forked (when forking)
-    pub(crate) forked_from_page_id: Option<String>,
+    /// Page version from which content is being forked (when forking)
+    pub(crate) forked_from: Option<ForkedFrom>,
 }
 
 impl PubkyApp {
-    fn new(rt: Runtime) -> Self {
-        let state = Arc::new(Mutex::new(AuthState::Initializing));
-
-        // Start the auth flow in a background task
-        let state_clone = state.clone();
-
-        let rt_arc = Arc::new(rt);
-        let rt_arc_clone = rt_arc.clone();
-        std::thread::spawn(move || {
-            let initialize_auth_fut = initialize_auth();
-            match rt_arc_clone.block_on(initialize_auth_fut) {
-                Ok((pubky, flow, auth_url)) => {
-                    *state_clone.lock().unwrap() = AuthState::ShowingQR {
-                        auth_url: auth_url.clone(),
-                    };
+    fn new(rt: Runtime, ctx: egui::Context) -> Self {
+        pubky_loader::install(&ctx, rt.handle().clone());
 
-                    // Poll for authentication
-                    let await_approval_fut = flow.await_approval();
-                    match rt_arc_clone.block_on(await_approval_fut) {
-                        Ok(session) => {
-                            Self::fetch_files_and_update(
-                                &session,
-                                &pubky.public_storage(),
-                                rt_arc_clone,
-                                state_clone,
-                            );
-                        }
-                        Err(e) => {
-                            *state_clone.lock().unwrap() =
-                                AuthState::Error(format!("Authentication failed: {e}"));
-                        }
-                    }
-                }
-                Err(e) => {
-                    *state_clone.lock().unwrap() =
-                        AuthState::Error(format!("Failed to initialize: {e}"));
-                }
-            }
-        });
+        // The worker starts the auth flow right away and reports back through events
+        Self::with_backend(Backend::spawn(rt, ctx))
+    }
 
+    fn with_backend(backend: Backend) -> Self {
         // Load logo image
         let logo_image = load_logo_image();
 
         Self {
-            state,
+            state: AuthState::Initializing,
+            backend,
             qr_texture: None,
             logo_texture: None,
             logo_image,
             view_state: ViewState::WikiList,
             edit_wiki_content: String::new(),
+            edit_wiki_tags: String::new(),
+            edit_wiki_language: String::new(),
+            edit_base_version: None,
+            editor_mode: EditorMode::default(),
+            editor_scroll: ScrollSync::default(),
+            editor_selection: None,
+            link_picker_query: None,
+            pick_attachment: editor::pick_attachment,
+            attach_file_path: None,
+            attachments_uploading: 0,
+            uploaded_attachments: vec![],
+            drafts: vec![],
+            draft_id: None,
+            draft_snapshot: Default::default(),
+            draft_checked_at: Instant::now(),
+            offer_draft_recovery: false,
+            first_drafts_fetch: false,
+            save_failed: false,
             selected_wiki_page_id: String::new(),
-            selected_wiki_content: String::new(),
+            selected_wiki_page: WikiPage::default(),
+            selected_wiki_upstream: None,
+            selected_wiki_history: vec![],
+            selected_wiki_revision: None,
+            selected_wiki_revision_page: None,
             selected_wiki_user_id: String::new(),
             selected_wiki_fork_urls: vec![],
-            needs_refresh: false,
+            navigation: Navigation::default(),
+            page_scroll_offset: 0.0,
+            restore_page_scroll: None,
+            scroll_to_heading: None,
+            diff_selection: vec![],
+            diff_sides: vec![],
+            merge: None,
+            merging: false,
+            proposal_message: String::new(),
+            proposing: false,
+            proposal_sent: false,
+            follows: vec![],
+            inbox: vec![],
+            inbox_loading: false,
+            search_index: SearchIndex::default(),
+            link_graph: LinkGraph::default(),
+            graph_layout: GraphLayout::default(),
+            graph_author_filter: None,
+            graph_tag_filter: None,
+            search_query: String::new(),
+            search_results: vec![],
+            search_indexing: false,
+            files_loading: false,
+            page_loading: false,
+            resolving_link: false,
+            pending_external_link: None,
+            downloading: false,
+            last_download: None,
+            forks_loading: false,
+            history_loading: false,
+            saving: false,
+            offline: false,
+            last_error: None,
             cache: CommonMarkCache::default(),
-            rt: rt_arc,
             show_copy_tooltip: false,
-            forked_from_page_id: None,
+            forked_from: None,
         }
     }
 
-    /// Fetch the list of files and their titles, then update the state with the file cache
-    fn fetch_files_and_update(
-        session: &PubkySession,
-        pub_storage: &PublicStorage,
-        rt_arc_clone: Arc<Runtime>,
-        state_clone: Arc<Mutex<AuthState>>,
-    ) {
-        let mut file_cache = HashMap::new();
-
-        match get_list(session, "/pub/wiki.app/", rt_arc_clone.clone()) {
-            Ok(file_urls) => {
-                for file_url in &file_urls {
-                    // Synchronously fetch the content
-                    let get_path_fut = pub_storage.get(file_url);
-                    match rt_arc_clone.block_on(get_path_fut) {
-                        Ok(response) => {
-                            let response_text_fut = response.text();
-                            match rt_arc_clone.block_on(response_text_fut) {
-                                Ok(content) => {
-                                    let file_title = extract_title(&content);
-
-                                    file_cache.insert(file_url.into(), file_title.into());
-                                }
-                                Err(e) => log::error!("Error reading content: {e}"),
+    /// Apply the results posted by the background worker since the last frame
+    fn handle_backend_events(&mut self) {
+        for event in self.backend.poll() {
+            match event {
+                Event::AuthUrl(auth_url) => {
+                    self.state = AuthState::ShowingQR { auth_url };
+                }
+                Event::Authenticated { own_pk, offline } => {
+                    self.state = AuthState::Authenticated {
+                        own_pk,
+                        file_cache: HashMap::new(),
+                    };
+                    self.offline = offline;
+                    self.refresh_files();
+                    self.refresh_inbox();
+                    self.refresh_indexes();
+                    self.backend.send(Command::FetchFollows);
+                    self.first_drafts_fetch = true;
+                    self.backend.send(Command::FetchDrafts);
+                }
+                Event::AuthFailed(error) => {
+                    self.state = AuthState::Error(error);
+                }
+                Event::LoggedOut => {
+                    // The worker starts a new auth flow, which needs a new QR code
+                    self.state = AuthState::Initializing;
+                    self.qr_texture = None;
+                    self.navigate_to_wiki_list();
+                    self.navigation = Navigation::default();
+                    self.follows.clear();
+                    self.drafts.clear();
+                    self.offer_draft_recovery = false;
+                    self.first_drafts_fetch = false;
+                    self.inbox.clear();
+                    self.search_index = SearchIndex::default();
+                    self.link_graph = LinkGraph::default();
+                    self.search_query.clear();
+                    self.search_results.clear();
+                    self.last_error = None;
+                }
+                Event::FilesFetched(files) => {
+                    if let AuthState::Authenticated { file_cache, .. } = &mut self.state {
+                        *file_cache = files;
+                    }
+                    self.files_loading = false;
+                }
+                Event::PageFetched {
+                    user_pk,
+                    page_id,
+                    result,
+                } => {
+                    // Ignore responses for pages the user already navigated away from
+                    if user_pk != self.selected_wiki_user_id
+                        || page_id != self.selected_wiki_page_id
+                    {
+                        continue;
+                    }
+                    self.page_loading = false;
+                    match result {
+                        Ok(page) => {
+                            if let Some(forked_from) = &page.meta.forked_from {
+                                self.backend.send(Command::FetchUpstream {
+                                    user_pk: forked_from.author_pk.clone(),
+                                    page_id: forked_from.page_id.clone(),
+                                });
+                            }
+                            self.selected_wiki_page = page;
+                        }
+                        Err(e) => self.report_error(e),
+                    }
+                }
+                Event::UpstreamFetched {
+                    user_pk,
+                    page_id,
+                    result,
+                } => {
+                    let Some(forked_from) = &self.selected_wiki_page.meta.forked_from else {
+                        continue;
+                    };
+                    if forked_from.author_pk != user_pk || forked_from.page_id != page_id {
+                        continue;
+                    }
+                    match result {
+                        Ok(page) => self.selected_wiki_upstream = Some(page),
+                        Err(e) => log::warn!("Failed to fetch upstream page: {e}"),
+                    }
+                }
+                Event::HistoryFetched {
+                    user_pk,
+                    page_id,
+                    result,
+                } => {
+                    if user_pk != self.selected_wiki_user_id
+                        || page_id != self.selected_wiki_page_id
+                    {
+                        continue;
+                    }
+                    self.history_loading = false;
+                    match result {
+                        Ok(revisions) => self.selected_wiki_history = revisions,
+                        Err(e) => self.report_error(e),
+                    }
+                }
+                Event::RevisionFetched {
+                    user_pk,
+                    page_id,
+                    revision,
+                    result,
+                } => {
+                    if user_pk != self.selected_wiki_user_id
+                        || page_id != self.selected_wiki_page_id
+                        || Some(&revision) != self.selected_wiki_revision.as_ref()
+                    {
+                        continue;
+                    }
+                    match result {
+                        Ok(page) => self.selected_wiki_revision_page = Some(page),
+                        Err(e) => {
+                            self.selected_wiki_revision = None;
+                            self.report_error(e);
+                        }
+                    }
+                }
+                Event::FollowsFetched(follows) => self.follows = follows,
+                Event::TitleResolved {
+                    user_pk,
+                    title,
+                    heading,
+                    result,
+                } => {
+                    // Opening another page drops the lookup
+                    if !self.resolving_link || user_pk != self.selected_wiki_user_id {
+                        continue;
+                    }
+                    self.resolving_link = false;
+                    match result {
+                        Ok(Some(page_id)) => {
+                            self.navigate_to_view_wiki_page(&user_pk, &page_id);
+                            self.scroll_to_heading = heading;
+                        }
+                        Ok(None) => self.report_error(format!("No page titled \"{title}\"")),
+                        Err(e) => self.report_error(e),
+                    }
+                }
+                Event::ForksDiscovered { page_id, fork_urls } => {
+                    if page_id != self.selected_wiki_page_id {
+                        continue;
+                    }
+                    self.selected_wiki_fork_urls = fork_urls;
+                    self.forks_loading = false;
+                }
+                Event::DiffSideFetched { side, result } => {
+                    let Some((_, page)) = self.diff_sides.iter_mut().find(|(s, _)| *s == side) else {
+                        continue;
+                    };
+                    match result {
+                        Ok(fetched) => *page = Some(fetched),
+                        Err(e) => self.report_error(e),
+                    }
+                }
+                Event::MergePrepared { page_id, result } => {
+                    self.merging = false;
+                    if page_id != self.selected_wiki_page_id {
+                        continue;
+                    }
+                    match result {
+                        Ok(merge) => {
+                            self.edit_wiki_content = merge.result.text.clone();
+                            self.merge = Some(*merge);
+                            self.view_state = ViewState::MergeWiki;
+                        }
+                        Err(e) => self.report_error(e),
+                    }
+                }
+                Event::ChangesProposed(result) => {
+                    self.proposing = false;
+                    match result {
+                        Ok(()) => {
+                            self.proposal_message.clear();
+                            self.proposal_sent = true;
+                        }
+                        Err(e) => self.report_error(e),
+                    }
+                }
+                Event::InboxFetched(inbox) => {
+                    self.inbox = inbox;
+                    self.inbox_loading = false;
+                }
+                Event::ProposalDismissed { url, result } => match result {
+                    Ok(()) => self.inbox.retain(|p| p.url != url),
+                    Err(e) => self.report_error(e),
+                },
+                Event::DraftsFetched(result) => {
+                    let first_fetch = std::mem::take(&mut self.first_drafts_fetch);
+                    match result {
+                        Ok(drafts) => {
+                            // Drafts found on signing in were left by a previous session
+                            if first_fetch && self.drafts.is_empty() && !drafts.is_empty() {
+                                self.offer_draft_recovery = true;
+                            }
+                            self.drafts = drafts;
+                        }
+                        Err(e) => self.report_error(e),
+                    }
+                }
+                Event::AttachmentUploaded { file_name, result } => {
+                    self.attachments_uploading = self.attachments_uploading.saturating_sub(1);
+                    match result {
+                        Ok(url) if matches!(self.view_state, ViewState::CreateWiki | ViewState::EditWiki) => {
+                            self.uploaded_attachments.push(markdown_reference(&url, &file_name));
+                        }
+                        Ok(url) => log::warn!("Uploaded {url} after the editor was closed"),
+                        Err(e) => self.report_error(e),
+                    }
+                }
+                Event::AttachmentDownloaded(result) => {
+                    self.downloading = false;
+                    match result {
+                        Ok(path) => {
+                            log::info!("Saved download to {}", path.display());
+                            self.last_download = Some(path);
+                        }
+                        Err(e) => self.report_error(e),
+                    }
+                }
+                Event::DraftSaved(result) | Event::DraftDiscarded(result) => {
+                    if let Err(e) = result {
+                        self.report_error(e);
+                    }
+                }
+                Event::PendingWritesSynced(result) => match result {
+                    Ok(report) => {
+                        if report.synced > 0 || !report.conflicts.is_empty() {
+                            log::info!("Synced {} offline changes", report.synced);
+                            self.refresh_files();
+                            self.refresh_indexes();
+                        }
+                        // Changes that could not be synced are kept in drafts
+                        if !report.failed.is_empty() || !report.conflicts.is_empty() {
+                            self.backend.send(Command::FetchDrafts);
+                        }
+                        let mut problems = vec![];
+                        if !report.failed.is_empty() {
+                            problems.push(format!("Failed to sync offline changes to {}", report.failed.join("; ")));
+                        }
+                        problems.extend(self.merge_sync_conflicts(report.conflicts));
+                        if !problems.is_empty() {
+                            self.report_error(problems.join("\n"));
+                        }
+                    }
+                    Err(e) => self.report_error(e),
+                },
+                Event::IndexesBuilt { search, links } => {
+                    self.search_index = search;
+                    self.link_graph = links;
+                    self.search_indexing = false;
+                    self.run_search();
+                }
+                Event::PageCreated(result) => {
+                    self.saving = false;
+                    match result {
+                        Ok((wiki_page_path, page)) => {
+                            log::info!("Created wiki post at: {}", wiki_page_path);
+
+                            // Convert path to pubky URL format for the file_cache list
+                            if let AuthState::Authenticated { own_pk, file_cache } = &mut self.state {
+                                let own_user_pk = own_pk.clone();
+                                let file_url = format!("pubky://{own_user_pk}{wiki_page_path}");
+                                file_cache.insert(file_url, page.title().into());
+
+                                let page_id = wiki_page_path.split('/').next_back().unwrap_or(&wiki_page_path);
+                                self.search_index.insert(&own_user_pk, page_id, &page);
+                                self.link_graph.insert(&own_user_pk, page_id, &page);
+                                self.run_search();
+                            }
+
+                            if let Some(draft_id) = self.draft_id.take() {
+                                self.discard_draft(&draft_id);
+                            }
+                            self.navigate_to_wiki_list();
+                        }
+                        Err(e) => self.keep_failed_save(e),
+                    }
+                }
+                Event::PageUpdated { page_id, result } => {
+                    self.saving = false;
+                    match result {
+                        Ok(page) => {
+                            log::info!("Updated wiki post: {}", page_id);
+                            if let AuthState::Authenticated { own_pk, .. } = &self.state {
+                                let own_user_pk = own_pk.clone();
+                                self.search_index.insert(&own_user_pk, &page_id, &page);
+                                self.link_graph.insert(&own_user_pk, &page_id, &page);
+                                self.run_search();
+                            }
+
+                            // An accepted proposal leaves the inbox
+                            if let Some(MergeSource::Proposal(proposal)) =
+                                self.merge.take().map(|merge| merge.source)
+                            {
+                                self.backend.send(Command::DismissProposal { url: proposal.url });
+                            }
+
+                            if let Some(draft_id) = self.draft_id.take() {
+                                self.discard_draft(&draft_id);
+                            }
+
+                            // Update the selected content to reflect changes, unless another
+                            // page was opened while saving
+                            if page_id == self.selected_wiki_page_id {
+                                self.selected_wiki_page = page;
+                            }
+                            self.refresh_files();
+
+                            if self.view_state == ViewState::EditWiki {
+                                self.navigate_to_wiki_list();
+                            } else {
+                                // Restored revisions and merges stay on the page
+                                self.clear_editor();
+                                self.selected_wiki_revision = None;
+                                self.selected_wiki_revision_page = None;
+                                self.view_state = ViewState::ViewWiki;
+                                self.refresh_history();
+                            }
+                        }
+                        Err(e) => self.keep_failed_save(e),
+                    }
+                }
+                Event::PageConflicted { page_id, merge } => {
+                    self.saving = false;
+                    if page_id != self.selected_wiki_page_id {
+                        continue;
+                    }
+                    log::warn!("Update of {page_id} conflicts with a version saved elsewhere");
+
+                    // The changes stay in their draft until the merge is saved
+                    self.edit_wiki_content = merge.result.text.clone();
+                    self.merge = Some(*merge);
+                    self.view_state = ViewState::MergeWiki;
+                }
+                Event::PageDeleted { page_id, result } => {
+                    self.saving = false;
+                    match result {
+                        Ok(()) => {
+                            log::info!("Deleted wiki post: {}", page_id);
+
+                            // Remove from file_urls list
+                            if let AuthState::Authenticated { own_pk, file_cache } = &mut self.state {
+                                let own_user_pk = own_pk.clone();
+                                let file_url = format!("pubky://{own_user_pk}/pub/wiki.app/{page_id}");
+                                file_cache.remove(&file_url);
+
+                                self.search_index.remove(&own_user_pk, &page_id);
+                                self.link_graph.remove(&own_user_pk, &page_id);
+                                self.run_search();
+                                self.navigation.forget_page(&own_user_pk, &page_id);
                             }
+
+                            // Changes to a deleted page can't be saved anymore
+                            let page_drafts: Vec<String> = self
+                                .drafts
+                                .iter()
+                                .filter(|draft| draft.page_id.as_ref() == Some(&page_id))
+                                .map(|draft| draft.id.clone())
+                                .collect();
+                            for draft_id in page_drafts {
+                                self.discard_draft(&draft_id);
+                            }
+
+                            self.navigate_to_wiki_list();
+                            self.refresh_files();
                         }
-                        Err(e) => log::error!("Error fetching path {file_url}: {e}"),
+                        Err(e) => self.report_error(e),
                     }
                 }
             }
-            Err(e) => log::error!("Failed to list files: {e}"),
         }
+    }
 
-        *state_clone.lock().unwrap() = AuthState::Authenticated {
-            session: session.clone(),
-            pub_storage: pub_storage.clone(),
-            file_cache,
+    /// Open the first offline update refused on sync in the merge view, the others are left in
+    /// their drafts. An open editor is not replaced, so all of them are left in the drafts then.
+    ///
+    /// Returns the message telling about the ones left in the drafts, if any.
+    fn merge_sync_conflicts(&mut self, conflicts: Vec<SyncConflict>) -> Option<String> {
+        let AuthState::Authenticated { own_pk, .. } = &self.state else {
+            return None;
         };
+        let own_pk = own_pk.clone();
+
+        let editing = matches!(
+            self.view_state,
+            ViewState::CreateWiki | ViewState::EditWiki | ViewState::MergeWiki
+        );
+        let mut conflicts = conflicts.into_iter();
+        let merged = if editing { None } else { conflicts.next() };
+        let notice = (conflicts.len() > 0).then(|| {
+            format!(
+                "{} offline change(s) conflict with versions saved elsewhere, they are kept in the drafts",
+                conflicts.len()
+            )
+        });
+        let Some(conflict) = merged else {
+            return notice;
+        };
+
+        self.navigate_to_view_wiki_page(&own_pk, &conflict.page_id);
+        self.clear_editor();
+        // The changes stay in their draft until the merge is saved
+        self.draft_id = Some(conflict.draft_id);
+        self.edit_wiki_content = conflict.merge.result.text.clone();
+        self.merge = Some(conflict.merge);
+        self.view_state = ViewState::MergeWiki;
+
+        notice
+    }
+
+    /// Report a failed create or update, keeping the changes in a draft to retry from
+    fn keep_failed_save(&mut self, error: String) {
+        if self.draft_id.is_some() {
+            self.save_failed = true;
+            self.save_draft();
+        }
+        self.report_error(error);
+    }
+
+    fn report_error(&mut self, error: String) {
+        log::error!("{error}");
+        self.last_error = Some(error);
+    }
+
+    /// Reload the list of own pages in the background
+    pub(crate) fn refresh_files(&mut self) {
+        self.files_loading = true;
+        self.backend.send(Command::FetchFiles);
+    }
+
+    /// Look for new proposals from the follows in the background
+    pub(crate) fn refresh_inbox(&mut self) {
+        self.inbox_loading = true;
+        self.backend.send(Command::FetchInbox);
     }
 
-    fn navigate_to_view_wiki_page(
-        &mut self,
-        user_pk: &str,
-        page_id: &str,
-        session: &PubkySession,
-        pub_storage: &PublicStorage,
-    ) {
+    /// Rebuild the search index and the link graph in the background
+    pub(crate) fn refresh_indexes(&mut self) {
+        self.search_indexing = true;
+        self.backend.send(Command::BuildIndexes);
+    }
+
+    /// Update the search results for the current query
+    pub(crate) fn run_search(&mut self) {
+        self.search_results = self.search_index.search(&self.search_query);
+    }
+
+    /// Matches of the search box, replacing the page list of the Wiki List view
+    fn show_search_results(&mut self, ui: &mut egui::Ui) {
+        ui.label(egui::RichText::new(format!("Search Results ({})", self.search_results.len())).size(18.0).strong());
+        ui.add_space(15.0);
+
+        let results = self.search_results.clone();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            if results.is_empty() {
+                ui.label(egui::RichText::new("No matching pages").italics().color(egui::Color32::GRAY));
+            }
+            for hit in results {
+                ui.horizontal(|ui| {
+                    if ui.button(egui::RichText::new(&hit.title).strong()).clicked() {
+                        self.navigate_to_view_wiki_page(&hit.user_pk, &hit.page_id);
+                    }
+                    ui.label(egui::RichText::new(&hit.user_pk).monospace().small());
+                });
+                if let Some(snippet) = &hit.snippet {
+                    ui.label(egui::RichText::new(snippet).small().color(egui::Color32::GRAY));
+                }
+                ui.add_space(5.0);
+            }
+        });
+    }
+
+    /// Open a page, leaving the current location in the navigation trail
+    fn navigate_to_view_wiki_page(&mut self, user_pk: &str, page_id: &str) {
+        let current = self.current_location();
+        if !current.is_page(user_pk, page_id) {
+            self.navigation.visit(current);
+        }
+        self.open_wiki_page(user_pk, page_id);
+    }
+
+    /// Follow a link clicked in the selected page
+    pub(crate) fn follow_link(&mut self, url: &str) {
+        match WikiLink::parse(url) {
+            Some(WikiLink::Page {
+                user_pk,
+                page_id,
+                heading,
+            }) => {
+                self.navigate_to_view_wiki_page(&user_pk, &page_id);
+                self.scroll_to_heading = heading;
+            }
+            Some(WikiLink::Title { title, heading }) => {
+                self.resolving_link = true;
+                self.backend.send(Command::ResolveTitle {
+                    user_pk: self.selected_wiki_user_id.clone(),
+                    title,
+                    heading,
+                });
+            }
+            Some(WikiLink::Attachment { .. }) => {
+                self.downloading = true;
+                self.last_download = None;
+                self.backend.send(Command::DownloadAttachment { url: url.to_string() });
+            }
+            Some(WikiLink::External(url)) => self.pending_external_link = Some(url),
+            Some(WikiLink::NewPage { title }) => {
+                self.clear_editor();
+                self.edit_wiki_content = format!("# {title}\n\n");
+                self.view_state = ViewState::CreateWiki;
+            }
+            None => self.report_error(format!("Invalid link: {url}")),
+        }
+    }
+
+    /// Body of a page of `author_pk`, with its `[[…]]` links turned into markdown links
+    pub(crate) fn expand_title_links(&self, author_pk: &str, body: &str) -> String {
+        expand_title_links(body, |link| self.resolve_title_link(author_pk, link))
+    }
+
+    /// Target of a `[[…]]` link of a page of `author_pk`, `None` if no page has its title.
+    ///
+    /// The own pages are looked up in the list of own pages, the pages of the follows in the search
+    /// index. Links in the pages of other users are looked up once clicked.
+    fn resolve_title_link(&self, author_pk: &str, link: &TitleLink) -> Option<String> {
+        let AuthState::Authenticated { own_pk, file_cache } = &self.state else {
+            return None;
+        };
+
+        let author_pk = match &link.alias {
+            Some(alias) => &self.follows.iter().find(|follow| follow.matches_alias(alias))?.user_pk,
+            None => author_pk,
+        };
+
+        if author_pk == own_pk {
+            return file_cache
+                .iter()
+                .find(|(_, title)| same_title(title, &link.title))
+                .map(|(file_url, _)| file_url.clone());
+        }
+
+        match self.search_index.find_title(author_pk, &link.title) {
+            Some(page_id) => Some(WikiLink::page_url(author_pk, &page_id)),
+            None if link.alias.is_none() => Some(WikiLink::title_url(&link.title)),
+            None => None,
+        }
+    }
+
+    /// Profile name of a follow, "Me" for the own pages, or else the public key
+    pub(crate) fn author_name(&self, own_pk: &str, user_pk: &str) -> String {
+        if user_pk == own_pk {
+            return "Me".to_string();
+        }
+
+        self.follows
+            .iter()
+            .find(|follow| follow.user_pk == user_pk)
+            .map(|follow| follow.display_name().to_string())
+            .unwrap_or_else(|| user_pk.to_string())
+    }
+
+    /// Go back to the previously visited location, or to the list of own pages
+    pub(crate) fn navigate_back(&mut self) {
+        match self.navigation.back(self.current_location()) {
+            Some(location) => self.go_to(location),
+            None => self.navigate_to_wiki_list(),
+        }
+    }
+
+    pub(crate) fn navigate_forward(&mut self) {
+        if let Some(location) = self.navigation.forward(self.current_location()) {
+            self.go_to(location);
+        }
+    }
+
+    /// Alt+Left and Alt+Right, or the back and forward mouse buttons, outside of the editors
+    fn handle_navigation_shortcuts(&mut self, ctx: &egui::Context) {
+        if !matches!(self.view_state, ViewState::WikiList | ViewState::ViewWiki | ViewState::Graph) {
+            return;
+        }
+
+        let typing = ctx.wants_keyboard_input();
+        let (back, forward) = ctx.input(|i| {
+            let shortcut = |key| !typing && i.modifiers.alt && i.key_pressed(key);
+            (
+                shortcut(egui::Key::ArrowLeft) || i.pointer.button_pressed(egui::PointerButton::Extra1),
+                shortcut(egui::Key::ArrowRight) || i.pointer.button_pressed(egui::PointerButton::Extra2),
+            )
+        });
+
+        if back && (self.view_state != ViewState::WikiList || self.navigation.can_go_back()) {
+            self.navigate_back();
+        } else if forward {
+            self.navigate_forward();
+        }
+    }
+
+    /// Where the user is, as it would be restored by going back to it
+    fn current_location(&self) -> Location {
+        if self.view_state == ViewState::Graph {
+            return Location::Graph;
+        }
+        if self.view_state == ViewState::WikiList || self.selected_wiki_page_id.is_empty() {
+            return Location::WikiList;
+        }
+
+        Location::Page {
+            user_pk: self.selected_wiki_user_id.clone(),
+            page_id: self.selected_wiki_page_id.clone(),
+            scroll_offset: self.page_scroll_offset,
+            diff_selection: self.diff_selection.clone(),
+        }
+    }
+
+    /// Show a location from the navigation trail again
+    fn go_to(&mut self, location: Location) {
+        match location {
+            Location::WikiList => self.navigate_to_wiki_list(),
+            Location::Graph => self.show_graph(),
+            Location::Page {
+                user_pk,
+                page_id,
+                scroll_offset,
+                diff_selection,
+            } => {
+                self.clear_editor();
+                self.open_wiki_page(&user_pk, &page_id);
+                self.diff_selection = diff_selection;
+                self.restore_page_scroll = Some(scroll_offset);
+            }
+        }
+    }
+
+    fn open_wiki_page(&mut self, user_pk: &str, page_id: &str) {
         self.selected_wiki_user_id = user_pk.to_string();
         self.selected_wiki_page_id = page_id.to_string();
-        self.selected_wiki_fork_urls = self.discover_fork_urls(session, pub_storage, page_id);
-        self.selected_wiki_content.clear();
+        self.selected_wiki_fork_urls.clear();
+        self.selected_wiki_page = WikiPage::default();
+        self.selected_wiki_upstream = None;
+        self.selected_wiki_history.clear();
+        self.selected_wiki_revision = None;
+        self.selected_wiki_revision_page = None;
+        self.proposal_message.clear();
+        self.proposal_sent = false;
+        self.resolving_link = false;
+        self.pending_external_link = None;
+        // The content area keeps its scroll position across pages otherwise
+        self.page_scroll_offset = 0.0;
+        self.restore_page_scroll = Some(0.0);
+        self.scroll_to_heading = None;
+
+        self.page_loading = true;
+        self.forks_loading = true;
+        self.backend.send(Command::FetchPage {
+            user_pk: user_pk.to_string(),
+            page_id: page_id.to_string(),
+        });
+        self.backend.send(Command::DiscoverForks {
+            page_id: page_id.to_string(),
+        });
+        self.refresh_history();
 
         self.view_state = ViewState::ViewWiki;
     }
 
-    fn navigate_to_edit_selected_wiki_page(&mut self) {
-        self.edit_wiki_content = self.selected_wiki_content.clone();
-        self.view_state = ViewState::EditWiki;
+    /// Merge the upstream changes into the selected page, which must be an own fork
+    pub(crate) fn merge_upstream_into_selected_wiki_page(&mut self) {
+        self.merging = true;
+        self.backend.send(Command::PrepareMerge {
+            page_id: self.selected_wiki_page_id.clone(),
+            mine: self.selected_wiki_page.clone(),
+        });
+    }
+
+    /// Propose the changes of the selected page, an own fork, to the author of the forked page
+    pub(crate) fn propose_selected_wiki_page(&mut self) {
+        let Some(proposal) = Proposal::new(
+            &self.selected_wiki_page_id,
+            &self.selected_wiki_page,
+            &self.proposal_message,
+        ) else {
+            return;
+        };
+
+        self.proposing = true;
+        self.proposal_sent = false;
+        self.backend.send(Command::ProposeChanges { proposal });
     }
 
-    fn get_my_follows(&self, session: &PubkySession) -> Vec<String> {
-        get_list(session, "/pub/pubky.app/follows/", self.rt.clone())
-            .inspect_err(|e| log::error!("Failed to get follows: {e}"))
-            .map(|list| {
-                list.iter()
-                    .map(|path| path.split('/').last().unwrap_or(&path).to_string())
-                    .collect()
-            })
-            .unwrap_or_default()
+    /// Compare a received proposal with the current version of the own page it targets
+    pub(crate) fn view_proposal_diff(&mut self, own_pk: &str, received: &ReceivedProposal) {
+        let proposal = &received.proposal;
+        self.navigate_to_view_wiki_page(own_pk, &proposal.target_page_id);
+
+        self.diff_selection = vec![
+            DiffSide::Page {
+                user_pk: own_pk.to_string(),
+                page_id: proposal.target_page_id.clone(),
+            },
+            DiffSide::Revision {
+                user_pk: received.author_pk.clone(),
+                page_id: proposal.fork_page_id.clone(),
+                revision: Revision::at(proposal.fork_version),
+            },
+        ];
+        self.navigate_to_diff();
     }
 
-    fn discover_fork_urls(
-        &self,
-        session: &PubkySession,
-        pub_storage: &PublicStorage,
-        page_id: &str,
-    ) -> Vec<String> {
-        let follows = self.get_my_follows(session);
+    /// Merge a received proposal into the own page it targets
+    pub(crate) fn accept_proposal(&mut self, own_pk: &str, received: &ReceivedProposal) {
+        self.navigate_to_view_wiki_page(own_pk, &received.proposal.target_page_id);
 
-        let mut result = vec![];
+        self.merging = true;
+        self.backend.send(Command::PrepareProposalMerge {
+            proposal: received.clone(),
+        });
+    }
 
-        // Add the current user's version as a fork (root version)
-        let own_pk = session.info().public_key().to_string();
-        result.push(format!("{own_pk}/{page_id}"));
+    /// Compare the two versions ticked in the View Wiki view
+    pub(crate) fn navigate_to_diff(&mut self) {
+        self.diff_sides = self
+            .diff_selection
+            .iter()
+            .map(|side| (side.clone(), None))
+            .collect();
+        for side in &self.diff_selection {
+            self.backend.send(Command::FetchDiffSide { side: side.clone() });
+        }
 
-        for follow_pk in follows {
-            let fork_path = format!("pubky://{follow_pk}/pub/wiki.app/{page_id}");
-            log::info!("fork_path = {fork_path}");
-            let exists_fut = pub_storage.get(fork_path);
+        self.view_state = ViewState::DiffWiki;
+    }
 
-            match self.rt.block_on(exists_fut) {
-                Ok(_) => result.push(format!("{follow_pk}/{page_id}")),
-                Err(e) => log::error!("Failed to check if file exists: {e}"),
+    /// Tick or untick a version for comparison, keeping the two most recently ticked
+    pub(crate) fn toggle_diff_selection(&mut self, side: DiffSide) {
+        if let Some(pos) = self.diff_selection.iter().position(|s| *s == side) {
+            self.diff_selection.remove(pos);
+        } else {
+            if self.diff_selection.len() == 2 {
+                self.diff_selection.remove(0);
             }
+            self.diff_selection.push(side);
         }
-        result
+    }
+
+    /// Reload the revisions of the selected page in the background
+    pub(crate) fn refresh_history(&mut self) {
+        self.history_loading = true;
+        self.backend.send(Command::FetchHistory {
+            user_pk: self.selected_wiki_user_id.clone(),
+            page_id: self.selected_wiki_page_id.clone(),
+        });
+    }
+
+    /// Show a past revision of the selected page instead of its current version
+    pub(crate) fn view_revision(&mut self, revision: Revision) {
+        self.selected_wiki_revision = Some(revision.clone());
+        self.selected_wiki_revision_page = None;
+        self.backend.send(Command::FetchRevision {
+            user_pk: self.selected_wiki_user_id.clone(),
+            page_id: self.selected_wiki_page_id.clone(),
+            revision,
+        });
+    }
+
+    fn navigate_to_edit_selected_wiki_page(&mut self) {
+        let meta = &self.selected_wiki_page.meta;
+        self.edit_wiki_content = self.selected_wiki_page.body.clone();
+        self.edit_wiki_tags = meta.tags.join(", ");
+        self.edit_wiki_language = meta.language.clone().unwrap_or_default();
+        self.edit_base_version = Some(Revision::of(&self.selected_wiki_page));
+        self.view_state = ViewState::EditWiki;
+    }
+
+    /// Start creating a fork of the selected page
+    fn navigate_to_fork_selected_wiki_page(&mut self) {
+        let meta = &self.selected_wiki_page.meta;
+        self.edit_wiki_content = self.selected_wiki_page.body.clone();
+        self.edit_wiki_tags = meta.tags.join(", ");
+        self.edit_wiki_language = meta.language.clone().unwrap_or_default();
+        self.forked_from = Some(ForkedFrom::new(
+            &self.selected_wiki_user_id,
+            &self.selected_wiki_page_id,
+            &self.selected_wiki_page,
+        ));
+        self.view_state = ViewState::CreateWiki;
+    }
+
+    /// Show the links between the pages, leaving the current location in the navigation trail
+    pub(crate) fn navigate_to_graph(&mut self) {
+        let current = self.current_location();
+        if current != Location::Graph {
+            self.navigation.visit(current);
+        }
+        self.show_graph();
+    }
+
+    fn show_graph(&mut self) {
+        self.clear_editor();
+        self.clear_selected_wiki_page();
+        self.view_state = ViewState::Graph;
+    }
+
+    /// Go back to the list of own pages, dropping the selected page and any unsaved editor content
+    pub(crate) fn navigate_to_wiki_list(&mut self) {
+        self.clear_editor();
+        self.clear_selected_wiki_page();
+        self.view_state = ViewState::WikiList;
+    }
+
+    /// Forget the selected page and everything fetched or ticked for it
+    fn clear_selected_wiki_page(&mut self) {
+        self.selected_wiki_page_id.clear();
+        self.selected_wiki_user_id.clear();
+        self.selected_wiki_page = WikiPage::default();
+        self.selected_wiki_upstream = None;
+        self.selected_wiki_fork_urls.clear();
+        self.selected_wiki_history.clear();
+        self.selected_wiki_revision = None;
+        self.selected_wiki_revision_page = None;
+        self.diff_selection.clear();
+        self.diff_sides.clear();
+        self.merge = None;
+        self.proposal_message.clear();
+        self.proposal_sent = false;
+        self.resolving_link = false;
+        self.pending_external_link = None;
+        self.last_download = None;
+    }
+
+    /// Discard the content of the create and edit views
+    pub(crate) fn clear_editor(&mut self) {
+        self.edit_wiki_content.clear();
+        self.edit_wiki_tags.clear();
+        self.edit_wiki_language.clear();
+        self.edit_base_version = None;
+        self.editor_scroll = ScrollSync::default();
+        self.editor_selection = None;
+        self.link_picker_query = None;
+        self.attach_file_path = None;
+        self.uploaded_attachments.clear();
+        self.draft_id = None;
+        self.save_failed = false;
+        self.forked_from = None;
+    }
+
+    /// The page as the editor would save it: the selected page with the new body when editing,
+    /// or else a new page
+    pub(crate) fn editor_page(&self) -> WikiPage {
+        let mut page = if self.view_state == ViewState::EditWiki {
+            let mut page = self.selected_wiki_page.clone();
+            page.set_body(self.edit_wiki_content.clone());
+            page
+        } else {
+            let mut page = WikiPage::new(self.edit_wiki_content.clone());
+            page.meta.forked_from = self.forked_from.clone();
+            page
+        };
+        self.apply_editor_metadata(&mut page);
+
+        page
+    }
+
+    /// Apply the tags and language typed in the editor to the page metadata
+    pub(crate) fn apply_editor_metadata(&self, page: &mut WikiPage) {
+        page.meta.tags = parse_tags(&self.edit_wiki_tags);
+        let language = self.edit_wiki_language.trim();
+        page.meta.language = (!language.is_empty()).then(|| language.to_string());
     }
 }
 
 impl eframe::App for PubkyApp {
+    /// Keep the changes of the editor made since its draft was last saved
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if self.save_draft() {
+            let saved = self
+                .backend
+                .wait_for(Duration::from_secs(2), |event| matches!(event, Event::DraftSaved(_)));
+            if !saved {
+                log::error!("Failed to save the draft before closing");
+            }
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.ui(ctx);
+    }
+}
+
+impl PubkyApp {
+    /// Render a frame, apart from the window integration of `eframe`, so the UI can be driven
+    /// headless by the tests
+    pub(crate) fn ui(&mut self, ctx: &egui::Context) {
+        self.handle_backend_events();
+        if matches!(self.state, AuthState::Authenticated { .. }) {
+            self.handle_navigation_shortcuts(ctx);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical_centered(|ui| {
                 ui.add_space(30.0);
@@ -296,7 +1274,7 @@ impl eframe::App for PubkyApp {
                 ui.heading(egui::RichText::new(APP_NAME).size(24.0).strong());
                 ui.add_space(30.0);
 
-                let state = self.state.lock().unwrap().clone();
+                let state = self.state.clone();
 
                 match state {
                     AuthState::Initializing => {
@@ -332,29 +1310,37 @@ impl eframe::App for PubkyApp {
                         ui.spinner();
                     }
                     AuthState::Authenticated {
-                        session,
-                        ref pub_storage,
+                        ref own_pk,
                         ref file_cache,
                     } => {
-                        // Check if we need to refresh the files cache
-                        if self.needs_refresh {
-                            let state_clone = self.state.clone();
-
-                            Self::fetch_files_and_update(
-                                &session,
-                                pub_storage,
-                                self.rt.clone(),
-                                state_clone,
-                            );
-
-                            self.needs_refresh = false;
+                        if let Some(error) = self.last_error.clone() {
+                            ui.horizontal(|ui| {
+                                ui.colored_label(egui::Color32::RED, error);
+                                if ui.small_button("✖").clicked() {
+                                    self.last_error = None;
+                                }
+                            });
+                            ui.add_space(10.0);
                         }
 
-                        let own_pk = session.info().public_key();
+                        if self.offline {
+                            ui.label(
+                                egui::RichText::new("📴 Offline: showing cached pages, changes are synced once the homeserver is reachable")
+                                    .color(egui::Color32::GRAY),
+                            );
+                            ui.add_space(10.0);
+                        }
 
                         // Show different views based on view_state
                         match self.view_state {
                             ViewState::WikiList => {
+                                ui.add_space(10.0);
+                                ui.horizontal(|ui| {
+                                    ui.label(egui::RichText::new(format!("Logged in as {own_pk}")).monospace().small());
+                                    if ui.small_button("Log out").clicked() {
+                                        self.backend.send(Command::Logout);
+                                    }
+                                });
                                 ui.add_space(10.0);
                                 let create_button = ui.add_sized(
                                     [200.0, 40.0],
@@ -363,45 +1349,88 @@ impl eframe::App for PubkyApp {
                                 if create_button.clicked() {
                                     self.view_state = ViewState::CreateWiki;
                                 }
-                                ui.add_space(30.0);
-
-                                ui.label(egui::RichText::new("My Wiki Posts").size(18.0).strong());
-                                ui.add_space(15.0);
-
-                                // List all wiki posts as buttons
-                                egui::ScrollArea::vertical().show(ui, |ui| {
-                                    if file_cache.is_empty() {
-                                        ui.add_space(10.0);
-                                        ui.label(egui::RichText::new("No wiki posts yet. Create your first one!").italics().color(egui::Color32::GRAY));
-                                    } else {
-                                        let pk = own_pk.to_string();
-                                        for (file_url, file_title) in file_cache {
-                                            // Extract just the filename from the URL
-                                            let file_name =
-                                                file_url.split('/').last().unwrap_or(file_url);
-
-                                            ui.horizontal(|ui| {
-                                                if ui.button(egui::RichText::new(file_name).monospace()).clicked() {
-                                                    self.navigate_to_view_wiki_page(
-                                                        &pk,
-                                                        file_name,
-                                                        &session,
-                                                        pub_storage,
-                                                    );
-                                                }
-
-                                                ui.label(egui::RichText::new(file_title).strong());
-                                            });
-                                            ui.add_space(5.0);
-                                        }
+                                ui.add_space(10.0);
+                                let graph_button = ui.add_sized(
+                                    [200.0, 30.0],
+                                    egui::Button::new(egui::RichText::new("🕸 Knowledge Graph").size(15.0))
+                                );
+                                if graph_button.clicked() {
+                                    self.navigate_to_graph();
+                                }
+                                ui.add_space(20.0);
+
+                                ui.horizontal(|ui| {
+                                    let search_box = ui.add(
+                                        egui::TextEdit::singleline(&mut self.search_query)
+                                            .hint_text("🔍 Search my pages and my follows' pages")
+                                            .desired_width(300.0),
+                                    );
+                                    if search_box.changed() {
+                                        self.run_search();
+                                    }
+                                    if self.search_indexing {
+                                        ui.spinner();
+                                    } else if ui.small_button("⟳").on_hover_text("Rebuild the search index").clicked() {
+                                        self.refresh_indexes();
                                     }
                                 });
+                                ui.add_space(20.0);
+
+                                if !self.search_query.trim().is_empty() {
+                                    self.show_search_results(ui);
+                                } else {
+                                    inbox::show_inbox(self, ui, own_pk);
+                                    ui.add_space(20.0);
+                                    if !self.drafts.is_empty() {
+                                        drafts::show_drafts(self, ui, own_pk);
+                                        ui.add_space(20.0);
+                                    }
+
+                                    ui.horizontal(|ui| {
+                                        ui.label(egui::RichText::new("My Wiki Posts").size(18.0).strong());
+                                        if self.files_loading {
+                                            ui.spinner();
+                                        }
+                                    });
+                                    ui.add_space(15.0);
+
+                                    // List all wiki posts as buttons
+                                    egui::ScrollArea::vertical().show(ui, |ui| {
+                                        if file_cache.is_empty() && !self.files_loading {
+                                            ui.add_space(10.0);
+                                            ui.label(egui::RichText::new("No wiki posts yet. Create your first one!").italics().color(egui::Color32::GRAY));
+                                        } else {
+                                            let pk = own_pk.to_string();
+                                            let mut files: Vec<_> = file_cache.iter().collect();
+                                            files.sort_by(|a, b| a.1.cmp(b.1));
+                                            for (file_url, file_title) in files {
+                                                // Extract just the filename from the URL
+                                                let file_name =
+                                                    file_url.split('/').next_back().unwrap_or(file_url);
+
+                                                ui.horizontal(|ui| {
+                                                    if ui.button(egui::RichText::new(file_name).monospace()).clicked() {
+                                                        self.navigate_to_view_wiki_page(&pk, file_name);
+                                                    }
+
+                                                    ui.label(egui::RichText::new(file_title).strong());
+                                                });
+                                                ui.add_space(5.0);
+                                            }
+                                        }
+                                    });
+                                }
+
+                                if self.offer_draft_recovery {
+                                    drafts::show_draft_recovery(self, ctx, own_pk);
+                                }
                             }
-                            ViewState::CreateWiki => create_wiki::update(self, &session, ctx, ui),
-                            ViewState::EditWiki => edit_wiki::update(self, &session, ctx, ui),
-                            ViewState::ViewWiki => {
-                                view_wiki::update(self, &session, &pub_storage, ctx, ui)
-                            }
+                            ViewState::CreateWiki => create_wiki::update(self, own_pk, ctx, ui),
+                            ViewState::EditWiki => edit_wiki::update(self, own_pk, ctx, ui),
+                            ViewState::ViewWiki => view_wiki::update(self, own_pk, ctx, ui),
+                            ViewState::DiffWiki => diff_wiki::update(self, own_pk, ctx, ui),
+                            ViewState::MergeWiki => merge_wiki::update(self, own_pk, ctx, ui),
+                            ViewState::Graph => graph_view::update(self, own_pk, ctx, ui),
                         }
                     }
                     AuthState::Error(ref error) => {
@@ -414,57 +1443,3 @@ impl eframe::App for PubkyApp {
         });
     }
 }
-
-async fn initialize_auth() -> Result<(Pubky, PubkyAuthFlow, String)> {
-    let pubky = Pubky::new()?;
-    let caps = Capabilities::builder().write("/pub/wiki.app/").finish();
-    let flow = pubky.start_auth_flow(&caps)?;
-    let auth_url = flow.authorization_url().to_string();
-
-    Ok((pubky, flow, auth_url))
-}
-
-pub(crate) async fn create_wiki_post(
-    session: &PubkySession,
-    content: &str,
-    filename: Option<&str>,
-) -> Result<String> {
-    let path = if let Some(fname) = filename {
-        format!("/pub/wiki.app/{}", fname)
-    } else {
-        format!("/pub/wiki.app/{}", Uuid::new_v4())
-    };
-
-    // Create the post with the provided content
-    session.storage().put(&path, content.to_string()).await?;
-
-    log::info!("Created post at path: {}", path);
-
-    Ok(path)
-}
-
-pub(crate) async fn update_wiki_post(
-    session: &PubkySession,
-    page_id: &str,
-    content: &str,
-) -> Result<()> {
-    let path = format!("/pub/wiki.app/{}", page_id);
-
-    // Update the post with the provided content
-    session.storage().put(&path, content.to_string()).await?;
-
-    log::info!("Updated post at path: {}", path);
-
-    Ok(())
-}
-
-pub(crate) async fn delete_wiki_post(session: &PubkySession, page_id: &str) -> Result<()> {
-    let path = format!("/pub/wiki.app/{}", page_id);
-
-    // Delete the post
-    session.storage().delete(&path).await?;
-
-    log::info!("Deleted post at path: {}", path);
-
-    Ok(())
-}
@@ -0,0 +1,163 @@
+use crate::{backend::Command, diff_wiki::show_rows, PubkyApp, ViewState};
+
+use eframe::egui::{Context, Ui};
+use egui::CollapsingHeader;
+use wiki_core::{
+    history::Revision,
+    merge::{conflict_count, resolve_conflict, MergeSource, Resolution},
+    page::format_timestamp,
+};
+
+pub(crate) fn update(app: &mut PubkyApp, _own_pk: &str, _ctx: &Context, ui: &mut Ui) {
+    let Some(merge) = &app.merge else {
+        return;
+    };
+    let source = merge.source.label();
+    let applied = merge.result.applied;
+
+    let title = match &merge.source {
+        MergeSource::Upstream(_) => "Merge Upstream Changes".to_string(),
+        MergeSource::Proposal(received) => format!("Merge Changes Proposed by {}", received.author_pk),
+        MergeSource::Concurrent { .. } => "Resolve Conflicting Edit".to_string(),
+    };
+    ui.label(egui::RichText::new(title).size(20.0).strong());
+    ui.add_space(25.0);
+
+    if let MergeSource::Proposal(received) = &merge.source {
+        if !received.proposal.message.is_empty() {
+            ui.label(egui::RichText::new(&received.proposal.message).italics());
+            ui.add_space(10.0);
+        }
+    }
+
+    if let MergeSource::Concurrent { base, current } = &merge.source {
+        ui.label(format!(
+            "This page was saved elsewhere on {} after you started editing it",
+            format_timestamp(current.meta.updated_at)
+        ));
+        ui.label(egui::RichText::new("Your changes are kept in Drafts until the merge is saved").small());
+        ui.add_space(10.0);
+
+        let mine = merge.mine.body.clone();
+        let (base, current) = (base.body.clone(), current.body.clone());
+        CollapsingHeader::new("🔍 Changes saved elsewhere").show(ui, |ui| show_rows(ui, &base, &current));
+        CollapsingHeader::new("🔍 Your changes").show(ui, |ui| show_rows(ui, &base, &mine));
+        ui.add_space(10.0);
+    }
+
+    let conflicts = conflict_count(&app.edit_wiki_content);
+    ui.label(format!("{applied} {source} change(s) applied automatically"));
+    if conflicts == 0 {
+        ui.label("No conflicts left, review the merged content and save it");
+    } else {
+        ui.label(
+            egui::RichText::new(format!("{conflicts} conflict(s) left to resolve"))
+                .color(egui::Color32::from_rgb(200, 80, 80)),
+        );
+    }
+
+    // Conflicts can also be resolved by editing the content between the markers
+    for index in 0..conflicts {
+        ui.horizontal(|ui| {
+            ui.label(format!("Conflict #{}:", index + 1));
+            for (label, resolution) in [
+                ("Keep mine".to_string(), Resolution::Mine),
+                (format!("Take {source}"), Resolution::Upstream),
+                ("Keep both".to_string(), Resolution::Both),
+            ] {
+                if ui.small_button(label).clicked() {
+                    app.edit_wiki_content = resolve_conflict(&app.edit_wiki_content, index, resolution);
+                }
+            }
+        });
+    }
+
+    ui.add_space(12.0);
+    ui.label(egui::RichText::new("Content:").size(16.0));
+    ui.add_space(12.0);
+
+    egui::ScrollArea::vertical()
+        .max_height(400.0)
+        .show(ui, |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut app.edit_wiki_content)
+                    .desired_width(f32::INFINITY)
+                    .desired_rows(15)
+                    .font(egui::TextStyle::Monospace),
+            );
+        });
+
+    ui.add_space(25.0);
+
+    ui.horizontal(|ui| {
+        ui.add_enabled_ui(!app.saving && conflicts == 0, |ui| {
+            let save_button = ui.add_sized(
+                [120.0, 35.0],
+                egui::Button::new(egui::RichText::new("✓ Save merge").size(15.0))
+            );
+            if save_button.clicked() {
+                save_merge(app);
+            }
+        });
+
+        ui.add_space(10.0);
+        let cancel_button = ui.add_sized(
+            [120.0, 35.0],
+            egui::Button::new(egui::RichText::new("Cancel").size(15.0))
+        );
+        if cancel_button.clicked() {
+            app.clear_editor();
+            app.merge = None;
+            app.view_state = ViewState::ViewWiki;
+        }
+
+        if let Some(MergeSource::Concurrent { current, .. }) = app.merge.as_ref().map(|merge| &merge.source) {
+            ui.add_space(10.0);
+            let discard_button = ui.add_sized(
+                [120.0, 35.0],
+                egui::Button::new(egui::RichText::new("🗑 Discard mine").size(15.0))
+            );
+            if discard_button.on_hover_text("Keep the version saved elsewhere").clicked() {
+                app.selected_wiki_page = current.as_ref().clone();
+                if let Some(draft_id) = app.draft_id.take() {
+                    app.discard_draft(&draft_id);
+                }
+                app.clear_editor();
+                app.merge = None;
+                app.view_state = ViewState::ViewWiki;
+            }
+        }
+
+        if app.saving {
+            ui.add_space(10.0);
+            ui.spinner();
+        }
+    });
+}
+
+/// Save the merged content. Merging upstream also moves the fork base to the merged version.
+fn save_merge(app: &mut PubkyApp) {
+    let Some(merge) = &app.merge else {
+        return;
+    };
+
+    let mut page = merge.mine.clone();
+    page.set_body(app.edit_wiki_content.clone());
+    if let (MergeSource::Upstream(upstream), Some(forked_from)) = (&merge.source, &mut page.meta.forked_from) {
+        forked_from.version = upstream.meta.updated_at;
+        forked_from.content_hash = upstream.content_hash();
+    }
+
+    // The merge is made to the version saved elsewhere, if that's what it merges
+    let base_version = match &merge.source {
+        MergeSource::Concurrent { current, .. } => Revision::of(current),
+        _ => Revision::of(&merge.mine),
+    };
+
+    app.saving = true;
+    app.backend.send(Command::UpdatePage {
+        page_id: app.selected_wiki_page_id.clone(),
+        page,
+        base_version: Some(base_version),
+    });
+}
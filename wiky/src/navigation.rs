@@ -0,0 +1,69 @@
+use wiki_core::diff::DiffSide;
+
+/// A place the user navigated to, with what is needed to show it again as it was left
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Location {
+    WikiList,
+    Graph,
+    Page {
+        user_pk: String,
+        page_id: String,
+        scroll_offset: f32,
+        /// Versions ticked for comparison
+        diff_selection: Vec<DiffSide>,
+    },
+}
+
+impl Location {
+    pub(crate) fn is_page(&self, user_pk: &str, page_id: &str) -> bool {
+        matches!(self, Location::Page { user_pk: u, page_id: p, .. } if u == user_pk && p == page_id)
+    }
+}
+
+/// Browser-style trail of the visited locations, to go back and forward
+#[derive(Default)]
+pub(crate) struct Navigation {
+    back: Vec<Location>,
+    forward: Vec<Location>,
+}
+
+impl Navigation {
+    /// Leave `current` for a new location, which drops the locations gone back from
+    pub(crate) fn visit(&mut self, current: Location) {
+        self.back.push(current);
+        self.forward.clear();
+    }
+
+    /// Location to go back to from `current`, if any
+    pub(crate) fn back(&mut self, current: Location) -> Option<Location> {
+        let previous = self.back.pop()?;
+        self.forward.push(current);
+
+        Some(previous)
+    }
+
+    /// Location to go forward to from `current`, if any
+    pub(crate) fn forward(&mut self, current: Location) -> Option<Location> {
+        let next = self.forward.pop()?;
+        self.back.push(current);
+
+        Some(next)
+    }
+
+    pub(crate) fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub(crate) fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+
+    /// Drop a deleted page from the trail
+    pub(crate) fn forget_page(&mut self, user_pk: &str, page_id: &str) {
+        self.back.retain(|location| !location.is_page(user_pk, page_id));
+        self.forward.retain(|location| !location.is_page(user_pk, page_id));
+        // Dropping a page can leave the list twice in a row
+        self.back.dedup();
+        self.forward.dedup();
+    }
+}
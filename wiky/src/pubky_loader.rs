@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use egui::{
+    load::{Bytes, BytesLoadResult, BytesLoader, BytesPoll, LoadError},
+    mutex::Mutex,
+};
+use pubky::PublicStorage;
+use tokio::runtime::Handle;
+use wiki_core::{
+    cache::{CachedFile, PageCache},
+    storage::is_not_found,
+};
+
+/// How long a file that failed to load stays failed, before it is fetched again on next use
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+enum Entry {
+    Pending,
+    /// The bytes of the file, with their MIME type if the homeserver gave one
+    Loaded { bytes: Arc<[u8]>, mime: Option<String> },
+    Failed { error: String, at: Instant },
+}
+
+/// Loads the `pubky://` images of the pages, from the homeserver of any user, for the markdown
+/// viewers.
+///
+/// Files are kept in memory for the session, and in the [`PageCache`] across sessions, where they
+/// are revalidated with their ETag and served while their homeserver is unreachable.
+struct PubkyLoader {
+    storage: PublicStorage,
+    cache: Option<PageCache>,
+    runtime: Handle,
+    files: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+/// Let egui load `pubky://` images, fetching them on `runtime`
+pub(crate) fn install(ctx: &egui::Context, runtime: Handle) {
+    let _guard = runtime.enter();
+    let storage = match PublicStorage::new() {
+        Ok(storage) => storage,
+        Err(e) => {
+            log::error!("Failed to set up the loading of pubky:// images: {e}");
+            return;
+        }
+    };
+    let cache = PageCache::open()
+        .inspect_err(|e| log::error!("Failed to open the cache, pubky:// images won't be available offline: {e}"))
+        .ok();
+
+    ctx.add_bytes_loader(Arc::new(PubkyLoader {
+        storage,
+        cache,
+        runtime,
+        files: Arc::default(),
+    }));
+}
+
+impl PubkyLoader {
+    const ID: &'static str = egui::generate_loader_id!(PubkyLoader);
+
+    fn fetch(&self, ctx: &egui::Context, uri: &str) {
+        let storage = self.storage.clone();
+        let cache = self.cache.clone();
+        let files = self.files.clone();
+        let ctx = ctx.clone();
+        let uri = uri.to_string();
+
+        self.runtime.spawn(async move {
+            let entry = match fetch_file(&storage, cache.as_ref(), &uri).await {
+                Ok(file) => Entry::Loaded {
+                    bytes: file.content.into(),
+                    mime: file.mime,
+                },
+                Err(error) => {
+                    log::warn!("Failed to load {uri}: {error}");
+                    Entry::Failed {
+                        error,
+                        at: Instant::now(),
+                    }
+                }
+            };
+
+            // Forgotten in the meantime, so not wanted anymore
+            if let Some(pending) = files.lock().get_mut(&uri) {
+                *pending = entry;
+            }
+            ctx.request_repaint();
+        });
+    }
+}
+
+/// Fetch a file, using the cached version if it is still current.
+///
+/// Falls back to the cached version, even if stale, if the file can't be fetched but was not deleted.
+async fn fetch_file(storage: &PublicStorage, cache: Option<&PageCache>, url: &str) -> Result<CachedFile, String> {
+    let cached = cache.and_then(|cache| {
+        cache
+            .get_file(url)
+            .inspect_err(|e| log::error!("Failed to read cached file {url}: {e}"))
+            .ok()
+            .flatten()
+    });
+
+    // A HEAD request is enough to know if the cached version is still current
+    if let Some(cached) = cached.as_ref().filter(|cached| cached.etag.is_some()) {
+        if let Ok(Some(stats)) = storage.stats(url).await {
+            if stats.etag == cached.etag {
+                return Ok(cached.clone());
+            }
+        }
+    }
+
+    let response = match storage.get(url).await {
+        Ok(response) => response,
+        Err(e) if is_not_found(&e) => return Err(e.to_string()),
+        Err(e) => {
+            let Some(cached) = cached else {
+                return Err(e.to_string());
+            };
+            log::warn!("Error fetching {url}: {e}, using the cached version");
+            return Ok(cached);
+        }
+    };
+
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let mime = header("content-type");
+    let etag = header("etag");
+    let content = response.bytes().await.map_err(|e| e.to_string())?.to_vec();
+
+    let file = CachedFile { content, mime, etag };
+    if let Some(cache) = cache {
+        if let Err(e) = cache.put_file(url, &file) {
+            log::error!("Failed to cache file {url}: {e}");
+        }
+    }
+
+    Ok(file)
+}
+
+impl BytesLoader for PubkyLoader {
+    fn id(&self) -> &str {
+        Self::ID
+    }
+
+    fn load(&self, ctx: &egui::Context, uri: &str) -> BytesLoadResult {
+        if !uri.starts_with("pubky://") {
+            return Err(LoadError::NotSupported);
+        }
+
+        let mut files = self.files.lock();
+        match files.get(uri) {
+            Some(Entry::Loaded { bytes, mime }) => {
+                return Ok(BytesPoll::Ready {
+                    size: None,
+                    bytes: Bytes::Shared(bytes.clone()),
+                    mime: mime.clone(),
+                })
+            }
+            Some(Entry::Pending) => return Ok(BytesPoll::Pending { size: None }),
+            Some(Entry::Failed { error, at }) if at.elapsed() < RETRY_INTERVAL => {
+                return Err(LoadError::Loading(error.clone()))
+            }
+            // Not fetched yet, or failed long enough ago to try again
+            _ => {}
+        }
+
+        files.insert(uri.to_string(), Entry::Pending);
+        drop(files);
+        self.fetch(ctx, uri);
+
+        Ok(BytesPoll::Pending { size: None })
+    }
+
+    fn forget(&self, uri: &str) {
+        self.files.lock().remove(uri);
+    }
+
+    fn forget_all(&self) {
+        self.files.lock().clear();
+    }
+
+    fn byte_size(&self) -> usize {
+        self.files
+            .lock()
+            .values()
+            .map(|entry| match entry {
+                Entry::Loaded { bytes, .. } => bytes.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    fn has_pending(&self) -> bool {
+        self.files.lock().values().any(|entry| matches!(entry, Entry::Pending))
+    }
+}
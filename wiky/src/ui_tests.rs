@@ -0,0 +1,845 @@
+//! Headless UI tests: `PubkyApp` is driven frame by frame, with the tests playing the background
+//! worker, so navigation, saving and deleting are checked without a homeserver.
+
+use std::{collections::HashMap, path::PathBuf, sync::mpsc::Sender};
+
+use egui::{epaint::Shape, Event as InputEvent, Key, Modifiers, PointerButton, Pos2, Rect, Vec2};
+use tokio::sync::mpsc::UnboundedReceiver;
+use wiki_core::{
+    cache::Draft,
+    client::{SyncConflict, SyncReport},
+    diff::DiffSide,
+    graph::LinkGraph,
+    history::Revision,
+    links::WikiLink,
+    merge::{MergeSource, PreparedMerge},
+    page::WikiPage,
+    search::SearchIndex,
+};
+
+use crate::{
+    backend::{Backend, Command, Event},
+    AuthState, PubkyApp, ViewState,
+};
+
+const OWN_PK: &str = "own_pk";
+const OTHER_PK: &str = "other_pk";
+
+/// Runs the app in a headless egui context, with a fake worker on the other end of the backend
+struct Harness {
+    ctx: egui::Context,
+    app: PubkyApp,
+    commands: UnboundedReceiver<Command>,
+    events: Sender<Event>,
+    /// Input for the next frame
+    input: Vec<InputEvent>,
+    modifiers: Modifiers,
+    /// Visible texts of the last frame, with where they were drawn
+    texts: Vec<(String, Rect)>,
+}
+
+impl Harness {
+    /// The app signed in as `OWN_PK`, showing `pages`, given as `(page_id, title)`
+    fn signed_in(pages: &[(&str, &str)]) -> Self {
+        let (backend, commands, events) = Backend::detached();
+        let mut harness = Self {
+            ctx: egui::Context::default(),
+            app: PubkyApp::with_backend(backend),
+            commands,
+            events,
+            input: vec![],
+            modifiers: Modifiers::NONE,
+            texts: vec![],
+        };
+        // No file dialog is shown in the tests, as if it was cancelled
+        harness.app.pick_attachment = || None;
+
+        harness.reply(Event::Authenticated {
+            own_pk: OWN_PK.to_string(),
+            offline: false,
+        });
+        let files: HashMap<String, String> = pages
+            .iter()
+            .map(|(page_id, title)| (format!("pubky://{OWN_PK}/pub/wiki.app/{page_id}"), title.to_string()))
+            .collect();
+        harness.reply(Event::FilesFetched(files));
+        harness.sent();
+
+        harness
+    }
+
+    /// Render one frame
+    fn run(&mut self) {
+        let input = egui::RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Vec2::new(800.0, 1600.0))),
+            events: std::mem::take(&mut self.input),
+            modifiers: self.modifiers,
+            ..Default::default()
+        };
+        let output = self.ctx.run(input, |ctx| self.app.ui(ctx));
+
+        self.texts.clear();
+        for clipped in output.shapes {
+            collect_texts(&clipped.shape, clipped.clip_rect, &mut self.texts);
+        }
+    }
+
+    /// Post an event from the worker, and render the frame that handles it
+    fn reply(&mut self, event: Event) {
+        self.events.send(event).unwrap();
+        self.run();
+    }
+
+    /// Commands sent to the worker since the last call
+    fn sent(&mut self) -> Vec<Command> {
+        let mut commands = vec![];
+        while let Ok(command) = self.commands.try_recv() {
+            commands.push(command);
+        }
+        commands
+    }
+
+    fn has_text(&self, needle: &str) -> bool {
+        self.texts.iter().any(|(text, _)| text.contains(needle))
+    }
+
+    /// Click the widget labelled `label`, and render the frame that shows the outcome
+    fn click(&mut self, label: &str) {
+        let Some((_, rect)) = self.texts.iter().find(|(text, _)| text == label) else {
+            panic!("No `{label}` on screen, showing {:?}", self.texts.iter().map(|(t, _)| t).collect::<Vec<_>>());
+        };
+        let pos = rect.center();
+
+        // Widgets react to the pointer based on where they were in the previous frame
+        self.input.push(InputEvent::PointerMoved(pos));
+        self.run();
+        for pressed in [true, false] {
+            self.input.push(InputEvent::PointerButton {
+                pos,
+                button: PointerButton::Primary,
+                pressed,
+                modifiers: Modifiers::NONE,
+            });
+            self.run();
+        }
+        self.run();
+    }
+
+    /// Press a key with modifiers, and render the frame that shows the outcome
+    fn press(&mut self, key: Key, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+        self.input.push(InputEvent::Key {
+            key,
+            physical_key: None,
+            pressed: true,
+            repeat: false,
+            modifiers,
+        });
+        self.run();
+        self.modifiers = Modifiers::NONE;
+        self.run();
+    }
+
+    fn selected_page(&self) -> (&str, &str) {
+        (&self.app.selected_wiki_user_id, &self.app.selected_wiki_page_id)
+    }
+
+    /// Open a page from the list of own pages, and answer its fetch
+    fn open_own_page(&mut self, page_id: &str, body: &str) {
+        self.click(page_id);
+        self.sent();
+        self.reply(Event::PageFetched {
+            user_pk: OWN_PK.to_string(),
+            page_id: page_id.to_string(),
+            result: Ok(WikiPage::new(body.to_string())),
+        });
+    }
+
+    fn file_titles(&self) -> Vec<String> {
+        let AuthState::Authenticated { file_cache, .. } = &self.app.state else {
+            panic!("Not signed in");
+        };
+        file_cache.values().cloned().collect()
+    }
+}
+
+fn collect_texts(shape: &Shape, clip_rect: Rect, texts: &mut Vec<(String, Rect)>) {
+    match shape {
+        Shape::Text(text) => {
+            let rect = text.visual_bounding_rect();
+            if clip_rect.intersects(rect) {
+                texts.push((text.galley.text().to_string(), rect));
+            }
+        }
+        Shape::Vec(shapes) => {
+            for shape in shapes {
+                collect_texts(shape, clip_rect, texts);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn created_page_is_saved_and_listed() {
+    let mut harness = Harness::signed_in(&[]);
+    assert!(harness.has_text("No wiki posts yet"));
+
+    harness.click("✨ Create New Wiki Page");
+    assert!(harness.app.view_state == ViewState::CreateWiki);
+    assert!(harness.has_text("Create New Wiki Page"));
+
+    harness.app.edit_wiki_content = "# Rust\n\nA language".to_string();
+    harness.click("💾 Save");
+    let sent = harness.sent();
+    let [Command::SaveDraft { draft }, Command::CreatePage { page, filename: None }] = &sent[..] else {
+        panic!("Expected the draft to be saved, then a CreatePage command");
+    };
+    assert_eq!(draft.page.body, "# Rust\n\nA language");
+    assert_eq!(page.body, "# Rust\n\nA language");
+    assert!(harness.app.saving);
+
+    harness.reply(Event::PageCreated(Ok(("/pub/wiki.app/rust".to_string(), page.clone()))));
+    assert!(harness.app.view_state == ViewState::WikiList);
+    assert!(!harness.app.saving);
+    assert!(harness.app.edit_wiki_content.is_empty());
+    assert!(harness.has_text("Rust"));
+    assert!(harness.has_text("rust"));
+    assert!(matches!(&harness.sent()[..], [Command::DiscardDraft { id }] if *id == draft.id));
+    assert!(harness.app.drafts.is_empty());
+}
+
+#[test]
+fn cancelled_creation_is_kept_in_drafts() {
+    let mut harness = Harness::signed_in(&[]);
+    harness.click("✨ Create New Wiki Page");
+    harness.app.edit_wiki_content = "# Draft".to_string();
+
+    harness.click("Cancel");
+
+    assert!(harness.app.view_state == ViewState::WikiList);
+    assert!(harness.app.edit_wiki_content.is_empty());
+    let sent = harness.sent();
+    let [Command::SaveDraft { draft }] = &sent[..] else {
+        panic!("Expected the draft to be saved");
+    };
+    assert_eq!(draft.page_id, None);
+    assert!(harness.has_text("📝 Drafts (1)"));
+
+    harness.click("✏ Restore");
+    assert!(harness.app.view_state == ViewState::CreateWiki);
+    assert_eq!(harness.app.edit_wiki_content, "# Draft");
+}
+
+#[test]
+fn untouched_editor_leaves_no_draft() {
+    let mut harness = Harness::signed_in(&[]);
+    harness.click("✨ Create New Wiki Page");
+
+    harness.click("Cancel");
+
+    assert!(harness.sent().is_empty());
+    assert!(!harness.has_text("📝 Drafts"));
+}
+
+#[test]
+fn own_page_is_shown_with_an_edit_button() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+
+    harness.click("rust");
+    let sent = harness.sent();
+    assert!(harness.app.view_state == ViewState::ViewWiki);
+    assert!(sent.iter().any(|c| matches!(c, Command::FetchPage { user_pk, page_id } if user_pk == OWN_PK && page_id == "rust")));
+    assert!(sent.iter().any(|c| matches!(c, Command::DiscoverForks { page_id } if page_id == "rust")));
+    assert!(sent.iter().any(|c| matches!(c, Command::FetchHistory { .. })));
+
+    harness.reply(Event::PageFetched {
+        user_pk: OWN_PK.to_string(),
+        page_id: "rust".to_string(),
+        result: Ok(WikiPage::new("# Rust\n\nA systems language".to_string())),
+    });
+    assert!(harness.has_text("A systems language"));
+    assert!(harness.has_text("✏ Edit"));
+    assert!(!harness.has_text("🍴 Fork"));
+}
+
+#[test]
+fn back_from_a_page_forgets_it() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    harness.open_own_page("rust", "# Rust");
+
+    harness.click("← Back");
+
+    assert!(harness.app.view_state == ViewState::WikiList);
+    assert!(harness.app.selected_wiki_page_id.is_empty());
+    assert!(harness.app.selected_wiki_user_id.is_empty());
+    assert_eq!(harness.app.selected_wiki_page, WikiPage::default());
+}
+
+#[test]
+fn edited_page_is_updated() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    harness.open_own_page("rust", "# Rust");
+
+    harness.click("✏ Edit");
+    assert!(harness.app.view_state == ViewState::EditWiki);
+    assert_eq!(harness.app.edit_wiki_content, "# Rust");
+
+    harness.app.edit_wiki_content = "# Rust\n\nA language".to_string();
+    harness.app.edit_wiki_tags = "lang, systems".to_string();
+    harness.click("✓ Update");
+    let sent = harness.sent();
+    let [Command::SaveDraft { .. }, Command::UpdatePage { page_id, page, base_version }] = &sent[..] else {
+        panic!("Expected the draft to be saved, then an UpdatePage command");
+    };
+    assert_eq!(*base_version, Some(Revision::of(&harness.app.selected_wiki_page)));
+    assert_eq!(page_id, "rust");
+    assert_eq!(page.body, "# Rust\n\nA language");
+    assert_eq!(page.meta.tags, vec!["lang", "systems"]);
+
+    harness.reply(Event::PageUpdated {
+        page_id: page_id.clone(),
+        result: Ok(page.clone()),
+    });
+    assert!(harness.app.view_state == ViewState::WikiList);
+    assert!(harness.app.edit_wiki_content.is_empty());
+    assert!(harness.app.selected_wiki_page_id.is_empty());
+}
+
+#[test]
+fn update_finishing_after_opening_another_page_leaves_it_shown() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust"), ("go", "Go")]);
+    harness.open_own_page("rust", "# Rust");
+    harness.click("← Back");
+    harness.open_own_page("go", "# Go\n\nA language");
+
+    harness.reply(Event::PageUpdated {
+        page_id: "rust".to_string(),
+        result: Ok(WikiPage::new("# Rust\n\nA restored version".to_string())),
+    });
+    assert_eq!(harness.selected_page(), (OWN_PK, "go"));
+    assert_eq!(harness.app.selected_wiki_page.body, "# Go\n\nA language");
+    assert!(harness.has_text("A language"));
+}
+
+#[test]
+fn cancelled_edit_clears_the_editor_and_keeps_a_draft() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    harness.open_own_page("rust", "# Rust");
+    harness.click("✏ Edit");
+    harness.app.edit_wiki_content = "# Unsaved".to_string();
+
+    harness.click("Cancel");
+
+    assert!(harness.app.view_state == ViewState::WikiList);
+    assert!(harness.app.edit_wiki_content.is_empty());
+    assert!(harness.app.selected_wiki_page_id.is_empty());
+    assert!(harness.app.selected_wiki_user_id.is_empty());
+    let sent = harness.sent();
+    assert!(matches!(&sent[..], [Command::SaveDraft { draft }] if draft.page_id.as_deref() == Some("rust")));
+}
+
+#[test]
+fn failed_update_stays_in_the_editor() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    harness.open_own_page("rust", "# Rust");
+    harness.click("✏ Edit");
+    harness.app.edit_wiki_content = "# Rust\n\nA language".to_string();
+    harness.click("✓ Update");
+    harness.sent();
+
+    harness.reply(Event::PageUpdated {
+        page_id: "rust".to_string(),
+        result: Err("Failed to update wiki post: boom".to_string()),
+    });
+
+    assert!(harness.app.view_state == ViewState::EditWiki);
+    assert!(!harness.app.saving);
+    assert_eq!(harness.app.edit_wiki_content, "# Rust\n\nA language");
+    assert!(harness.has_text("Failed to update wiki post: boom"));
+    assert_eq!(harness.app.drafts.len(), 1);
+
+    harness.click("⟳ Retry");
+    let sent = harness.sent();
+    assert!(matches!(&sent[..], [Command::UpdatePage { page_id, .. }] if page_id == "rust"));
+    assert!(harness.app.saving);
+}
+
+#[test]
+fn conflicting_update_is_merged_with_the_version_saved_elsewhere() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    harness.open_own_page("rust", "# Rust\n\nA language\n\nBy Mozilla");
+    harness.click("✏ Edit");
+    harness.app.edit_wiki_content = "# Rust\n\nA language\n\nBy the Rust Foundation".to_string();
+    harness.click("✓ Update");
+    let sent = harness.sent();
+    let [Command::SaveDraft { .. }, Command::UpdatePage { page, .. }] = &sent[..] else {
+        panic!("Expected the draft to be saved, then an UpdatePage command");
+    };
+
+    let base = harness.app.selected_wiki_page.clone();
+    let mut current = base.clone();
+    current.set_body("# Rust\n\nA systems language\n\nBy Mozilla".to_string());
+    current.meta.updated_at = base.meta.updated_at + 60;
+    harness.reply(Event::PageConflicted {
+        page_id: "rust".to_string(),
+        merge: Box::new(PreparedMerge::new(
+            &base.body,
+            page.clone(),
+            &current.body,
+            MergeSource::Concurrent {
+                base: Box::new(base.clone()),
+                current: Box::new(current.clone()),
+            },
+        )),
+    });
+    assert!(harness.app.view_state == ViewState::MergeWiki);
+    assert!(!harness.app.saving);
+    assert!(harness.has_text("Resolve Conflicting Edit"));
+    assert_eq!(harness.app.edit_wiki_content, "# Rust\n\nA systems language\n\nBy the Rust Foundation");
+
+    harness.click("✓ Save merge");
+    let sent = harness.sent();
+    let [Command::UpdatePage { page, base_version, .. }] = &sent[..] else {
+        panic!("Expected a single UpdatePage command");
+    };
+    assert_eq!(page.body, "# Rust\n\nA systems language\n\nBy the Rust Foundation");
+    assert_eq!(*base_version, Some(Revision::of(&current)));
+}
+
+#[test]
+fn offline_update_in_conflict_on_sync_is_merged() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    let base = WikiPage::new("# Rust\n\nA language\n\nBy Mozilla".to_string());
+    let mut mine = base.clone();
+    mine.set_body("# Rust\n\nA language\n\nBy the Rust Foundation".to_string());
+    let mut current = base.clone();
+    current.set_body("# Rust\n\nA systems language\n\nBy Mozilla".to_string());
+    let conflict = SyncConflict {
+        page_id: "rust".to_string(),
+        draft_id: "draft".to_string(),
+        merge: PreparedMerge::new(
+            &base.body,
+            mine,
+            &current.body,
+            MergeSource::Concurrent {
+                base: Box::new(base.clone()),
+                current: Box::new(current.clone()),
+            },
+        ),
+    };
+
+    harness.reply(Event::PendingWritesSynced(Ok(SyncReport {
+        conflicts: vec![conflict],
+        ..Default::default()
+    })));
+    assert!(harness.app.view_state == ViewState::MergeWiki);
+    assert_eq!(harness.selected_page(), (OWN_PK, "rust"));
+    assert_eq!(harness.app.edit_wiki_content, "# Rust\n\nA systems language\n\nBy the Rust Foundation");
+    assert!(harness.sent().iter().any(|command| matches!(command, Command::FetchDrafts)));
+
+    harness.click("✓ Save merge");
+    let sent = harness.sent();
+    let [Command::UpdatePage { base_version, .. }] = &sent[..] else {
+        panic!("Expected a single UpdatePage command");
+    };
+    assert_eq!(*base_version, Some(Revision::of(&current)));
+    assert_eq!(harness.app.draft_id.as_deref(), Some("draft"));
+}
+
+#[test]
+fn drafts_left_by_a_previous_session_are_offered_for_restoring() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    let draft = Draft {
+        id: "draft".to_string(),
+        page_id: Some("rust".to_string()),
+        base_version: Some(Revision::at(1)),
+        page: WikiPage::new("# Rust\n\nUnsaved".to_string()),
+        saved_at: 1,
+    };
+    harness.reply(Event::DraftsFetched(Ok(vec![draft])));
+    // Windows are laid out on their first frame, and shown from the next one
+    harness.run();
+    assert!(harness.has_text("Restore unsaved changes?"));
+
+    harness.click("✏ Restore \"Rust\"");
+    assert!(harness.app.view_state == ViewState::EditWiki);
+    assert_eq!(harness.selected_page(), (OWN_PK, "rust"));
+    assert_eq!(harness.app.edit_wiki_content, "# Rust\n\nUnsaved");
+    assert_eq!(harness.app.draft_id.as_deref(), Some("draft"));
+}
+
+#[test]
+fn drafts_of_failed_saves_are_not_offered_for_restoring() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    harness.reply(Event::DraftsFetched(Ok(vec![])));
+
+    // An offline update refused on sync is kept in a draft, fetched once the sync is done
+    harness.reply(Event::PendingWritesSynced(Ok(SyncReport {
+        failed: vec!["Rust: refused".to_string()],
+        ..Default::default()
+    })));
+    assert!(harness.sent().iter().any(|command| matches!(command, Command::FetchDrafts)));
+    let draft = Draft {
+        id: "draft".to_string(),
+        page_id: Some("rust".to_string()),
+        base_version: Some(Revision::at(1)),
+        page: WikiPage::new("# Rust\n\nUnsaved".to_string()),
+        saved_at: 1,
+    };
+    harness.reply(Event::DraftsFetched(Ok(vec![draft])));
+    harness.run();
+
+    assert_eq!(harness.app.drafts.len(), 1);
+    assert!(harness.has_text("📝 Drafts (1)"));
+    assert!(!harness.has_text("Restore unsaved changes?"));
+}
+
+#[test]
+fn deleted_page_leaves_the_list() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust"), ("go", "Go")]);
+    harness.open_own_page("rust", "# Rust");
+    harness.click("✏ Edit");
+
+    harness.click("🗑 Delete");
+    let sent = harness.sent();
+    let [Command::DeletePage { page_id }] = &sent[..] else {
+        panic!("Expected a single DeletePage command");
+    };
+    assert_eq!(page_id, "rust");
+
+    harness.reply(Event::PageDeleted {
+        page_id: page_id.clone(),
+        result: Ok(()),
+    });
+    assert!(harness.app.view_state == ViewState::WikiList);
+    assert_eq!(harness.file_titles(), vec!["Go"]);
+    assert!(harness.has_text("Go"));
+    assert!(!harness.has_text("Rust"));
+}
+
+#[test]
+fn page_of_another_user_is_forked_under_the_same_id() {
+    let mut harness = Harness::signed_in(&[]);
+    harness.app.navigate_to_view_wiki_page(OTHER_PK, "rust");
+    harness.reply(Event::PageFetched {
+        user_pk: OTHER_PK.to_string(),
+        page_id: "rust".to_string(),
+        result: Ok(WikiPage::new("# Rust".to_string())),
+    });
+    assert!(harness.has_text("🍴 Fork"));
+    assert!(!harness.has_text("✏ Edit"));
+    harness.sent();
+
+    harness.click("🍴 Fork");
+    assert!(harness.app.view_state == ViewState::CreateWiki);
+    assert_eq!(harness.app.edit_wiki_content, "# Rust");
+
+    harness.click("💾 Save");
+    let sent = harness.sent();
+    let [Command::CreatePage { page, filename }] = &sent[..] else {
+        panic!("Expected a single CreatePage command");
+    };
+    assert_eq!(filename.as_deref(), Some("rust"));
+    let forked_from = page.meta.forked_from.as_ref().unwrap();
+    assert_eq!(forked_from.author_pk, OTHER_PK);
+    assert_eq!(forked_from.page_id, "rust");
+}
+
+#[test]
+fn back_and_forward_follow_the_visited_pages() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    harness.open_own_page("rust", "# Rust");
+    harness.reply(Event::ForksDiscovered {
+        page_id: "rust".to_string(),
+        fork_urls: vec![WikiLink::page_url(OWN_PK, "rust"), WikiLink::page_url(OTHER_PK, "rust")],
+    });
+
+    harness.click("🔀 Available Forks (2)");
+    harness.click(&format!("Fork: {OTHER_PK}"));
+    assert_eq!(harness.selected_page(), (OTHER_PK, "rust"));
+
+    harness.click("← Back");
+    assert!(harness.app.view_state == ViewState::ViewWiki);
+    assert_eq!(harness.selected_page(), (OWN_PK, "rust"));
+    assert!(harness.sent().iter().any(|c| matches!(c, Command::FetchPage { user_pk, .. } if user_pk == OWN_PK)));
+
+    harness.click("Forward →");
+    assert_eq!(harness.selected_page(), (OTHER_PK, "rust"));
+
+    harness.click("← Back");
+    harness.click("← Back");
+    assert!(harness.app.view_state == ViewState::WikiList);
+}
+
+#[test]
+fn visiting_a_page_drops_the_pages_gone_back_from() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust"), ("go", "Go")]);
+    harness.open_own_page("rust", "# Rust");
+    harness.click("← Back");
+    assert!(harness.app.navigation.can_go_forward());
+
+    harness.open_own_page("go", "# Go");
+
+    assert!(!harness.app.navigation.can_go_forward());
+}
+
+#[test]
+fn going_back_restores_the_comparison_and_the_scroll_position() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    harness.open_own_page("rust", "# Rust");
+    let side = DiffSide::Page {
+        user_pk: OTHER_PK.to_string(),
+        page_id: "rust".to_string(),
+    };
+    harness.app.diff_selection = vec![side.clone()];
+    harness.app.page_scroll_offset = 120.0;
+
+    harness.app.navigate_to_view_wiki_page(OTHER_PK, "rust");
+    harness.app.diff_selection.clear();
+    harness.app.navigate_back();
+
+    assert_eq!(harness.selected_page(), (OWN_PK, "rust"));
+    assert_eq!(harness.app.diff_selection, vec![side]);
+    assert_eq!(harness.app.restore_page_scroll, Some(120.0));
+}
+
+#[test]
+fn alt_arrows_go_back_and_forward() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    harness.open_own_page("rust", "# Rust");
+
+    harness.press(Key::ArrowLeft, Modifiers::ALT);
+    assert!(harness.app.view_state == ViewState::WikiList);
+
+    harness.press(Key::ArrowRight, Modifiers::ALT);
+    assert!(harness.app.view_state == ViewState::ViewWiki);
+    assert_eq!(harness.selected_page(), (OWN_PK, "rust"));
+}
+
+#[test]
+fn title_links_open_the_page_of_the_same_author() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust"), ("go", "Go")]);
+    harness.open_own_page("rust", "# Rust\n\nUnlike [Go](wiki:Go), it has no garbage collector");
+    harness.sent();
+
+    harness.click("Go");
+    let sent = harness.sent();
+    let [Command::ResolveTitle { user_pk, title, heading }] = &sent[..] else {
+        panic!("Expected a single ResolveTitle command");
+    };
+    assert_eq!((user_pk.as_str(), title.as_str(), heading), (OWN_PK, "Go", &None));
+
+    harness.reply(Event::TitleResolved {
+        user_pk: OWN_PK.to_string(),
+        title: "Go".to_string(),
+        heading: None,
+        result: Ok(Some("go".to_string())),
+    });
+    assert_eq!(harness.selected_page(), (OWN_PK, "go"));
+}
+
+#[test]
+fn links_to_a_heading_scroll_to_it() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust"), ("go", "Go")]);
+    harness.open_own_page("rust", "# Rust\n\nUnlike [Go](wiki:Go#goroutines), it has no garbage collector");
+    harness.sent();
+
+    harness.click("Go");
+    let sent = harness.sent();
+    let [Command::ResolveTitle { heading, .. }] = &sent[..] else {
+        panic!("Expected a single ResolveTitle command");
+    };
+    assert_eq!(heading.as_deref(), Some("goroutines"));
+
+    harness.reply(Event::TitleResolved {
+        user_pk: OWN_PK.to_string(),
+        title: "Go".to_string(),
+        heading: Some("goroutines".to_string()),
+        result: Ok(Some("go".to_string())),
+    });
+    assert_eq!(harness.selected_page(), (OWN_PK, "go"));
+    assert_eq!(harness.app.scroll_to_heading.as_deref(), Some("goroutines"));
+
+    let history = "A paragraph about the history of Go.\n\n".repeat(40);
+    harness.reply(Event::PageFetched {
+        user_pk: OWN_PK.to_string(),
+        page_id: "go".to_string(),
+        result: Ok(WikiPage::new(format!(
+            "# Go\n\n[tour]: https://go.dev/tour\n\n{history}## Goroutines\n\nLightweight threads, see [the tour][tour]"
+        ))),
+    });
+    harness.run();
+    assert_eq!(harness.app.scroll_to_heading, None);
+    // The content is scrolled from the frame after
+    harness.run();
+    assert!(harness.app.page_scroll_offset > 0.0);
+    assert!(harness.has_text("Lightweight threads"));
+    assert!(!harness.has_text("pubky-wiki:heading"));
+
+    // Links defined elsewhere in the page still resolve
+    harness.click("the tour");
+    assert_eq!(harness.app.pending_external_link.as_deref(), Some("https://go.dev/tour"));
+}
+
+#[test]
+fn external_links_are_opened_once_confirmed() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    harness.open_own_page("rust", "# Rust\n\nSee [the book](https://doc.rust-lang.org/book/)");
+
+    harness.click("the book");
+    assert_eq!(harness.app.pending_external_link.as_deref(), Some("https://doc.rust-lang.org/book/"));
+    assert!(harness.has_text("Open external link?"));
+    assert_eq!(harness.selected_page(), (OWN_PK, "rust"));
+
+    harness.click("Cancel");
+    assert_eq!(harness.app.pending_external_link, None);
+    assert!(!harness.has_text("Open external link?"));
+}
+
+#[test]
+fn double_bracket_links_open_own_pages_or_offer_to_create_them() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust"), ("go", "Go")]);
+    harness.open_own_page("rust", "# Rust\n\nUnlike [[Go]], it has [[Traits|traits]]");
+
+    harness.click("Go");
+    assert_eq!(harness.selected_page(), (OWN_PK, "go"));
+
+    harness.click("← Back");
+    harness.reply(Event::PageFetched {
+        user_pk: OWN_PK.to_string(),
+        page_id: "rust".to_string(),
+        result: Ok(WikiPage::new("# Rust\n\nUnlike [[Go]], it has [[Traits|traits]]".to_string())),
+    });
+    harness.click("traits ✨");
+    assert!(harness.app.view_state == ViewState::CreateWiki);
+    assert_eq!(harness.app.edit_wiki_content, "# Traits\n\n");
+}
+
+#[test]
+fn pages_linking_here_are_listed_and_opened() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    let mut links = LinkGraph::default();
+    links.insert(OTHER_PK, "cargo", &WikiPage::new(format!("# Cargo\n\nBuilds [Rust]({})", WikiLink::page_url(OWN_PK, "rust"))));
+    harness.reply(Event::IndexesBuilt {
+        search: SearchIndex::default(),
+        links,
+    });
+    harness.open_own_page("rust", "# Rust");
+
+    harness.click("🔗 Linked from (1)");
+    harness.click("Cargo");
+
+    assert_eq!(harness.selected_page(), (OTHER_PK, "cargo"));
+}
+
+#[test]
+fn graph_opens_pages_and_filters_by_author() {
+    let mut harness = Harness::signed_in(&[("rust", "Rust")]);
+    let mut links = LinkGraph::default();
+    links.insert(OWN_PK, "rust", &WikiPage::new("# Rust".to_string()));
+    links.insert(OTHER_PK, "cargo", &WikiPage::new("# Cargo\n\nBuilds [[@own/Rust]]".to_string()));
+    harness.reply(Event::IndexesBuilt {
+        search: SearchIndex::default(),
+        links,
+    });
+
+    harness.click("🕸 Knowledge Graph");
+    assert!(harness.app.view_state == ViewState::Graph);
+    assert!(harness.has_text("Rust") && harness.has_text("Cargo"));
+
+    harness.app.graph_author_filter = Some(OWN_PK.to_string());
+    harness.run();
+    assert!(!harness.has_text("Cargo"));
+
+    harness.click("Rust");
+    assert_eq!(harness.selected_page(), (OWN_PK, "rust"));
+    harness.click("← Back");
+    assert!(harness.app.view_state == ViewState::Graph);
+}
+
+#[test]
+fn editor_previews_the_rendered_page() {
+    let mut harness = Harness::signed_in(&[]);
+    harness.click("✨ Create New Wiki Page");
+    harness.app.edit_wiki_content = "# Rust\n\nA **systems** language".to_string();
+    harness.run();
+    assert!(harness.has_text("A **systems** language"));
+
+    harness.click("Preview");
+    assert!(!harness.has_text("A **systems** language"));
+    assert!(harness.has_text("systems"));
+
+    harness.click("Split");
+    assert!(harness.has_text("A **systems** language"));
+    assert!(harness.texts.iter().any(|(text, _)| text == "systems"));
+}
+
+#[test]
+fn toolbar_formats_the_selection_and_links_to_pages() {
+    let mut harness = Harness::signed_in(&[("ownership", "Ownership")]);
+    harness.click("✨ Create New Wiki Page");
+    harness.app.edit_wiki_content = "Rust is fast".to_string();
+    harness.app.editor_selection = Some(8..12);
+    harness.run();
+
+    harness.click("B");
+    assert_eq!(harness.app.edit_wiki_content, "Rust is **fast**");
+    assert_eq!(harness.app.editor_selection, Some(10..14));
+
+    harness.app.editor_selection = Some(16..16);
+    harness.click("📄 Link to page");
+    harness.click("Ownership");
+    assert_eq!(
+        harness.app.edit_wiki_content,
+        format!("Rust is **fast**[Ownership]({})", WikiLink::page_url(OWN_PK, "ownership"))
+    );
+    assert!(!harness.has_text("Search your pages and your follows' pages"));
+}
+
+#[test]
+fn attached_files_are_uploaded_and_inserted_at_the_cursor() {
+    let mut harness = Harness::signed_in(&[]);
+    harness.click("✨ Create New Wiki Page");
+    harness.app.edit_wiki_content = "# Cats\n\n".to_string();
+    harness.app.editor_selection = Some(8..8);
+    harness.run();
+
+    // Without a file from the file dialog, the path is typed
+    harness.click("📎 Attach file");
+    harness.run();
+    harness.app.attach_file_path = Some("/home/me/cat.png".to_string());
+    harness.run();
+    harness.click("📎 Attach");
+    let sent = harness.sent();
+    let [Command::UploadAttachment { path }] = &sent[..] else {
+        panic!("Expected a single UploadAttachment command");
+    };
+    assert_eq!(path.to_str(), Some("/home/me/cat.png"));
+    assert!(harness.has_text("Uploading 1 file(s)…"));
+
+    let url = WikiLink::attachment_url(OWN_PK, "/pub/wiki.app/files/id-cat.png");
+    harness.reply(Event::AttachmentUploaded {
+        file_name: "cat.png".to_string(),
+        result: Ok(url.clone()),
+    });
+    harness.run();
+    assert_eq!(harness.app.edit_wiki_content, format!("# Cats\n\n![cat.png]({url})"));
+    assert!(!harness.has_text("Uploading 1 file(s)…"));
+}
+
+#[test]
+fn files_picked_in_the_file_dialog_are_uploaded_right_away() {
+    let mut harness = Harness::signed_in(&[]);
+    harness.app.pick_attachment = || Some(PathBuf::from("/home/me/cat.png"));
+    harness.click("✨ Create New Wiki Page");
+
+    harness.click("📎 Attach file");
+    let sent = harness.sent();
+    let [Command::UploadAttachment { path }] = &sent[..] else {
+        panic!("Expected a single UploadAttachment command");
+    };
+    assert_eq!(path.to_str(), Some("/home/me/cat.png"));
+    assert_eq!(harness.app.attach_file_path, None);
+    assert!(harness.has_text("Uploading 1 file(s)…"));
+}
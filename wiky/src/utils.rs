@@ -1,8 +1,4 @@
-use std::sync::Arc;
-
-use pubky::PubkySession;
 use qrcode::QrCode;
-use tokio::runtime::Runtime;
 
 pub fn generate_qr_image(url: &str) -> Option<egui::ColorImage> {
     let qr = QrCode::new(url.as_bytes()).ok()?;
@@ -32,44 +28,13 @@ pub fn generate_qr_image(url: &str) -> Option<egui::ColorImage> {
     Some(egui::ColorImage::new([scaled_width, scaled_height], pixels))
 }
 
-/// In this context, the title is the readable text on the 1st line
-pub fn extract_title(input: &str) -> &str {
-    // Get the first line by splitting on newlines and taking the first element
-    let first_line = input.lines().next().unwrap_or("");
-    first_line.trim_start_matches("# ")
-}
-
-pub fn extract_details_wiki_url(url: &str) -> Option<(String, String)> {
-    // Split once on '/' and collect the two parts.
-    let mut parts = url.splitn(2, '/');
-
-    let first = parts.next()?.trim();
-    let second = parts.next()?.trim();
-
-    // Ensure both parts are present and not empty.
-    if first.is_empty() || second.is_empty() {
-        log::warn!("Invalid Pubky Wiki link: {url}");
-        return None;
-    }
-
-    Some((first.to_string(), second.to_string()))
-}
-
-/// List files from the homeserver
-pub fn get_list(
-    session: &PubkySession,
-    folder_path: &str,
-    rt: Arc<Runtime>,
-) -> anyhow::Result<Vec<String>> {
-    let session_storage = session.storage();
-    let session_storage_list_fut = session_storage.list(folder_path).unwrap().send();
-
-    log::info!("listing {folder_path}");
-
-    let mut result_list = vec![];
-    for entry in rt.block_on(session_storage_list_fut)? {
-        result_list.push(entry.to_pubky_url());
-    }
-
-    Ok(result_list)
+/// Inputs for the page metadata, shared by the create and edit views
+pub fn show_metadata_fields(ui: &mut egui::Ui, tags: &mut String, language: &mut String) {
+    ui.horizontal(|ui| {
+        ui.label("Tags:");
+        ui.add(egui::TextEdit::singleline(tags).hint_text("comma, separated").desired_width(250.0));
+        ui.add_space(10.0);
+        ui.label("Language:");
+        ui.add(egui::TextEdit::singleline(language).hint_text("en").desired_width(50.0));
+    });
 }
@@ -1,17 +1,19 @@
-use crate::{utils::extract_details_wiki_url, PubkyApp, ViewState};
+use crate::{backend::Command, PubkyApp};
 
 use eframe::egui::{Context, Ui};
 use egui::CollapsingHeader;
 use egui_commonmark::CommonMarkViewer;
-use pubky::{PubkySession, PublicStorage};
-
-pub(crate) fn update(
-    app: &mut PubkyApp,
-    session: &PubkySession,
-    pub_storage: &PublicStorage,
-    ctx: &Context,
-    ui: &mut Ui,
-) {
+use wiki_core::{
+    diff::DiffSide,
+    history::Revision,
+    links::{extract_details_wiki_url, heading_offset, WikiLink},
+    page::format_timestamp,
+};
+
+/// Put before the heading a link points to, to find where it is shown
+const HEADING_MARKER: &str = "<!-- pubky-wiki:heading -->";
+
+pub(crate) fn update(app: &mut PubkyApp, own_pk: &str, ctx: &Context, ui: &mut Ui) {
     ui.label(egui::RichText::new("View Wiki Post").size(20.0).strong());
     ui.add_space(25.0);
 
@@ -19,27 +21,79 @@ pub(crate) fn update(
         ui.add_space(5.0);
         ui.label(egui::RichText::new(format!("Page ID: {}", &app.selected_wiki_page_id)).monospace());
         ui.label(egui::RichText::new(format!("User ID: {}", &app.selected_wiki_user_id)).monospace());
+
+        let meta = &app.selected_wiki_page.meta;
+        ui.label(format!("Title: {}", app.selected_wiki_page.title()));
+        ui.label(format!("Created: {}", format_timestamp(meta.created_at)));
+        ui.label(format!("Updated: {}", format_timestamp(meta.updated_at)));
+        if !meta.tags.is_empty() {
+            ui.label(format!("Tags: {}", meta.tags.join(", ")));
+        }
+        if let Some(language) = &meta.language {
+            ui.label(format!("Language: {language}"));
+        }
+        if let Some(forked_from) = &meta.forked_from {
+            ui.label(egui::RichText::new(format!("Forked content hash: {}", forked_from.content_hash)).monospace());
+        }
     });
 
+    // Check if this is the user's own page
+    let is_own_page = app.selected_wiki_user_id == own_pk;
+
+    show_fork_banner(app, ui, is_own_page);
+
     ui.add_space(10.0);
     let fork_links = app.selected_wiki_fork_urls.clone();
     CollapsingHeader::new(egui::RichText::new(format!("🔀 Available Forks ({})", fork_links.len())).size(15.0)).show(ui, |ui| {
         ui.add_space(5.0);
+        if app.forks_loading {
+            ui.spinner();
+        }
         for fork_link in fork_links {
             if let Some((user_pk, page_id)) = extract_details_wiki_url(&fork_link) {
                 let mut btn_label = format!("Fork: {user_pk}");
 
-                if &app.selected_wiki_user_id == &user_pk {
+                if app.selected_wiki_user_id == user_pk {
                     btn_label = format!("{btn_label} (current)");
                 }
 
-                if ui.button(btn_label).clicked() {
-                    app.navigate_to_view_wiki_page(&user_pk, &page_id, session, pub_storage);
-                }
+                ui.horizontal(|ui| {
+                    let side = DiffSide::Page {
+                        user_pk: user_pk.clone(),
+                        page_id: page_id.clone(),
+                    };
+                    show_compare_checkbox(app, ui, side);
+
+                    if ui.button(btn_label).clicked() {
+                        app.navigate_to_view_wiki_page(&user_pk, &page_id);
+                    }
+                });
             }
         }
     });
 
+    ui.add_space(10.0);
+    show_backlinks(app, ui);
+
+    ui.add_space(10.0);
+    show_history(app, ui);
+
+    if !app.diff_selection.is_empty() {
+        ui.add_space(10.0);
+        ui.horizontal(|ui| {
+            let compare_button = ui.add_enabled(
+                app.diff_selection.len() == 2,
+                egui::Button::new(format!("🔍 Compare selected ({}/2)", app.diff_selection.len())),
+            );
+            if compare_button.clicked() {
+                app.navigate_to_diff();
+            }
+            if ui.small_button("Clear").clicked() {
+                app.diff_selection.clear();
+            }
+        });
+    }
+
     ui.add_space(15.0);
     // Add "Share Page Link" button with tooltip support
     let share_button = ui.add_sized(
@@ -55,7 +109,7 @@ pub(crate) fn update(
     if share_button.clicked() {
         let user_id = &app.selected_wiki_user_id;
         let page_id = &app.selected_wiki_page_id;
-        ctx.copy_text(format!("[link]({user_id}/{page_id})"));
+        ctx.copy_text(format!("[link]({})", WikiLink::page_url(user_id, page_id)));
         app.show_copy_tooltip = true;
     }
 
@@ -66,42 +120,65 @@ pub(crate) fn update(
 
     ui.add_space(15.0);
 
+    if let Some(revision) = app.selected_wiki_revision.clone() {
+        show_revision_banner(app, ui, &revision, is_own_page);
+        ui.add_space(15.0);
+    }
+
     // Display content in a scrollable area
     ui.separator();
     ui.add_space(15.0);
-    egui::ScrollArea::vertical()
-        .max_height(400.0)
+    let mut content_area = egui::ScrollArea::vertical().id_salt("page_content").max_height(400.0);
+    // Wait for the content, as the offset is clamped to the height of what is shown
+    if !app.page_loading {
+        if let Some(offset) = app.restore_page_scroll.take() {
+            content_area = content_area.vertical_scroll_offset(offset);
+        }
+    }
+    let content_output = content_area
         .show(ui, |ui| {
-            // Try to fetch content if empty
-            if app.selected_wiki_content.is_empty()
-                && !app.selected_wiki_page_id.is_empty()
-                && !app.selected_wiki_user_id.is_empty()
-            {
-                let public_storage_clone = pub_storage.clone();
-                let path_clone = app.selected_wiki_page_id.clone();
-                let user_id = app.selected_wiki_user_id.clone();
-
-                let path = format!("pubky{user_id}/pub/wiki.app/{path_clone}");
-
-                // Synchronously fetch the content
-                let get_path_fut = public_storage_clone.get(&path);
-                let fetched_content = match app.rt.block_on(get_path_fut) {
-                    Ok(response) => match app.rt.block_on(response.text()) {
-                        Ok(text) => text,
-                        Err(e) => format!("Error reading content: {e}"),
-                    },
-                    Err(e) => format!("Error fetching path {path}: {e}"),
-                };
-                app.selected_wiki_content = fetched_content;
+            if app.page_loading {
+                ui.spinner();
+                return;
             }
 
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                CommonMarkViewer::new().max_image_width(Some(512)).show(
-                    ui,
-                    &mut app.cache,
-                    &app.selected_wiki_content.as_str(),
-                );
-            });
+            // A selected revision is shown instead of the current version
+            let body = match (&app.selected_wiki_revision, &app.selected_wiki_revision_page) {
+                (None, _) => app.selected_wiki_page.body.as_str(),
+                (Some(_), Some(revision_page)) => revision_page.body.as_str(),
+                (Some(_), None) => {
+                    ui.spinner();
+                    return;
+                }
+            };
+
+            let body = app.expand_title_links(&app.selected_wiki_user_id, body);
+
+            // A link to a heading marks it for the frame it is scrolled to in. The marker is an
+            // HTML comment, handed to the HTML hook of the viewer instead of being shown.
+            let marked = app
+                .scroll_to_heading
+                .take()
+                .and_then(|heading| heading_offset(&body, &heading))
+                .map(|offset| format!("{}{HEADING_MARKER}\n{}", &body[..offset], &body[offset..]));
+            match &marked {
+                Some(marked) => {
+                    let scroll_to_marker = |ui: &mut Ui, html: &str| {
+                        if html.trim() == HEADING_MARKER {
+                            ui.scroll_to_cursor_animation(Some(egui::Align::TOP), egui::style::ScrollAnimation::none());
+                        } else {
+                            ui.label(html.trim_end());
+                        }
+                    };
+                    CommonMarkViewer::new()
+                        .max_image_width(Some(512))
+                        .render_html_fn(Some(&scroll_to_marker))
+                        .show(ui, &mut app.cache, marked);
+                }
+                None => {
+                    CommonMarkViewer::new().max_image_width(Some(512)).show(ui, &mut app.cache, &body);
+                }
+            }
 
             // Intercept link clicks by checking the output commands
             let clicked_urls: Vec<String> = ui.ctx().output_mut(|o| {
@@ -119,20 +196,22 @@ pub(crate) fn update(
                 urls
             });
 
-            // Navigate to clicked URLs
             for url in clicked_urls {
-                if let Some((user_pk, page_id)) = extract_details_wiki_url(&url) {
-                    app.navigate_to_view_wiki_page(&user_pk, &page_id, session, pub_storage);
-                }
+                app.follow_link(&url);
             }
         });
+    if app.resolving_link || app.downloading {
+        ui.spinner();
+    }
+    if let Some(path) = &app.last_download {
+        ui.label(format!("⬇ Saved to {}", path.display()));
+    }
+    if !app.page_loading {
+        app.page_scroll_offset = content_output.state.offset.y;
+    }
 
     ui.add_space(25.0);
 
-    // Check if this is the user's own page
-    let pk = session.info().public_key();
-    let is_own_page = app.selected_wiki_user_id == pk.to_string();
-
     ui.horizontal(|ui| {
         // Show Edit button only for own pages
         if is_own_page {
@@ -153,23 +232,229 @@ pub(crate) fn update(
                 egui::Button::new(egui::RichText::new("🍴 Fork").size(15.0))
             );
             if fork_button.clicked() {
-                app.edit_wiki_content = app.selected_wiki_content.clone();
-                app.forked_from_page_id = Some(app.selected_wiki_page_id.clone());
-                app.view_state = ViewState::CreateWiki;
+                app.navigate_to_fork_selected_wiki_page();
             }
             ui.add_space(10.0);
         }
 
-        // Go back button
+        // Back and forward through the visited pages, like in a browser
         let back_button = ui.add_sized(
             [120.0, 35.0],
             egui::Button::new(egui::RichText::new("← Back").size(15.0))
         );
-        if back_button.clicked() {
-            app.selected_wiki_page_id.clear();
-            app.selected_wiki_content.clear();
-            app.selected_wiki_fork_urls.clear();
-            app.view_state = ViewState::WikiList;
+        if back_button.on_hover_text("Alt+←").clicked() {
+            app.navigate_back();
+        }
+
+        ui.add_space(10.0);
+        let forward_button = ui.add_enabled(
+            app.navigation.can_go_forward(),
+            egui::Button::new(egui::RichText::new("Forward →").size(15.0)).min_size(egui::vec2(120.0, 35.0)),
+        );
+        if forward_button.on_hover_text("Alt+→").clicked() {
+            app.navigate_forward();
+        }
+    });
+
+    // After the content, whose link clicks are intercepted, so opening the confirmed link goes through
+    show_external_link_confirmation(app, ctx);
+}
+
+/// Asks before leaving the wiki for an external link clicked in the page
+fn show_external_link_confirmation(app: &mut PubkyApp, ctx: &Context) {
+    let Some(url) = app.pending_external_link.clone() else {
+        return;
+    };
+
+    egui::Window::new("Open external link?")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            ui.label("This link leaves the wiki and opens in your browser:");
+            ui.label(egui::RichText::new(&url).monospace());
+            ui.add_space(10.0);
+
+            ui.horizontal(|ui| {
+                if ui.button("Open in browser").clicked() {
+                    ctx.open_url(egui::OpenUrl::new_tab(&url));
+                    app.pending_external_link = None;
+                }
+                if ui.button("Cancel").clicked() {
+                    app.pending_external_link = None;
+                }
+            });
+        });
+}
+
+/// Provenance of the selected page, if it is a fork, with a way back to the upstream page
+fn show_fork_banner(app: &mut PubkyApp, ui: &mut Ui, is_own_page: bool) {
+    let Some(forked_from) = app.selected_wiki_page.meta.forked_from.clone() else {
+        return;
+    };
+
+    ui.add_space(10.0);
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.label(egui::RichText::new("🍴 Forked from").strong());
+        ui.label(egui::RichText::new(format!("{}/{}", forked_from.author_pk, forked_from.page_id)).monospace());
+        ui.label(format!(
+            "Forked on {} from the version of {}",
+            format_timestamp(forked_from.forked_at),
+            format_timestamp(forked_from.version)
+        ));
+
+        ui.horizontal(|ui| {
+            let mut upstream_changed = false;
+            match &app.selected_wiki_upstream {
+                Some(upstream) if upstream.content_hash() == forked_from.content_hash => {
+                    ui.label(egui::RichText::new("Upstream is unchanged since the fork").italics());
+                }
+                Some(_) => {
+                    ui.label(egui::RichText::new("Upstream has changed since the fork").italics());
+                    upstream_changed = true;
+                }
+                None => {
+                    ui.spinner();
+                }
+            }
+
+            if ui.button("↩ View upstream").clicked() {
+                app.navigate_to_view_wiki_page(&forked_from.author_pk, &forked_from.page_id);
+            }
+
+            if is_own_page && upstream_changed {
+                let merge_button = ui.add_enabled(!app.merging, egui::Button::new("⤵ Merge upstream changes"));
+                if merge_button.clicked() {
+                    app.merge_upstream_into_selected_wiki_page();
+                }
+                if app.merging {
+                    ui.spinner();
+                }
+            }
+        });
+
+        // Let the author of the forked page know about the changes made here
+        if is_own_page {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut app.proposal_message)
+                        .hint_text("Message for the author (optional)"),
+                );
+
+                let propose_button = ui.add_enabled(!app.proposing, egui::Button::new("📨 Propose changes"));
+                if propose_button.clicked() {
+                    app.propose_selected_wiki_page();
+                }
+                if app.proposing {
+                    ui.spinner();
+                } else if app.proposal_sent {
+                    ui.label("✓ Proposal sent");
+                }
+            });
         }
     });
 }
+
+/// Own pages and pages of the follows linking to the selected page, or to another version of it
+fn show_backlinks(app: &mut PubkyApp, ui: &mut Ui) {
+    let backlinks = app
+        .link_graph
+        .backlinks(&app.selected_wiki_user_id, &app.selected_wiki_page_id);
+    CollapsingHeader::new(egui::RichText::new(format!("🔗 Linked from ({})", backlinks.len())).size(15.0)).show(ui, |ui| {
+        ui.add_space(5.0);
+        if app.search_indexing {
+            ui.spinner();
+        }
+        for backlink in backlinks {
+            ui.horizontal(|ui| {
+                if ui.button(&backlink.title).clicked() {
+                    app.navigate_to_view_wiki_page(&backlink.user_pk, &backlink.page_id);
+                }
+                ui.label(egui::RichText::new(&backlink.user_pk).monospace().small());
+                if let Some(fork_author) = &backlink.via_fork_of {
+                    ui.label(egui::RichText::new(format!("(links to the version of {fork_author})")).italics());
+                }
+            });
+        }
+    });
+}
+
+/// Past revisions of the selected page, newest first
+fn show_history(app: &mut PubkyApp, ui: &mut Ui) {
+    let revisions = app.selected_wiki_history.clone();
+    CollapsingHeader::new(egui::RichText::new(format!("🕘 History ({})", revisions.len())).size(15.0)).show(ui, |ui| {
+        ui.add_space(5.0);
+        if app.history_loading {
+            ui.spinner();
+        }
+        for (i, revision) in revisions.into_iter().enumerate() {
+            let mut btn_label = format_timestamp(revision.timestamp);
+
+            if i == 0 {
+                btn_label = format!("{btn_label} (latest)");
+            }
+            if app.selected_wiki_revision.as_ref() == Some(&revision) {
+                btn_label = format!("{btn_label} (viewing)");
+            }
+
+            ui.horizontal(|ui| {
+                let side = DiffSide::Revision {
+                    user_pk: app.selected_wiki_user_id.clone(),
+                    page_id: app.selected_wiki_page_id.clone(),
+                    revision: revision.clone(),
+                };
+                show_compare_checkbox(app, ui, side);
+
+                if ui.button(btn_label).clicked() {
+                    app.view_revision(revision);
+                }
+            });
+        }
+    });
+}
+
+/// Marks that a past revision is shown, with ways back to the current version
+fn show_revision_banner(app: &mut PubkyApp, ui: &mut Ui, revision: &Revision, is_own_page: bool) {
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.label(egui::RichText::new(format!("🕘 Viewing the revision of {}", format_timestamp(revision.timestamp))).strong());
+
+        ui.horizontal(|ui| {
+            if ui.button("Back to current version").clicked() {
+                app.selected_wiki_revision = None;
+                app.selected_wiki_revision_page = None;
+            }
+
+            // Restoring saves the old content as a new revision, so the history stays intact
+            if is_own_page && !app.saving {
+                if let Some(revision_page) = &app.selected_wiki_revision_page {
+                    if ui.button("↺ Restore this revision").clicked() {
+                        let mut page = app.selected_wiki_page.clone();
+                        page.set_body(revision_page.body.clone());
+                        page.meta.tags = revision_page.meta.tags.clone();
+                        page.meta.language = revision_page.meta.language.clone();
+
+                        let base_version = Some(Revision::of(&app.selected_wiki_page));
+                        app.saving = true;
+                        app.backend.send(Command::UpdatePage {
+                            page_id: app.selected_wiki_page_id.clone(),
+                            page,
+                            base_version,
+                        });
+                    }
+                }
+            }
+
+            if app.saving {
+                ui.spinner();
+            }
+        });
+    });
+}
+
+/// Ticks a version for the side-by-side comparison
+fn show_compare_checkbox(app: &mut PubkyApp, ui: &mut Ui, side: DiffSide) {
+    let mut ticked = app.diff_selection.contains(&side);
+    if ui.checkbox(&mut ticked, "").on_hover_text("Select to compare").changed() {
+        app.toggle_diff_selection(side);
+    }
+}
@@ -0,0 +1,106 @@
+//! Images and other files attached to pages, stored next to the pages on the homeserver of their
+//! uploader and referenced from the markdown by their `pubky://` URL.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use uuid::Uuid;
+
+/// Folder of the attachments on the homeservers
+pub const FILES_DIR: &str = "/pub/wiki.app/files/";
+
+/// Largest file accepted as an attachment
+pub const MAX_ATTACHMENT_SIZE: usize = 10 * 1024 * 1024;
+
+/// Extensions of the files shown inline, as images, rather than linked
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp"];
+
+/// Path of a new attachment. A fresh ID keeps uploads of files with the same name apart, the name
+/// is kept so the URL tells what the file is.
+pub fn attachment_path(file_name: &str) -> String {
+    format!("{FILES_DIR}{}-{}", Uuid::new_v4(), sanitize(file_name))
+}
+
+/// Name of the attached file, without the ID its path starts with
+pub fn attachment_name(path: &str) -> &str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+
+    // IDs are hyphenated UUIDs, 36 characters long
+    match name.get(36..).and_then(|rest| rest.strip_prefix('-')) {
+        Some(rest) if !rest.is_empty() => rest,
+        _ => name,
+    }
+}
+
+/// Only letters, digits, dots, dashes and underscores, so the name needs no escaping in URLs
+fn sanitize(file_name: &str) -> String {
+    let name: String = file_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect();
+    let name = name.trim_matches('.');
+
+    if name.is_empty() {
+        "file".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Whether a file is shown inline as an image, judging by its extension
+pub fn is_image(file_name: &str) -> bool {
+    Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+}
+
+/// Markdown inserted in a page for an attachment: an image for images, a link otherwise
+pub fn markdown_reference(url: &str, file_name: &str) -> String {
+    let label = file_name.replace('\\', "\\\\").replace('[', "\\[").replace(']', "\\]");
+
+    if is_image(file_name) {
+        format!("![{label}]({url})")
+    } else {
+        format!("[{label}]({url})")
+    }
+}
+
+/// Content of a file to attach, refusing files too large to be attachments before reading them
+pub fn read_attachment(path: &Path) -> Result<Vec<u8>> {
+    if fs::metadata(path)?.len() > MAX_ATTACHMENT_SIZE as u64 {
+        return Err(too_large());
+    }
+
+    Ok(fs::read(path)?)
+}
+
+pub(crate) fn too_large() -> anyhow::Error {
+    anyhow!("Attachments can't be larger than {} MB", MAX_ATTACHMENT_SIZE / (1024 * 1024))
+}
+
+/// Write a downloaded attachment in the downloads folder, without overwriting an existing file,
+/// and return where it was written
+pub fn save_download(file_name: &str, content: &[u8]) -> Result<PathBuf> {
+    let folder = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .ok_or_else(|| anyhow!("No downloads folder"))?;
+    let file_name = sanitize(file_name);
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{extension}")),
+        _ => (file_name.as_str(), String::new()),
+    };
+
+    let mut path = folder.join(&file_name);
+    let mut copy = 1;
+    while path.exists() {
+        path = folder.join(format!("{stem} ({copy}){extension}"));
+        copy += 1;
+    }
+    fs::write(&path, content)?;
+
+    Ok(path)
+}
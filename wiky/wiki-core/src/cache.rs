@@ -0,0 +1,437 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use anyhow::{anyhow, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+    history::Revision,
+    page::{now, WikiPage},
+    profile,
+};
+
+const CACHE_FILE_NAME: &str = "cache.sqlite3";
+
+/// Room taken at most by the cached files, beyond which the least recently fetched are dropped
+const FILES_CACHE_SIZE: u64 = 200 * 1024 * 1024;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS pages (
+        user_pk TEXT NOT NULL,
+        page_id TEXT NOT NULL,
+        content TEXT NOT NULL,
+        etag TEXT,
+        fetched_at INTEGER NOT NULL,
+        PRIMARY KEY (user_pk, page_id)
+    );
+    CREATE TABLE IF NOT EXISTS pending_writes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_pk TEXT NOT NULL,
+        op TEXT NOT NULL,
+        page_id TEXT NOT NULL,
+        content TEXT,
+        base_version INTEGER,
+        base_content_hash TEXT,
+        queued_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS drafts (
+        user_pk TEXT NOT NULL,
+        draft_id TEXT NOT NULL,
+        page_id TEXT,
+        base_version INTEGER,
+        base_content_hash TEXT,
+        content TEXT NOT NULL,
+        saved_at INTEGER NOT NULL,
+        PRIMARY KEY (user_pk, draft_id)
+    );
+    CREATE TABLE IF NOT EXISTS files (
+        url TEXT PRIMARY KEY,
+        content BLOB NOT NULL,
+        mime TEXT,
+        etag TEXT,
+        fetched_at INTEGER NOT NULL
+    );
+";
+
+/// Columns added to the tables since they were first created, as `(table, column, type)`
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("pending_writes", "base_version", "INTEGER"),
+    ("pending_writes", "base_content_hash", "TEXT"),
+    ("drafts", "base_content_hash", "TEXT"),
+];
+
+/// A page as last fetched from a homeserver
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedPage {
+    pub page: WikiPage,
+    /// ETag of the stored version, `None` for versions written by this app and not refetched since
+    pub etag: Option<String>,
+}
+
+/// A file other than a page, like an image shown in a page, as last fetched from a homeserver
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedFile {
+    pub content: Vec<u8>,
+    /// MIME type given by the homeserver
+    pub mime: Option<String>,
+    pub etag: Option<String>,
+}
+
+/// A write made while offline, replayed against the homeserver once it is reachable again
+#[derive(Clone, Debug, PartialEq)]
+pub enum PendingWrite {
+    Create { page_id: String, page: WikiPage },
+    /// `base_version` is the version the changes were made to, checked once synced
+    Update {
+        page_id: String,
+        page: WikiPage,
+        base_version: Option<Revision>,
+    },
+    Delete { page_id: String },
+}
+
+impl PendingWrite {
+    pub fn page_id(&self) -> &str {
+        match self {
+            PendingWrite::Create { page_id, .. }
+            | PendingWrite::Update { page_id, .. }
+            | PendingWrite::Delete { page_id } => page_id,
+        }
+    }
+}
+
+/// Unsaved changes of an editor, kept locally so they survive a crash, a cancel or a failed save
+#[derive(Clone, Debug, PartialEq)]
+pub struct Draft {
+    pub id: String,
+    /// Own page being edited, `None` for a new page
+    pub page_id: Option<String>,
+    /// Version of the page the changes were made to
+    pub base_version: Option<Revision>,
+    /// The page as it would be saved, with the tags, language and fork typed in the editor
+    pub page: WikiPage,
+    /// Unix timestamp, in seconds
+    pub saved_at: u64,
+}
+
+/// On-disk cache of the fetched pages and files, and queue of the writes made while offline.
+///
+/// Shared by the tasks of the background worker, each call holds the connection briefly.
+#[derive(Clone)]
+pub struct PageCache {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl PageCache {
+    /// Open the cache in the profile directory, creating it if needed
+    pub fn open() -> Result<Self> {
+        let path = profile::profile_dir()?.join(CACHE_FILE_NAME);
+        Self::init(Connection::open(path)?)
+    }
+
+    /// A cache that is dropped on exit, for when the profile directory is not usable
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        for (table, column, kind) in ADDED_COLUMNS {
+            let exists: bool = conn.query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+                params![table, column],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {kind}"))?;
+            }
+        }
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    fn conn(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| anyhow!("Page cache is poisoned"))
+    }
+
+    pub fn get(&self, user_pk: &str, page_id: &str) -> Result<Option<CachedPage>> {
+        let cached = self
+            .conn()?
+            .query_row(
+                "SELECT content, etag FROM pages WHERE user_pk = ?1 AND page_id = ?2",
+                params![user_pk, page_id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+            )
+            .optional()?;
+
+        Ok(cached.map(|(content, etag)| CachedPage {
+            page: WikiPage::parse(&content),
+            etag,
+        }))
+    }
+
+    pub fn put(
+        &self,
+        user_pk: &str,
+        page_id: &str,
+        page: &WikiPage,
+        etag: Option<&str>,
+    ) -> Result<()> {
+        self.conn()?.execute(
+            "INSERT OR REPLACE INTO pages (user_pk, page_id, content, etag, fetched_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![user_pk, page_id, page.to_markdown()?, etag, now()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn remove(&self, user_pk: &str, page_id: &str) -> Result<()> {
+        self.conn()?.execute(
+            "DELETE FROM pages WHERE user_pk = ?1 AND page_id = ?2",
+            params![user_pk, page_id],
+        )?;
+
+        Ok(())
+    }
+
+    /// IDs of the cached pages of a user
+    pub fn page_ids(&self, user_pk: &str) -> Result<Vec<String>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT page_id FROM pages WHERE user_pk = ?1 ORDER BY page_id")?;
+        let page_ids = stmt
+            .query_map(params![user_pk], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(page_ids)
+    }
+
+    /// Titles of the cached pages of a user, by file URL
+    pub fn page_titles(&self, user_pk: &str) -> HashMap<String, String> {
+        let page_ids = self
+            .page_ids(user_pk)
+            .inspect_err(|e| log::error!("Failed to list cached pages: {e}"))
+            .unwrap_or_default();
+
+        page_ids
+            .into_iter()
+            .filter_map(|page_id| {
+                let cached = self.get(user_pk, &page_id).ok()??;
+                let file_url = format!("pubky://{user_pk}/pub/wiki.app/{page_id}");
+                Some((file_url, cached.page.title().to_string()))
+            })
+            .collect()
+    }
+
+    /// Users with a cached version of a page
+    pub fn page_authors(&self, page_id: &str) -> Result<Vec<String>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT user_pk FROM pages WHERE page_id = ?1 ORDER BY user_pk")?;
+        let authors = stmt
+            .query_map(params![page_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(authors)
+    }
+
+    /// All cached pages, with their author and page ID
+    pub fn all_pages(&self) -> Result<Vec<(String, String, WikiPage)>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT user_pk, page_id, content FROM pages")?;
+        let pages = stmt
+            .query_map([], |row| {
+                let content: String = row.get(2)?;
+                Ok((row.get(0)?, row.get(1)?, WikiPage::parse(&content)))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(pages)
+    }
+
+    /// Queue a write of `user_pk`, and apply it to the cached pages right away
+    pub fn queue(&self, user_pk: &str, write: &PendingWrite) -> Result<()> {
+        let (op, page_id, content, base_version) = match write {
+            PendingWrite::Create { page_id, page } => ("create", page_id, Some(page.to_markdown()?), None),
+            PendingWrite::Update {
+                page_id,
+                page,
+                base_version,
+            } => ("update", page_id, Some(page.to_markdown()?), base_version.as_ref()),
+            PendingWrite::Delete { page_id } => ("delete", page_id, None, None),
+        };
+        self.conn()?.execute(
+            "INSERT INTO pending_writes (user_pk, op, page_id, content, base_version, base_content_hash, queued_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                user_pk,
+                op,
+                page_id,
+                content,
+                base_version.map(|base| base.timestamp),
+                base_version.map(|base| &base.content_hash),
+                now()
+            ],
+        )?;
+
+        match write {
+            PendingWrite::Create { page_id, page } | PendingWrite::Update { page_id, page, .. } => {
+                self.put(user_pk, page_id, page, None)
+            }
+            PendingWrite::Delete { page_id } => self.remove(user_pk, page_id),
+        }
+    }
+
+    /// Writes of `user_pk` waiting to be synced, oldest first, with their queue IDs
+    pub fn pending(&self, user_pk: &str) -> Result<Vec<(i64, PendingWrite)>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, op, page_id, content, base_version, base_content_hash FROM pending_writes
+             WHERE user_pk = ?1 ORDER BY id",
+        )?;
+        let rows: Vec<(i64, String, Option<PendingWrite>)> = stmt
+            .query_map(params![user_pk], |row| {
+                let op: String = row.get(1)?;
+                let page_id = row.get(2)?;
+                let content: Option<String> = row.get(3)?;
+                let page = WikiPage::parse(content.as_deref().unwrap_or_default());
+                let write = match op.as_str() {
+                    "create" => Some(PendingWrite::Create { page_id, page }),
+                    "update" => Some(PendingWrite::Update {
+                        page_id,
+                        page,
+                        base_version: base_revision(row, 4)?,
+                    }),
+                    "delete" => Some(PendingWrite::Delete { page_id }),
+                    _ => None,
+                };
+                Ok((row.get(0)?, op, write))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        rows.into_iter()
+            .map(|(id, op, write)| Ok((id, write.ok_or_else(|| anyhow!("Unknown queued operation: {op}"))?)))
+            .collect()
+    }
+
+    /// Remove a write from the queue, once it was synced
+    pub fn dequeue(&self, id: i64) -> Result<()> {
+        self.conn()?
+            .execute("DELETE FROM pending_writes WHERE id = ?1", params![id])?;
+
+        Ok(())
+    }
+
+    /// Store a draft of `user_pk`, replacing the previous version of it
+    pub fn save_draft(&self, user_pk: &str, draft: &Draft) -> Result<()> {
+        self.conn()?.execute(
+            "INSERT OR REPLACE INTO drafts
+             (user_pk, draft_id, page_id, base_version, base_content_hash, content, saved_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                user_pk,
+                draft.id,
+                draft.page_id,
+                draft.base_version.as_ref().map(|base| base.timestamp),
+                draft.base_version.as_ref().map(|base| &base.content_hash),
+                draft.page.to_markdown()?,
+                draft.saved_at
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Drafts of `user_pk`, most recently saved first
+    pub fn drafts(&self, user_pk: &str) -> Result<Vec<Draft>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT draft_id, page_id, base_version, base_content_hash, content, saved_at FROM drafts
+             WHERE user_pk = ?1 ORDER BY saved_at DESC, draft_id",
+        )?;
+        let drafts = stmt
+            .query_map(params![user_pk], |row| {
+                let content: String = row.get(4)?;
+                Ok(Draft {
+                    id: row.get(0)?,
+                    page_id: row.get(1)?,
+                    base_version: base_revision(row, 2)?,
+                    page: WikiPage::parse(&content),
+                    saved_at: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(drafts)
+    }
+
+    pub fn discard_draft(&self, user_pk: &str, draft_id: &str) -> Result<()> {
+        self.conn()?.execute(
+            "DELETE FROM drafts WHERE user_pk = ?1 AND draft_id = ?2",
+            params![user_pk, draft_id],
+        )?;
+
+        Ok(())
+    }
+
+    /// A file fetched from `url`, a `pubky://` URL
+    pub fn get_file(&self, url: &str) -> Result<Option<CachedFile>> {
+        let cached = self
+            .conn()?
+            .query_row(
+                "SELECT content, mime, etag FROM files WHERE url = ?1",
+                params![url],
+                |row| {
+                    Ok(CachedFile {
+                        content: row.get(0)?,
+                        mime: row.get(1)?,
+                        etag: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(cached)
+    }
+
+    /// Keep a file fetched from `url`, making room for it if needed
+    pub fn put_file(&self, url: &str, file: &CachedFile) -> Result<()> {
+        self.conn()?.execute(
+            "INSERT OR REPLACE INTO files (url, content, mime, etag, fetched_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![url, file.content, file.mime, file.etag, now()],
+        )?;
+
+        self.prune_files(FILES_CACHE_SIZE)
+    }
+
+    /// Drop the least recently fetched files, until the cached files take at most `max_size` bytes
+    pub fn prune_files(&self, max_size: u64) -> Result<()> {
+        self.conn()?.execute(
+            "DELETE FROM files WHERE url IN (
+                SELECT url FROM (
+                    SELECT url, SUM(LENGTH(content)) OVER (ORDER BY fetched_at DESC, rowid DESC) AS total
+                    FROM files
+                )
+                WHERE total > ?1
+            )",
+            params![max_size],
+        )?;
+
+        Ok(())
+    }
+}
+
+/// The base version stored in the `base_version` and `base_content_hash` columns starting at `index`
+fn base_revision(row: &rusqlite::Row, index: usize) -> rusqlite::Result<Option<Revision>> {
+    let timestamp: Option<u64> = row.get(index)?;
+    let content_hash: Option<String> = row.get(index + 1)?;
+
+    Ok(timestamp.map(|timestamp| Revision {
+        timestamp,
+        content_hash: content_hash.unwrap_or_default(),
+    }))
+}
@@ -0,0 +1,613 @@
+use std::{cmp::Reverse, collections::HashMap, fmt};
+
+use anyhow::{anyhow, Result};
+use pubky::{PubkySession, PublicStorage};
+
+use crate::{
+    attachment::{attachment_path, too_large, MAX_ATTACHMENT_SIZE},
+    cache::{Draft, PageCache, PendingWrite},
+    follow::{parse_profile_name, Follow, PROFILE_PATH},
+    graph::LinkGraph,
+    history::{self, Revision},
+    links::{extract_details_wiki_url, is_valid_page_id, same_title, WikiLink},
+    merge::{MergeSource, PreparedMerge},
+    page::{now, ForkedFrom, WikiPage},
+    profile,
+    proposal::{self, Proposal, ReceivedProposal},
+    search::SearchIndex,
+    storage::{
+        create_wiki_post, delete_wiki_post, new_page_id, update_wiki_post, PubkyStorage,
+        StorageError, WikiStorage,
+    },
+};
+
+/// Folder of the follows, as written by pubky.app
+const FOLLOWS_DIR: &str = "/pub/pubky.app/follows/";
+
+/// An update was refused, as the page was saved elsewhere since the version it was made to
+#[derive(Debug)]
+pub struct UpdateConflict {
+    /// The version the update was made to
+    pub base_version: Revision,
+    /// The version stored now
+    pub current: WikiPage,
+}
+
+impl fmt::Display for UpdateConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The page was changed elsewhere since it was opened")
+    }
+}
+
+impl std::error::Error for UpdateConflict {}
+
+/// Outcome of replaying the writes made offline
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SyncReport {
+    pub synced: usize,
+    /// Offline updates of pages saved elsewhere since, which are merged instead of overwriting them
+    pub conflicts: Vec<SyncConflict>,
+    /// Why the writes refused by the homeserver failed, by page
+    pub failed: Vec<String>,
+}
+
+/// An offline update refused on sync, with its changes kept in a draft until the merge is saved
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncConflict {
+    pub page_id: String,
+    pub draft_id: String,
+    pub merge: PreparedMerge,
+}
+
+/// Wiki operations of a signed in user, against their homeserver and the homeservers of others.
+///
+/// Fetched pages are kept in the [`PageCache`], and reused as long as they didn't change.
+#[derive(Clone)]
+pub struct WikiClient<S = PubkyStorage> {
+    storage: S,
+    cache: PageCache,
+}
+
+impl WikiClient {
+    pub fn new(session: PubkySession, pub_storage: PublicStorage, cache: PageCache) -> Self {
+        Self::with_storage(PubkyStorage::new(session, pub_storage), cache)
+    }
+
+    pub fn session(&self) -> &PubkySession {
+        self.storage.session()
+    }
+
+    /// Sign out, and forget the stored session
+    pub async fn sign_out(self) {
+        if let Err(e) = profile::clear_session() {
+            log::error!("Failed to remove stored session: {e}");
+        }
+
+        // The stored session is gone either way, so a failed sign out only leaves a stale server session
+        if let Err((e, _)) = self.storage.into_session().signout().await {
+            log::error!("Failed to sign out: {e}");
+        }
+    }
+}
+
+impl<S: WikiStorage> WikiClient<S> {
+    pub fn with_storage(storage: S, cache: PageCache) -> Self {
+        Self { storage, cache }
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn cache(&self) -> &PageCache {
+        &self.cache
+    }
+
+    /// Public key of the signed in user
+    pub fn own_pk(&self) -> String {
+        self.storage.public_key()
+    }
+
+    /// IDs of the pages of a user, sorted
+    pub async fn list_pages(&self, user_pk: &str) -> Result<Vec<String>> {
+        let urls = self.storage.list(user_pk, "/pub/wiki.app/").await?;
+
+        let mut page_ids: Vec<String> = urls
+            .iter()
+            .map(|url| url.split('/').next_back().unwrap_or(url).to_string())
+            .collect();
+        page_ids.sort();
+
+        Ok(page_ids)
+    }
+
+    /// Titles of the own pages, by file URL.
+    ///
+    /// Falls back to the cached pages if the homeserver can't be reached.
+    pub async fn own_page_titles(&self) -> HashMap<String, String> {
+        let own_pk = self.own_pk();
+
+        let page_ids = match self.list_pages(&own_pk).await {
+            Ok(page_ids) => page_ids,
+            Err(e) => {
+                log::error!("Failed to list files: {e}");
+                return self.cache.page_titles(&own_pk);
+            }
+        };
+
+        let mut titles = HashMap::new();
+        for page_id in &page_ids {
+            match self.fetch_page(&own_pk, page_id).await {
+                Ok(page) => {
+                    let file_url = format!("pubky://{own_pk}/pub/wiki.app/{page_id}");
+                    titles.insert(file_url, page.title().to_string());
+                }
+                Err(e) => log::error!("{e}"),
+            }
+        }
+
+        // Forget the pages deleted from another device
+        for cached_id in self.cache.page_ids(&own_pk).unwrap_or_default() {
+            if !page_ids.contains(&cached_id) {
+                if let Err(e) = self.cache.remove(&own_pk, &cached_id) {
+                    log::error!("Failed to remove cached page {cached_id}: {e}");
+                }
+            }
+        }
+
+        titles
+    }
+
+    /// Fetch a page, using the cached version if it is still current.
+    ///
+    /// Falls back to the cached version, even if stale, if the homeserver can't be reached.
+    pub async fn fetch_page(&self, user_pk: &str, page_id: &str) -> Result<WikiPage> {
+        let path = format!("/pub/wiki.app/{page_id}");
+        let cached = self
+            .cache
+            .get(user_pk, page_id)
+            .inspect_err(|e| log::error!("Failed to read cached page {path}: {e}"))
+            .ok()
+            .flatten();
+
+        // A HEAD request is enough to know if the cached version is still current
+        if let Some(cached) = cached.as_ref().filter(|cached| cached.etag.is_some()) {
+            if let Ok(Some(etag)) = self.storage.etag(user_pk, &path).await {
+                if cached.etag.as_ref() == Some(&etag) {
+                    return Ok(cached.page.clone());
+                }
+            }
+        }
+
+        let file = match self.storage.get(user_pk, &path).await {
+            Ok(file) => file,
+            Err(e) if StorageError::is_unreachable(&e) => {
+                let Some(cached) = cached else {
+                    return Err(anyhow!("Error fetching path {path}: {e}"));
+                };
+                log::warn!("Error fetching path {path}: {e}, using the cached version");
+                return Ok(cached.page);
+            }
+            // Kept as the source of the error, so callers can tell a missing page apart
+            Err(e) if StorageError::is_not_found(&e) => {
+                if let Err(e) = self.cache.remove(user_pk, page_id) {
+                    log::error!("Failed to remove cached page {path}: {e}");
+                }
+                return Err(e.context(format!("Error fetching path {path}")));
+            }
+            Err(e) => return Err(anyhow!("Error fetching path {path}: {e}")),
+        };
+
+        let page = WikiPage::parse(&file.content);
+        if let Err(e) = self.cache.put(user_pk, page_id, &page, file.etag.as_deref()) {
+            log::error!("Failed to cache page {path}: {e}");
+        }
+
+        Ok(page)
+    }
+
+    /// Fetch the page a link points to
+    pub async fn fetch_linked_page(&self, link: &str) -> Result<(String, String, WikiPage)> {
+        let (user_pk, page_id) =
+            extract_details_wiki_url(link).ok_or_else(|| anyhow!("Invalid wiki link: {link}"))?;
+        let page = self.fetch_page(&user_pk, &page_id).await?;
+
+        Ok((user_pk, page_id, page))
+    }
+
+    /// ID of the page of `user_pk` with this title, for the `wiki:{title}` links
+    pub async fn find_page_by_title(&self, user_pk: &str, title: &str) -> Result<Option<String>> {
+        for page_id in self.list_pages(user_pk).await? {
+            match self.fetch_page(user_pk, &page_id).await {
+                Ok(page) if same_title(page.title(), title) => return Ok(Some(page_id)),
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to fetch {user_pk}/{page_id}: {e}"),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Create a page, with its first revision, and return its path
+    pub async fn create_page(&self, page: &WikiPage, filename: Option<&str>) -> Result<String> {
+        if let Some(filename) = filename {
+            check_page_id(filename)?;
+        }
+        let path = create_wiki_post(&self.storage, page, filename).await?;
+
+        let page_id = path.split('/').next_back().unwrap_or(&path);
+        history::save_revision(&self.storage, page_id, page).await?;
+        self.cache_own_page(page_id, Some(page));
+
+        Ok(path)
+    }
+
+    /// Overwrite a page, keeping the new version in its history.
+    ///
+    /// With a `base_version`, the version the changes were made to, the update fails with an
+    /// [`UpdateConflict`] if the page was saved since, rather than overwriting it.
+    pub async fn update_page(&self, page_id: &str, page: &WikiPage, base_version: Option<Revision>) -> Result<()> {
+        check_page_id(page_id)?;
+        if let Some(base_version) = base_version {
+            self.check_version(page_id, base_version).await?;
+        }
+
+        history::archive_current_version(&self.storage, page_id).await?;
+        update_wiki_post(&self.storage, page_id, page).await?;
+        history::save_revision(&self.storage, page_id, page).await?;
+        self.cache_own_page(page_id, Some(page));
+
+        Ok(())
+    }
+
+    /// Fail with an [`UpdateConflict`] if the stored version of an own page is not `base_version`
+    async fn check_version(&self, page_id: &str, base_version: Revision) -> Result<()> {
+        // Read from the homeserver, as the cache may hold what this app wrote, not what is stored
+        let path = format!("/pub/wiki.app/{page_id}");
+        let file = self.storage.get(&self.own_pk(), &path).await?;
+        let current = WikiPage::parse(&file.content);
+
+        // Timestamps have a one second resolution, so the content tells versions apart when known
+        let changed = if base_version.content_hash.is_empty() {
+            current.meta.updated_at != base_version.timestamp
+        } else {
+            current.content_hash() != base_version.content_hash
+        };
+        if changed {
+            return Err(UpdateConflict { base_version, current }.into());
+        }
+
+        Ok(())
+    }
+
+    /// Three-way merge of a refused update with the version saved since, using the version the
+    /// update was made to as base
+    pub async fn prepare_conflict_merge(&self, page_id: &str, mine: &WikiPage, conflict: UpdateConflict) -> PreparedMerge {
+        // Without the base, every difference is shown as a conflict
+        let base = self
+            .fetch_revision(&self.own_pk(), page_id, &conflict.base_version)
+            .await
+            .inspect_err(|e| log::warn!("Base version of the update is not available: {e}"))
+            .unwrap_or_default();
+
+        let theirs = conflict.current.body.clone();
+        let source = MergeSource::Concurrent {
+            base: Box::new(base.clone()),
+            current: Box::new(conflict.current),
+        };
+        PreparedMerge::new(&base.body, mine.clone(), &theirs, source)
+    }
+
+    /// Delete a page, along with its history
+    pub async fn delete_page(&self, page_id: &str) -> Result<()> {
+        check_page_id(page_id)?;
+        delete_wiki_post(&self.storage, page_id).await?;
+        history::delete_revisions(&self.storage, page_id).await?;
+        self.cache_own_page(page_id, None);
+
+        Ok(())
+    }
+
+    /// Fork a page of another user, under the same page ID, and return the fork as created
+    pub async fn fork_page(&self, author_pk: &str, page_id: &str) -> Result<WikiPage> {
+        let upstream = self.fetch_page(author_pk, page_id).await?;
+
+        let mut fork = WikiPage::new(upstream.body.clone());
+        fork.meta.tags = upstream.meta.tags.clone();
+        fork.meta.language = upstream.meta.language.clone();
+        fork.meta.forked_from = Some(ForkedFrom::new(author_pk, page_id, &upstream));
+        self.create_page(&fork, Some(page_id)).await?;
+
+        Ok(fork)
+    }
+
+    /// Upload a file to attach to the pages, and return its `pubky://` URL
+    pub async fn upload_attachment(&self, file_name: &str, content: Vec<u8>) -> Result<String> {
+        if content.len() > MAX_ATTACHMENT_SIZE {
+            return Err(too_large());
+        }
+
+        let path = attachment_path(file_name);
+        self.storage.put_bytes(&path, content).await?;
+        log::info!("Uploaded attachment at path: {path}");
+
+        Ok(WikiLink::attachment_url(&self.own_pk(), &path))
+    }
+
+    /// Content of the attachment a link points to, from the homeserver of its uploader
+    pub async fn fetch_attachment(&self, url: &str) -> Result<Vec<u8>> {
+        match WikiLink::parse(url) {
+            Some(WikiLink::Attachment { user_pk, path }) => self.storage.get_bytes(&user_pk, &path).await,
+            _ => Err(anyhow!("Not an attachment: {url}")),
+        }
+    }
+
+    /// Keep the cache in line with a write to an own page, or its deletion if `page` is `None`
+    fn cache_own_page(&self, page_id: &str, page: Option<&WikiPage>) {
+        let own_pk = self.own_pk();
+        let result = match page {
+            // The ETag of the written version is unknown, so it is refetched on next use
+            Some(page) => self.cache.put(&own_pk, page_id, page, None),
+            None => self.cache.remove(&own_pk, page_id),
+        };
+        if let Err(e) = result {
+            log::error!("Failed to update the cached page {page_id}: {e}");
+        }
+    }
+
+    /// Revisions of a page, newest first
+    pub async fn list_revisions(&self, user_pk: &str, page_id: &str) -> Result<Vec<Revision>> {
+        history::list_revisions(&self.storage, user_pk, page_id).await
+    }
+
+    pub async fn fetch_revision(&self, user_pk: &str, page_id: &str, revision: &Revision) -> Result<WikiPage> {
+        history::fetch_revision(&self.storage, user_pk, page_id, revision).await
+    }
+
+    /// Public keys of the followed users
+    pub async fn follows(&self) -> Vec<String> {
+        self.storage
+            .list(&self.own_pk(), FOLLOWS_DIR)
+            .await
+            .inspect_err(|e| log::error!("Failed to get follows: {e}"))
+            .map(|list| {
+                list.iter()
+                    .map(|path| path.split('/').next_back().unwrap_or(path).to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Followed users, with the names of their profiles
+    pub async fn followed_users(&self) -> Vec<Follow> {
+        let mut users = vec![];
+        for user_pk in self.follows().await {
+            let name = match self.storage.get(&user_pk, PROFILE_PATH).await {
+                Ok(file) => parse_profile_name(&file.content),
+                Err(e) => {
+                    if !StorageError::is_not_found(&e) {
+                        log::warn!("Failed to fetch the profile of {user_pk}: {e}");
+                    }
+                    None
+                }
+            };
+            users.push(Follow { user_pk, name });
+        }
+
+        users
+    }
+
+    /// Versions of a page, as page URLs: the own one first, then the ones of the
+    /// followed users who have it
+    pub async fn discover_forks(&self, page_id: &str) -> Vec<String> {
+        let follows = self.follows().await;
+
+        let mut result = vec![];
+
+        // Add the current user's version as a fork (root version)
+        result.push(WikiLink::page_url(&self.own_pk(), page_id));
+
+        // Only checked for, as the versions are fetched once opened
+        for follow_pk in follows {
+            let fork_path = format!("/pub/wiki.app/{page_id}");
+            match self.storage.exists(&follow_pk, &fork_path).await {
+                Ok(true) => result.push(WikiLink::page_url(&follow_pk, page_id)),
+                // Most follows don't have the page
+                Ok(false) => log::debug!("No fork at pubky://{follow_pk}{fork_path}"),
+                Err(e) => log::warn!("Failed to check for a fork at pubky://{follow_pk}{fork_path}: {e}"),
+            }
+        }
+        result
+    }
+
+    /// Three-way merge of a fork with its upstream page, using the forked version as base
+    pub async fn prepare_merge(&self, page_id: &str, mine: &WikiPage) -> Result<PreparedMerge> {
+        let forked_from = mine
+            .meta
+            .forked_from
+            .as_ref()
+            .ok_or_else(|| anyhow!("This page is not a fork"))?;
+
+        let upstream = self.fetch_page(&forked_from.author_pk, &forked_from.page_id).await?;
+        let base = history::find_fork_base(&self.storage, page_id, forked_from).await?;
+
+        let theirs = upstream.body.clone();
+        Ok(PreparedMerge::new(&base.body, mine.clone(), &theirs, MergeSource::Upstream(upstream)))
+    }
+
+    /// Publish a proposal for the changes of an own fork
+    pub async fn propose_changes(&self, proposal: &Proposal) -> Result<String> {
+        proposal::save_proposal(&self.storage, proposal).await
+    }
+
+    /// Proposals of the followed users to own pages, newest first, except the ones already
+    /// accepted or dismissed
+    pub async fn inbox(&self) -> Vec<ReceivedProposal> {
+        let own_pk = self.own_pk();
+        let handled = proposal::handled_proposals()
+            .inspect_err(|e| log::error!("Failed to read handled proposals: {e}"))
+            .unwrap_or_default();
+
+        let mut inbox = vec![];
+        for follow_pk in self.follows().await {
+            match proposal::list_proposals(&self.storage, &follow_pk, &own_pk).await {
+                Ok(proposals) => {
+                    inbox.extend(proposals.into_iter().filter(|p| !handled.contains(&p.url)))
+                }
+                Err(e) => log::warn!("Failed to list proposals of {follow_pk}: {e}"),
+            }
+        }
+        inbox.sort_by_key(|p| Reverse(p.proposal.created_at));
+
+        inbox
+    }
+
+    /// Three-way merge of a proposed fork version into the own page it targets, using the version
+    /// the fork is based on as base
+    pub async fn prepare_proposal_merge(&self, received: ReceivedProposal) -> Result<PreparedMerge> {
+        let own_pk = self.own_pk();
+        let proposal = &received.proposal;
+
+        let mine = self.fetch_page(&own_pk, &proposal.target_page_id).await?;
+        let theirs = self
+            .fetch_revision(&received.author_pk, &proposal.fork_page_id, &Revision::at(proposal.fork_version))
+            .await?;
+
+        // Without the base, every difference is shown as a conflict
+        let base = self
+            .fetch_revision(&own_pk, &proposal.target_page_id, &Revision::at(proposal.base_version))
+            .await
+            .inspect_err(|e| log::warn!("Base version of the proposal is not available: {e}"))
+            .unwrap_or_default();
+
+        Ok(PreparedMerge::new(&base.body, mine, &theirs.body, MergeSource::Proposal(received)))
+    }
+
+    /// Index the pages of the current user and of the followed users, for search and for the links
+    /// between them
+    pub async fn build_indexes(&self) -> (SearchIndex, LinkGraph) {
+        let follows = self.followed_users().await;
+        let mut users = vec![self.own_pk()];
+        users.extend(follows.iter().map(|follow| follow.user_pk.clone()));
+
+        let mut index = SearchIndex::default();
+        let mut graph = LinkGraph::default();
+        graph.set_follows(follows);
+        for user_pk in users {
+            let page_ids = match self.list_pages(&user_pk).await {
+                Ok(page_ids) => page_ids,
+                Err(e) => {
+                    log::warn!("Failed to list pages of {user_pk}: {e}");
+                    continue;
+                }
+            };
+
+            for page_id in page_ids {
+                match self.fetch_page(&user_pk, &page_id).await {
+                    Ok(page) => {
+                        index.insert(&user_pk, &page_id, &page);
+                        graph.insert(&user_pk, &page_id, &page);
+                    }
+                    Err(e) => log::error!("{e}"),
+                }
+            }
+        }
+
+        log::info!("Indexed {} pages for search", index.len());
+
+        (index, graph)
+    }
+
+    /// Replay the writes made offline, in order. Stops once the homeserver is unreachable, keeping
+    /// the rest queued.
+    ///
+    /// Updates of pages saved elsewhere since are not applied, they are turned into merges instead.
+    /// Writes refused for any other reason are dropped from the queue, so they don't hold up the
+    /// others. The pages of both are kept in drafts.
+    pub async fn sync_pending_writes(&self) -> Result<SyncReport> {
+        let pending = self.cache.pending(&self.own_pk())?;
+
+        let mut report = SyncReport::default();
+        for (id, write) in &pending {
+            let result = match write {
+                PendingWrite::Create { page_id, page } => self.create_page(page, Some(page_id)).await.map(|_| ()),
+                PendingWrite::Update {
+                    page_id,
+                    page,
+                    base_version,
+                } => self.update_page(page_id, page, base_version.clone()).await,
+                PendingWrite::Delete { page_id } => self.delete_page(page_id).await,
+            };
+            match (result, write) {
+                (Ok(()), _) => report.synced += 1,
+                (Err(e), _) if StorageError::is_unreachable(&e) => {
+                    log::warn!("Homeserver is unreachable, offline changes stay queued: {e}");
+                    break;
+                }
+                (Err(e), PendingWrite::Update { page_id, page, .. }) if e.is::<UpdateConflict>() => {
+                    log::warn!("Offline update of {page_id} conflicts with a version saved since");
+                    let conflict = e.downcast::<UpdateConflict>()?;
+                    report.conflicts.push(SyncConflict {
+                        page_id: page_id.clone(),
+                        draft_id: self.keep_in_draft(write)?.unwrap_or_default(),
+                        merge: self.prepare_conflict_merge(page_id, page, conflict).await,
+                    });
+                }
+                (Err(e), _) => {
+                    log::error!("Failed to sync offline change to {}: {e}", write.page_id());
+                    let kept = match self.keep_in_draft(write)? {
+                        Some(_) => ", the changes are kept in the drafts",
+                        None => "",
+                    };
+                    report.failed.push(format!("{}: {e}{kept}", write.page_id()));
+                }
+            }
+            self.cache.dequeue(*id)?;
+        }
+
+        if !pending.is_empty() {
+            log::info!(
+                "Synced {} offline changes, {} in conflict, {} failed",
+                report.synced,
+                report.conflicts.len(),
+                report.failed.len()
+            );
+        }
+
+        Ok(report)
+    }
+
+    /// Keep the page written by a queued write in a draft, and return the draft ID. Deletes have
+    /// nothing to keep.
+    fn keep_in_draft(&self, write: &PendingWrite) -> Result<Option<String>> {
+        let (page_id, page, base_version) = match write {
+            PendingWrite::Create { page, .. } => (None, page, None),
+            PendingWrite::Update {
+                page_id,
+                page,
+                base_version,
+            } => (Some(page_id.clone()), page, base_version.clone()),
+            PendingWrite::Delete { .. } => return Ok(None),
+        };
+
+        let draft = Draft {
+            id: new_page_id(),
+            page_id,
+            base_version,
+            page: page.clone(),
+            saved_at: now(),
+        };
+        self.cache.save_draft(&self.own_pk(), &draft)?;
+
+        Ok(Some(draft.id))
+    }
+}
+
+/// Refuse the IDs that would write outside of the page files, like into the history of the pages
+fn check_page_id(page_id: &str) -> Result<()> {
+    if !is_valid_page_id(page_id) {
+        return Err(anyhow!("Invalid page ID: {page_id:?}"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use similar::{ChangeTag, DiffTag, TextDiff};
+
+use crate::{history::Revision, page::format_timestamp};
+
+/// One of the two versions compared in the diff view
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffSide {
+    /// The current version of a page, from the homeserver of `user_pk`
+    Page { user_pk: String, page_id: String },
+    /// A past revision of a page
+    Revision {
+        user_pk: String,
+        page_id: String,
+        revision: Revision,
+    },
+}
+
+impl DiffSide {
+    pub fn label(&self) -> String {
+        match self {
+            DiffSide::Page { user_pk, .. } => format!("{user_pk} (current)"),
+            DiffSide::Revision {
+                user_pk, revision, ..
+            } => format!("{user_pk} ({})", format_timestamp(revision.timestamp)),
+        }
+    }
+}
+
+/// A piece of a line, highlighted if it differs from the other side
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub text: String,
+    pub changed: bool,
+}
+
+/// A row of the side-by-side diff. A side is `None` if the line only exists on the other side.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffRow {
+    pub left: Option<Vec<Segment>>,
+    pub right: Option<Vec<Segment>>,
+}
+
+/// Line-level diff of `old` and `new`, with word-level changes marked within modified lines
+pub fn side_by_side(old: &str, new: &str) -> Vec<DiffRow> {
+    let diff = TextDiff::from_lines(old, new);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+
+    let mut rows = vec![];
+    for op in diff.ops() {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Equal => {
+                for line in &old_lines[old_range] {
+                    rows.push(DiffRow {
+                        left: Some(unchanged(line)),
+                        right: Some(unchanged(line)),
+                    });
+                }
+            }
+            DiffTag::Delete => {
+                for line in &old_lines[old_range] {
+                    rows.push(DiffRow {
+                        left: Some(changed(line)),
+                        right: None,
+                    });
+                }
+            }
+            DiffTag::Insert => {
+                for line in &new_lines[new_range] {
+                    rows.push(DiffRow {
+                        left: None,
+                        right: Some(changed(line)),
+                    });
+                }
+            }
+            DiffTag::Replace => {
+                let old_block = &old_lines[old_range];
+                let new_block = &new_lines[new_range];
+
+                // Modified lines are paired up, so the words that changed can be highlighted
+                for i in 0..old_block.len().max(new_block.len()) {
+                    let row = match (old_block.get(i), new_block.get(i)) {
+                        (Some(old_line), Some(new_line)) => {
+                            let (left, right) = word_diff(old_line, new_line);
+                            DiffRow {
+                                left: Some(left),
+                                right: Some(right),
+                            }
+                        }
+                        (Some(old_line), None) => DiffRow {
+                            left: Some(changed(old_line)),
+                            right: None,
+                        },
+                        (None, Some(new_line)) => DiffRow {
+                            left: None,
+                            right: Some(changed(new_line)),
+                        },
+                        (None, None) => unreachable!("index is below the length of one block"),
+                    };
+                    rows.push(row);
+                }
+            }
+        }
+    }
+
+    rows
+}
+
+/// Number of added and deleted lines
+pub fn line_stats(old: &str, new: &str) -> (usize, usize) {
+    let diff = TextDiff::from_lines(old, new);
+
+    let mut added = 0;
+    let mut deleted = 0;
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => added += 1,
+            ChangeTag::Delete => deleted += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+
+    (added, deleted)
+}
+
+fn word_diff(old_line: &str, new_line: &str) -> (Vec<Segment>, Vec<Segment>) {
+    let diff = TextDiff::from_words(trim_newline(old_line), trim_newline(new_line));
+
+    let mut left = vec![];
+    let mut right = vec![];
+    for change in diff.iter_all_changes() {
+        let text = change.value();
+        match change.tag() {
+            ChangeTag::Equal => {
+                push_segment(&mut left, text, false);
+                push_segment(&mut right, text, false);
+            }
+            ChangeTag::Delete => push_segment(&mut left, text, true),
+            ChangeTag::Insert => push_segment(&mut right, text, true),
+        }
+    }
+
+    (left, right)
+}
+
+/// Append text to the last segment if it has the same highlighting, so rows stay short
+fn push_segment(segments: &mut Vec<Segment>, text: &str, changed: bool) {
+    match segments.last_mut() {
+        Some(last) if last.changed == changed => last.text.push_str(text),
+        _ => segments.push(Segment {
+            text: text.to_string(),
+            changed,
+        }),
+    }
+}
+
+fn unchanged(line: &str) -> Vec<Segment> {
+    vec![Segment {
+        text: trim_newline(line).to_string(),
+        changed: false,
+    }]
+}
+
+fn changed(line: &str) -> Vec<Segment> {
+    vec![Segment {
+        text: trim_newline(line).to_string(),
+        changed: true,
+    }]
+}
+
+fn trim_newline(line: &str) -> &str {
+    line.trim_end_matches(['\r', '\n'])
+}
@@ -0,0 +1,49 @@
+use serde::Deserialize;
+
+/// Profile of a user, as published by pubky.app
+pub const PROFILE_PATH: &str = "/pub/pubky.app/profile.json";
+
+/// A followed user
+#[derive(Clone, Debug, PartialEq)]
+pub struct Follow {
+    pub user_pk: String,
+    /// Name from the pubky.app profile, if there is one
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Profile {
+    name: String,
+}
+
+impl Follow {
+    /// Whether `alias`, as in `[[@alias/Title]]`, designates this user: it is either the profile
+    /// name or the start of the public key
+    pub fn matches_alias(&self, alias: &str) -> bool {
+        let alias = alias.trim();
+        if alias.is_empty() {
+            return false;
+        }
+
+        let name_matches = self
+            .name
+            .as_deref()
+            .is_some_and(|name| name.trim().eq_ignore_ascii_case(alias));
+        name_matches || self.user_pk.starts_with(alias)
+    }
+
+    /// Name to show for the user, falling back to the public key
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.user_pk)
+    }
+}
+
+/// Name of a pubky.app profile, `None` if the profile can't be read or has no name
+pub fn parse_profile_name(json: &str) -> Option<String> {
+    let profile: Profile = serde_json::from_str(json)
+        .inspect_err(|e| log::warn!("Invalid profile: {e}"))
+        .ok()?;
+    let name = profile.name.trim();
+
+    (!name.is_empty()).then(|| name.to_string())
+}
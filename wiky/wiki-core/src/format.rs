@@ -0,0 +1,128 @@
+//! Markdown formatting of the text selected in an editor.
+//!
+//! Selections are ranges of character indices, as editors count them, not byte offsets.
+
+use std::ops::Range;
+
+/// Formatting applied from the toolbar or the shortcuts of the editors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Heading of level 1 to 6, for the selected lines
+    Heading(u8),
+    Bold,
+    Italic,
+    InlineCode,
+    BulletList,
+    NumberedList,
+    CodeBlock,
+    Table,
+    /// Link with the selection as text, and the URL left to type
+    Link,
+}
+
+/// Apply `format` to the selection of `text`, and return what to select afterwards
+pub fn apply_format(text: &mut String, selection: Range<usize>, format: Format) -> Range<usize> {
+    let selection = clamp(text, selection);
+    match format {
+        Format::Heading(level) => {
+            let hashes = "#".repeat(level.clamp(1, 6) as usize);
+            prefix_lines(text, selection, |_, line| {
+                format!("{hashes} {}", line.trim_start_matches('#').trim_start())
+            })
+        }
+        Format::Bold => wrap(text, selection, "**", "**", "bold text"),
+        Format::Italic => wrap(text, selection, "*", "*", "italic text"),
+        Format::InlineCode => wrap(text, selection, "`", "`", "code"),
+        Format::BulletList => prefix_lines(text, selection, |_, line| format!("- {line}")),
+        Format::NumberedList => prefix_lines(text, selection, |i, line| format!("{}. {line}", i + 1)),
+        Format::CodeBlock => {
+            let selection = whole_lines(text, selection);
+            wrap(text, selection, "```\n", "\n```", "code")
+        }
+        Format::Table => {
+            let table = "| Column | Column |\n| ------ | ------ |\n| Cell   | Cell   |\n";
+            let start = line_start(text, selection.start);
+            let snippet = if start == 0 { table.to_string() } else { format!("\n{table}") };
+            insert_text(text, start..start, &snippet)
+        }
+        Format::Link => {
+            let selected = slice(text, selection.clone());
+            let label = if selected.is_empty() { "link text".to_string() } else { selected };
+            insert_text(text, selection.clone(), &format!("[{label}](url)"));
+
+            // Select the placeholder URL, to be typed over
+            let url_start = selection.start + label.chars().count() + 3;
+            url_start..url_start + 3
+        }
+    }
+}
+
+/// Replace the selection with `snippet`, and return the cursor after it
+pub fn insert_text(text: &mut String, selection: Range<usize>, snippet: &str) -> Range<usize> {
+    let selection = clamp(text, selection);
+    let start = byte_offset(text, selection.start);
+    let end = byte_offset(text, selection.end);
+    text.replace_range(start..end, snippet);
+
+    let cursor = selection.start + snippet.chars().count();
+    cursor..cursor
+}
+
+/// Surround the selection, or a selected placeholder if nothing is selected
+fn wrap(text: &mut String, selection: Range<usize>, before: &str, after: &str, placeholder: &str) -> Range<usize> {
+    let selected = slice(text, selection.clone());
+    let inner = if selected.is_empty() { placeholder.to_string() } else { selected };
+    insert_text(text, selection.clone(), &format!("{before}{inner}{after}"));
+
+    let start = selection.start + before.chars().count();
+    start..start + inner.chars().count()
+}
+
+/// Rewrite each line touched by the selection, and select them all
+fn prefix_lines(text: &mut String, selection: Range<usize>, rewrite: impl Fn(usize, &str) -> String) -> Range<usize> {
+    let selection = whole_lines(text, selection);
+    let lines = slice(text, selection.clone());
+    let rewritten: Vec<String> = lines.split('\n').enumerate().map(|(i, line)| rewrite(i, line)).collect();
+    let rewritten = rewritten.join("\n");
+    insert_text(text, selection.clone(), &rewritten);
+
+    selection.start..selection.start + rewritten.chars().count()
+}
+
+/// Extend the selection to the start of its first line and the end of its last line
+fn whole_lines(text: &str, selection: Range<usize>) -> Range<usize> {
+    let start = line_start(text, selection.start);
+    let chars: Vec<char> = text.chars().collect();
+    let mut end = selection.end.max(start);
+    while end < chars.len() && chars[end] != '\n' {
+        end += 1;
+    }
+
+    start..end
+}
+
+fn line_start(text: &str, index: usize) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let mut start = index.min(chars.len());
+    while start > 0 && chars[start - 1] != '\n' {
+        start -= 1;
+    }
+
+    start
+}
+
+fn clamp(text: &str, selection: Range<usize>) -> Range<usize> {
+    let len = text.chars().count();
+    let start = selection.start.min(selection.end).min(len);
+    let end = selection.end.max(selection.start).min(len);
+
+    start..end
+}
+
+fn slice(text: &str, selection: Range<usize>) -> String {
+    text.chars().skip(selection.start).take(selection.end - selection.start).collect()
+}
+
+fn byte_offset(text: &str, index: usize) -> usize {
+    text.char_indices().nth(index).map_or(text.len(), |(offset, _)| offset)
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use pulldown_cmark::{Event, Parser, Tag};
+
+use crate::{
+    follow::Follow,
+    links::{expand_title_links, same_title, WikiLink},
+    page::WikiPage,
+};
+
+/// Identifies a page by its author and page ID
+pub type PageKey = (String, String);
+
+/// Where a link of an indexed page points to, resolved when the graph is queried, as the pages
+/// linked by title may be indexed later
+#[derive(Clone, Debug, PartialEq)]
+enum LinkTarget {
+    Page(PageKey),
+    /// A page of `user_pk`, by title
+    Title { user_pk: String, title: String },
+    /// A page of a followed user, by title
+    Alias { alias: String, title: String },
+}
+
+/// A page of the graph
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphPage {
+    pub title: String,
+    pub tags: Vec<String>,
+    /// Page this page was forked from
+    pub forked_from: Option<PageKey>,
+    targets: Vec<LinkTarget>,
+}
+
+/// A page linking to another one
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backlink {
+    pub user_pk: String,
+    pub page_id: String,
+    pub title: String,
+    /// Author of the version linked to, if the link points to another author's version of the page
+    pub via_fork_of: Option<String>,
+}
+
+/// Links between the indexed pages, parsed from their markdown the same way they are followed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkGraph {
+    pages: HashMap<PageKey, GraphPage>,
+    /// To resolve the `[[@alias/Title]]` links
+    follows: Vec<Follow>,
+}
+
+impl LinkGraph {
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    pub fn set_follows(&mut self, follows: Vec<Follow>) {
+        self.follows = follows;
+    }
+
+    /// Add a page to the graph, replacing the previous version of it
+    pub fn insert(&mut self, user_pk: &str, page_id: &str, page: &WikiPage) {
+        let forked_from = page
+            .meta
+            .forked_from
+            .as_ref()
+            .map(|forked_from| (forked_from.author_pk.clone(), forked_from.page_id.clone()));
+
+        self.pages.insert(
+            (user_pk.to_string(), page_id.to_string()),
+            GraphPage {
+                title: page.title().to_string(),
+                tags: page.meta.tags.clone(),
+                forked_from,
+                targets: link_targets(user_pk, &page.body),
+            },
+        );
+    }
+
+    pub fn remove(&mut self, user_pk: &str, page_id: &str) {
+        self.pages.remove(&(user_pk.to_string(), page_id.to_string()));
+    }
+
+    /// All pages of the graph
+    pub fn pages(&self) -> impl Iterator<Item = (&PageKey, &GraphPage)> {
+        self.pages.iter()
+    }
+
+    pub fn page(&self, key: &PageKey) -> Option<&GraphPage> {
+        self.pages.get(key)
+    }
+
+    /// Pages linked from a page, whether they are indexed or not
+    pub fn links_of(&self, key: &PageKey) -> Vec<PageKey> {
+        let Some(page) = self.pages.get(key) else {
+            return vec![];
+        };
+
+        let mut links: Vec<PageKey> = page
+            .targets
+            .iter()
+            .filter_map(|target| self.resolve(target))
+            .filter(|target| target != key)
+            .collect();
+        links.sort();
+        links.dedup();
+
+        links
+    }
+
+    /// Pages linking to a page, or to the versions other authors have of it, sorted by title
+    pub fn backlinks(&self, user_pk: &str, page_id: &str) -> Vec<Backlink> {
+        let mut backlinks = vec![];
+        for (source, page) in &self.pages {
+            if source.0 == user_pk && source.1 == page_id {
+                continue;
+            }
+
+            // A direct link wins over links to other versions
+            let mut via_fork_of = None;
+            let mut links_here = false;
+            for target in self.links_of(source) {
+                if target.1 != page_id {
+                    continue;
+                }
+                if target.0 == user_pk {
+                    via_fork_of = None;
+                    links_here = true;
+                    break;
+                }
+                via_fork_of.get_or_insert(target.0);
+                links_here = true;
+            }
+
+            if links_here {
+                backlinks.push(Backlink {
+                    user_pk: source.0.clone(),
+                    page_id: source.1.clone(),
+                    title: page.title.clone(),
+                    via_fork_of,
+                });
+            }
+        }
+
+        backlinks.sort_by(|a, b| (&a.title, &a.user_pk).cmp(&(&b.title, &b.user_pk)));
+        backlinks
+    }
+
+    fn resolve(&self, target: &LinkTarget) -> Option<PageKey> {
+        match target {
+            LinkTarget::Page(key) => Some(key.clone()),
+            LinkTarget::Title { user_pk, title } => self.find_title(user_pk, title),
+            LinkTarget::Alias { alias, title } => {
+                let follow = self.follows.iter().find(|follow| follow.matches_alias(alias))?;
+                self.find_title(&follow.user_pk, title)
+            }
+        }
+    }
+
+    fn find_title(&self, user_pk: &str, title: &str) -> Option<PageKey> {
+        self.pages
+            .iter()
+            .find(|((pk, _), page)| pk == user_pk && same_title(&page.title, title))
+            .map(|(key, _)| key.clone())
+    }
+}
+
+/// Targets of the wiki links of a page of `user_pk`, external links left out
+fn link_targets(user_pk: &str, body: &str) -> Vec<LinkTarget> {
+    let mut targets = vec![];
+
+    // Only collects the `[[…]]` links, which are not markdown links
+    expand_title_links(body, |link| {
+        targets.push(match &link.alias {
+            Some(alias) => LinkTarget::Alias {
+                alias: alias.clone(),
+                title: link.title.clone(),
+            },
+            None => LinkTarget::Title {
+                user_pk: user_pk.to_string(),
+                title: link.title.clone(),
+            },
+        });
+        None
+    });
+
+    for event in Parser::new(body) {
+        let Event::Start(Tag::Link { dest_url, .. }) = event else {
+            continue;
+        };
+        match WikiLink::parse(&dest_url) {
+            Some(WikiLink::Page { user_pk, page_id, .. }) => {
+                targets.push(LinkTarget::Page((user_pk, page_id)));
+            }
+            Some(WikiLink::Title { title, .. }) => targets.push(LinkTarget::Title {
+                user_pk: user_pk.to_string(),
+                title,
+            }),
+            Some(WikiLink::Attachment { .. } | WikiLink::External(_) | WikiLink::NewPage { .. }) | None => {}
+        }
+    }
+
+    targets
+}
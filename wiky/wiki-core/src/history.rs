@@ -0,0 +1,182 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    page::{ForkedFrom, WikiPage},
+    storage::{StorageError, WikiStorage},
+};
+
+/// A saved version of a page.
+///
+/// Stored under `{timestamp}-{content_hash}`, so versions saved within the same second are all kept.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Revision {
+    /// `updated_at` of the saved version
+    pub timestamp: u64,
+    /// See [`WikiPage::content_hash`], empty if unknown or for revisions saved before it was part
+    /// of their key
+    pub content_hash: String,
+}
+
+impl Revision {
+    /// The revision `page` is saved as
+    pub fn of(page: &WikiPage) -> Self {
+        Self {
+            timestamp: page.meta.updated_at,
+            content_hash: page.content_hash(),
+        }
+    }
+
+    /// A revision known only by its timestamp
+    pub fn at(timestamp: u64) -> Self {
+        Self {
+            timestamp,
+            content_hash: String::new(),
+        }
+    }
+
+    fn key(&self) -> String {
+        if self.content_hash.is_empty() {
+            self.timestamp.to_string()
+        } else {
+            format!("{}-{}", self.timestamp, self.content_hash)
+        }
+    }
+
+    fn parse(key: &str) -> Option<Self> {
+        let (timestamp, content_hash) = key.split_once('-').unwrap_or((key, ""));
+
+        Some(Self {
+            timestamp: timestamp.parse().ok()?,
+            content_hash: content_hash.to_string(),
+        })
+    }
+}
+
+/// Folder holding the revisions of a page, relative to the homeserver of its author
+fn revisions_dir(page_id: &str) -> String {
+    format!("/pub/wiki.app/revisions/{page_id}/")
+}
+
+/// Store `page` as a revision of `page_id`
+pub async fn save_revision(
+    storage: &impl WikiStorage,
+    page_id: &str,
+    page: &WikiPage,
+) -> Result<()> {
+    let path = format!("{}{}", revisions_dir(page_id), Revision::of(page).key());
+    storage.put(&path, page.to_markdown()?).await?;
+
+    log::info!("Saved revision at path: {}", path);
+
+    Ok(())
+}
+
+/// All revisions of a page, newest first
+pub async fn list_revisions(
+    storage: &impl WikiStorage,
+    user_pk: &str,
+    page_id: &str,
+) -> Result<Vec<Revision>> {
+    let mut revisions: Vec<Revision> = storage
+        .list(user_pk, &revisions_dir(page_id))
+        .await?
+        .iter()
+        .filter_map(|file_url| Revision::parse(file_url.split('/').next_back()?))
+        .collect();
+    revisions.sort_unstable_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| a.content_hash.cmp(&b.content_hash)));
+
+    Ok(revisions)
+}
+
+/// Content of a revision.
+///
+/// A revision known only by its timestamp, or saved before the content hash was part of the key,
+/// is looked up among the revisions saved at that time.
+pub async fn fetch_revision(
+    storage: &impl WikiStorage,
+    user_pk: &str,
+    page_id: &str,
+    revision: &Revision,
+) -> Result<WikiPage> {
+    let path = format!("{}{}", revisions_dir(page_id), revision.key());
+    match storage.get(user_pk, &path).await {
+        Ok(file) => return Ok(WikiPage::parse(&file.content)),
+        Err(e) if !StorageError::is_not_found(&e) => return Err(e),
+        Err(_) => {}
+    }
+
+    let saved = list_revisions(storage, user_pk, page_id)
+        .await?
+        .into_iter()
+        .find(|saved| {
+            saved.timestamp == revision.timestamp
+                && (revision.content_hash.is_empty() || saved.content_hash.is_empty())
+        })
+        .ok_or(StorageError::NotFound(path))?;
+    let file = storage
+        .get(user_pk, &format!("{}{}", revisions_dir(page_id), saved.key()))
+        .await?;
+
+    Ok(WikiPage::parse(&file.content))
+}
+
+/// Find the version a fork was created from, to use as the base of a three-way merge.
+///
+/// This is the upstream revision recorded at fork time or, if upstream doesn't have it, the
+/// revision of the fork that still has the forked content.
+pub async fn find_fork_base(
+    storage: &impl WikiStorage,
+    page_id: &str,
+    forked_from: &ForkedFrom,
+) -> Result<WikiPage> {
+    let is_base = |page: &WikiPage| {
+        forked_from.content_hash.is_empty() || page.content_hash() == forked_from.content_hash
+    };
+
+    let forked_revision = Revision {
+        timestamp: forked_from.version,
+        content_hash: forked_from.content_hash.clone(),
+    };
+    let upstream_revision =
+        fetch_revision(storage, &forked_from.author_pk, &forked_from.page_id, &forked_revision).await;
+    match upstream_revision {
+        Ok(page) if is_base(&page) => return Ok(page),
+        Ok(_) => log::warn!("Upstream revision doesn't match the forked content"),
+        Err(e) => log::warn!("Upstream revision of the fork is not available: {e}"),
+    }
+
+    // Oldest first, as the forked content is most likely in the first revisions
+    let own_pk = storage.public_key();
+    for revision in list_revisions(storage, &own_pk, page_id).await?.into_iter().rev() {
+        let page = fetch_revision(storage, &own_pk, page_id, &revision).await?;
+        if page.content_hash() == forked_from.content_hash {
+            return Ok(page);
+        }
+    }
+
+    Err(anyhow!("The version this page was forked from could not be found"))
+}
+
+/// Make sure the version currently stored for a page is in its history, before it gets overwritten.
+///
+/// Only pages saved before revisions existed lack one.
+pub async fn archive_current_version(storage: &impl WikiStorage, page_id: &str) -> Result<()> {
+    let own_pk = storage.public_key();
+    if !list_revisions(storage, &own_pk, page_id).await?.is_empty() {
+        return Ok(());
+    }
+
+    let file = storage.get(&own_pk, &format!("/pub/wiki.app/{page_id}")).await?;
+    save_revision(storage, page_id, &WikiPage::parse(&file.content)).await
+}
+
+/// Delete all revisions of a page
+pub async fn delete_revisions(storage: &impl WikiStorage, page_id: &str) -> Result<()> {
+    let own_pk = storage.public_key();
+    for revision in list_revisions(storage, &own_pk, page_id).await? {
+        let path = format!("{}{}", revisions_dir(page_id), revision.key());
+        storage.delete(&path).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,21 @@
+//! Pages, forks, history, proposals, search and the local cache of Pubky Wiki, independent of
+//! any frontend. [`WikiClient`] is the entry point once signed in.
+
+pub mod attachment;
+pub mod cache;
+pub mod client;
+pub mod diff;
+pub mod follow;
+pub mod format;
+pub mod graph;
+pub mod history;
+pub mod links;
+pub mod memory;
+pub mod merge;
+pub mod page;
+pub mod profile;
+pub mod proposal;
+pub mod search;
+pub mod storage;
+
+pub use client::WikiClient;
@@ -0,0 +1,352 @@
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use pubky::PublicKey;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+use crate::attachment::FILES_DIR;
+
+/// Folder of the wiki pages on the homeservers
+const WIKI_FOLDER: &str = "/pub/wiki.app/";
+
+/// Sub-folders of the wiki folder, holding revisions, proposals and attachments rather than pages
+const RESERVED_PAGE_IDS: [&str; 3] = ["revisions", "proposals", "files"];
+
+/// Escaped in the titles of `wiki:` links, so they stay a single markdown link target
+const TITLE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'(')
+    .add(b')')
+    .add(b'[')
+    .add(b']');
+
+/// Where a link in a page points to.
+///
+/// The link targets understood are:
+/// - `pubky://{user_pk}/pub/wiki.app/{page_id}`, the canonical URL of a page
+/// - `pubky://{user_pk}/pub/wiki.app/files/{name}`, a file attached to a page
+/// - `wiki:{user_pk}/{page_id}`
+/// - `wiki:{title}`, with the title percent-encoded, for a page of the author of the linking page
+/// - `{user_pk}/{page_id}`, the links of the first versions of the app
+/// - `http://`, `https://` and `mailto:` URLs
+/// - `wiki-new:{title}`, with the title percent-encoded, for `[[…]]` links to missing pages
+///
+/// Links to pages can end with `#heading`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WikiLink {
+    Page {
+        user_pk: String,
+        page_id: String,
+        heading: Option<String>,
+    },
+    /// A page of the author of the linking page, found by its title
+    Title {
+        title: String,
+        heading: Option<String>,
+    },
+    /// An image or another file attached to a page, at `path` on the homeserver of `user_pk`
+    Attachment { user_pk: String, path: String },
+    /// A web page or an e-mail address, outside of the wiki
+    External(String),
+    /// No page has this title yet, so the link offers to create it
+    NewPage { title: String },
+}
+
+impl WikiLink {
+    /// Parse the target of a link, `None` if it is none of the forms above
+    pub fn parse(url: &str) -> Option<Self> {
+        let link = parse_link(url.trim());
+        if link.is_none() {
+            log::warn!("Invalid Pubky Wiki link: {url}");
+        }
+
+        link
+    }
+
+    /// Canonical URL of a page, as shared
+    pub fn page_url(user_pk: &str, page_id: &str) -> String {
+        format!("pubky://{user_pk}{WIKI_FOLDER}{page_id}")
+    }
+
+    /// URL of an attachment, as inserted in the pages
+    pub fn attachment_url(user_pk: &str, path: &str) -> String {
+        format!("pubky://{user_pk}{path}")
+    }
+
+    /// Target of a link to a page of the same author, by title
+    pub fn title_url(title: &str) -> String {
+        format!("wiki:{}", utf8_percent_encode(title, TITLE))
+    }
+
+    /// Target of a link offering to create a page with this title
+    pub fn new_page_url(title: &str) -> String {
+        format!("wiki-new:{}", utf8_percent_encode(title, TITLE))
+    }
+}
+
+fn parse_link(url: &str) -> Option<WikiLink> {
+    let lowercase = url.to_ascii_lowercase();
+    if ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|scheme| lowercase.starts_with(scheme))
+    {
+        return Some(WikiLink::External(url.to_string()));
+    }
+
+    let (target, heading) = match url.split_once('#') {
+        Some((target, heading)) => (target, Some(decode(heading)).filter(|h| !h.is_empty())),
+        None => (url, None),
+    };
+
+    if let Some(rest) = target.strip_prefix("pubky://") {
+        let (user_pk, path) = rest.split_once('/')?;
+        if let Some(name) = path.strip_prefix(&FILES_DIR[1..]) {
+            let user_pk = user_pk.trim();
+            if user_pk.is_empty() || name.is_empty() || name.contains('/') {
+                return None;
+            }
+            return Some(WikiLink::Attachment {
+                user_pk: user_pk.to_string(),
+                path: format!("/{path}"),
+            });
+        }
+        let page_id = path.strip_prefix(&WIKI_FOLDER[1..])?;
+        return page_link(user_pk, page_id, heading);
+    }
+
+    if let Some(rest) = url.strip_prefix("wiki-new:") {
+        let title = decode(rest).trim().to_string();
+        return (!title.is_empty()).then_some(WikiLink::NewPage { title });
+    }
+
+    if let Some(rest) = target.strip_prefix("wiki:") {
+        if let Some((user_pk, page_id)) = rest.split_once('/') {
+            return page_link(user_pk, page_id, heading);
+        }
+
+        let title = decode(rest).trim().to_string();
+        return (!title.is_empty()).then_some(WikiLink::Title { title, heading });
+    }
+
+    // Without a scheme, only a public key tells a wiki link apart from a relative URL
+    let (user_pk, page_id) = target.split_once('/')?;
+    PublicKey::try_from(user_pk).ok()?;
+    page_link(user_pk, page_id, heading)
+}
+
+fn page_link(user_pk: &str, page_id: &str, heading: Option<String>) -> Option<WikiLink> {
+    let user_pk = user_pk.trim();
+    let page_id = page_id.trim();
+
+    if user_pk.is_empty() || !is_valid_page_id(page_id) {
+        return None;
+    }
+
+    Some(WikiLink::Page {
+        user_pk: user_pk.to_string(),
+        page_id: page_id.to_string(),
+        heading,
+    })
+}
+
+/// Whether a page can be stored under `page_id`, as a file right in the wiki folder
+pub fn is_valid_page_id(page_id: &str) -> bool {
+    !page_id.is_empty() && !page_id.contains('/') && !RESERVED_PAGE_IDS.contains(&page_id)
+}
+
+fn decode(component: &str) -> String {
+    percent_decode_str(component).decode_utf8_lossy().into_owned()
+}
+
+/// Author and ID of the page a link points to, if it is a link to a page
+pub fn extract_details_wiki_url(url: &str) -> Option<(String, String)> {
+    match WikiLink::parse(url)? {
+        WikiLink::Page {
+            user_pk, page_id, ..
+        } => Some((user_pk, page_id)),
+        _ => None,
+    }
+}
+
+/// Whether a page title matches the title of a `wiki:{title}` link
+pub fn same_title(page_title: &str, title: &str) -> bool {
+    page_title.trim().to_lowercase() == title.trim().to_lowercase()
+}
+
+/// Whether a heading of a page is the one a `#heading` link points to.
+///
+/// Both are compared as slugs, so `#getting-started` and `#Getting%20Started` point to a
+/// "Getting Started" heading.
+pub fn same_heading(page_heading: &str, heading: &str) -> bool {
+    let wanted = slug(heading);
+    !wanted.is_empty() && wanted == slug(page_heading)
+}
+
+/// Lowercase words of a heading, without punctuation, joined with hyphens
+fn slug(text: &str) -> String {
+    text.split(|c: char| c.is_whitespace() || c == '-')
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Byte offset in `body` of the first heading a `#heading` link points to, `None` if there is none.
+///
+/// Only headings outside of lists and quotes are found, so the offset is always at the start of a
+/// block.
+pub fn heading_offset(body: &str, heading: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    // Start and text of the top-level heading being parsed
+    let mut current: Option<(usize, String)> = None;
+
+    for (event, range) in Parser::new(body).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { .. }) if depth == 0 => {
+                current = Some((range.start, String::new()));
+                depth += 1;
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(TagEnd::Heading(_)) if depth == 1 => {
+                if let Some((start, text)) = current.take() {
+                    if same_heading(text.trim(), heading) {
+                        return Some(start);
+                    }
+                }
+                depth -= 1;
+            }
+            Event::End(_) => depth = depth.saturating_sub(1),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, current_text)) = &mut current {
+                    current_text.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// A `[[Title]]`, `[[Title|label]]` or `[[@alias/Title]]` link in the body of a page
+#[derive(Clone, Debug, PartialEq)]
+pub struct TitleLink {
+    /// Name or start of the public key of a followed user, for a page of theirs
+    pub alias: Option<String>,
+    pub title: String,
+    pub label: Option<String>,
+}
+
+impl TitleLink {
+    /// Parse what is between the double brackets
+    fn parse(inner: &str) -> Option<Self> {
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(label.trim().to_string()).filter(|l| !l.is_empty())),
+            None => (inner, None),
+        };
+
+        let (alias, title) = match target.trim().strip_prefix('@') {
+            Some(rest) => {
+                let (alias, title) = rest.split_once('/')?;
+                (Some(alias.trim().to_string()), title)
+            }
+            None => (None, target),
+        };
+
+        let title = title.trim();
+        if title.is_empty() || alias.as_deref() == Some("") {
+            return None;
+        }
+
+        Some(Self {
+            alias,
+            title: title.to_string(),
+            label,
+        })
+    }
+
+    /// Text shown for the link
+    pub fn text(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.title)
+    }
+}
+
+/// Turn the `[[…]]` links of a body into markdown links, leaving code untouched.
+///
+/// `resolve` gives the target of each link, or `None` if no page has its title, in which case the
+/// link offers to create the page.
+pub fn expand_title_links(body: &str, mut resolve: impl FnMut(&TitleLink) -> Option<String>) -> String {
+    let mut expanded = String::with_capacity(body.len());
+    let mut in_fence = false;
+
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        if in_fence || line.starts_with("    ") || line.starts_with('\t') {
+            expanded.push_str(line);
+            continue;
+        }
+
+        expand_line(line, &mut resolve, &mut expanded);
+    }
+
+    expanded
+}
+
+fn expand_line(line: &str, resolve: &mut impl FnMut(&TitleLink) -> Option<String>, expanded: &mut String) {
+    let mut rest = line;
+    let mut in_code = false;
+
+    while !rest.is_empty() {
+        // Inline code spans are left as they are
+        let next_tick = rest.find('`');
+        let next_link = if in_code { None } else { rest.find("[[") };
+
+        match (next_tick, next_link) {
+            (Some(tick), link) if link.is_none_or(|link| tick < link) => {
+                expanded.push_str(&rest[..=tick]);
+                rest = &rest[tick + 1..];
+                in_code = !in_code;
+            }
+            (_, Some(start)) => {
+                expanded.push_str(&rest[..start]);
+                let after = &rest[start + 2..];
+                let link = after
+                    .find("]]")
+                    .and_then(|end| Some((TitleLink::parse(&after[..end])?, end)));
+
+                match link {
+                    Some((link, end)) => {
+                        let text = escape_label(link.text());
+                        match resolve(&link) {
+                            Some(url) => expanded.push_str(&format!("[{text}](<{url}>)")),
+                            None => expanded.push_str(&format!(
+                                "[{text} ✨](<{}>)",
+                                WikiLink::new_page_url(&link.title)
+                            )),
+                        }
+                        rest = &after[end + 2..];
+                    }
+                    None => {
+                        expanded.push_str("[[");
+                        rest = after;
+                    }
+                }
+            }
+            _ => {
+                expanded.push_str(rest);
+                rest = "";
+            }
+        }
+    }
+}
+
+fn escape_label(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('[', "\\[").replace(']', "\\]")
+}
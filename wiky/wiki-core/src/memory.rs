@@ -0,0 +1,166 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::storage::{StorageError, StoredFile, WikiStorage};
+
+/// A stored file, kept as bytes as attachments need not be text
+#[derive(Clone)]
+struct MemoryFile {
+    content: Vec<u8>,
+    etag: Option<String>,
+}
+
+/// Files of all users, by user and absolute path
+#[derive(Default)]
+struct Homeservers {
+    files: BTreeMap<(String, String), MemoryFile>,
+    /// Bumped on every write, to give each version its own ETag
+    version: u64,
+}
+
+/// [`WikiStorage`] keeping the files in memory, to run the wiki without a homeserver, like in tests.
+///
+/// Clones, and the handles made with [`MemoryStorage::as_user`], see the same files, like users of
+/// the same network would.
+#[derive(Clone)]
+pub struct MemoryStorage {
+    public_key: String,
+    homeservers: Arc<Mutex<Homeservers>>,
+    reachable: Arc<AtomicBool>,
+}
+
+impl MemoryStorage {
+    /// Empty storage, signed in as `public_key`
+    pub fn new(public_key: &str) -> Self {
+        Self {
+            public_key: public_key.to_string(),
+            homeservers: Arc::default(),
+            reachable: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Handle on the same files, signed in as another user
+    pub fn as_user(&self, public_key: &str) -> Self {
+        Self {
+            public_key: public_key.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Simulate the network going down, or back up. All handles are affected.
+    pub fn set_reachable(&self, reachable: bool) {
+        self.reachable.store(reachable, Ordering::Relaxed);
+    }
+
+    /// Make the signed in user follow `user_pk`, as pubky.app does
+    pub fn follow(&self, user_pk: &str) -> Result<()> {
+        self.write(&format!("/pub/pubky.app/follows/{user_pk}"), Some(vec![]))
+    }
+
+    fn homeservers(&self) -> Result<MutexGuard<'_, Homeservers>> {
+        if !self.reachable.load(Ordering::Relaxed) {
+            return Err(StorageError::Unreachable("network is down".to_string()).into());
+        }
+
+        self.homeservers
+            .lock()
+            .map_err(|_| anyhow!("Memory storage is poisoned"))
+    }
+
+    /// Write a file of the signed in user, or delete it if `content` is `None`
+    fn write(&self, path: &str, content: Option<Vec<u8>>) -> Result<()> {
+        let mut homeservers = self.homeservers()?;
+        let key = (self.public_key.clone(), path.to_string());
+
+        match content {
+            Some(content) => {
+                homeservers.version += 1;
+                let etag = Some(format!("\"{}\"", homeservers.version));
+                homeservers.files.insert(key, MemoryFile { content, etag });
+            }
+            None => {
+                homeservers.files.remove(&key);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn file(&self, user_pk: &str, path: &str) -> Result<MemoryFile> {
+        let key = (user_pk.to_string(), path.to_string());
+
+        self.homeservers()?
+            .files
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| StorageError::NotFound(format!("pubky://{user_pk}{path}")).into())
+    }
+}
+
+impl WikiStorage for MemoryStorage {
+    fn public_key(&self) -> String {
+        self.public_key.clone()
+    }
+
+    async fn put(&self, path: &str, content: String) -> Result<()> {
+        self.write(path, Some(content.into_bytes()))
+    }
+
+    async fn put_bytes(&self, path: &str, content: Vec<u8>) -> Result<()> {
+        self.write(path, Some(content))
+    }
+
+    async fn delete(&self, path: &str) -> Result<()> {
+        self.write(path, None)
+    }
+
+    async fn get(&self, user_pk: &str, path: &str) -> Result<StoredFile> {
+        let file = self.file(user_pk, path)?;
+
+        Ok(StoredFile {
+            content: String::from_utf8_lossy(&file.content).into_owned(),
+            etag: file.etag,
+        })
+    }
+
+    async fn get_bytes(&self, user_pk: &str, path: &str) -> Result<Vec<u8>> {
+        Ok(self.file(user_pk, path)?.content)
+    }
+
+    async fn etag(&self, user_pk: &str, path: &str) -> Result<Option<String>> {
+        let key = (user_pk.to_string(), path.to_string());
+
+        Ok(self.homeservers()?.files.get(&key).and_then(|file| file.etag.clone()))
+    }
+
+    async fn exists(&self, user_pk: &str, path: &str) -> Result<bool> {
+        let key = (user_pk.to_string(), path.to_string());
+
+        Ok(self.homeservers()?.files.contains_key(&key))
+    }
+
+    async fn list(&self, user_pk: &str, folder: &str) -> Result<Vec<String>> {
+        let homeservers = self.homeservers()?;
+
+        let urls = homeservers
+            .files
+            .keys()
+            .filter(|(owner, path)| {
+                owner == user_pk
+                    && path
+                        .strip_prefix(folder)
+                        .is_some_and(|name| !name.is_empty() && !name.contains('/'))
+            })
+            .map(|(_, path)| format!("pubky://{user_pk}{path}"))
+            .collect();
+
+        Ok(urls)
+    }
+}
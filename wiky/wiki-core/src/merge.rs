@@ -0,0 +1,261 @@
+use std::ops::Range;
+
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+
+use crate::{page::WikiPage, proposal::ReceivedProposal};
+
+const CONFLICT_START: &str = "<<<<<<< mine";
+const CONFLICT_SEPARATOR: &str = "=======";
+/// Followed by the label of the other side
+const CONFLICT_END: &str = ">>>>>>>";
+
+/// Outcome of a three-way merge
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeResult {
+    /// Merged text, with conflict markers around the hunks that could not be merged
+    pub text: String,
+    /// Number of non-conflicting hunks applied from upstream
+    pub applied: usize,
+    pub conflicts: usize,
+}
+
+/// Where the changes merged into an own page come from
+#[derive(Clone, Debug, PartialEq)]
+pub enum MergeSource {
+    /// The current version of the page a fork was created from
+    Upstream(WikiPage),
+    /// A fork version proposed by one of the follows
+    Proposal(ReceivedProposal),
+    /// The version of an own page saved elsewhere since `base`, the version an update was made to
+    Concurrent { base: Box<WikiPage>, current: Box<WikiPage> },
+}
+
+impl MergeSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            MergeSource::Upstream(_) => "upstream",
+            MergeSource::Proposal(_) => "proposal",
+            MergeSource::Concurrent { .. } => "concurrent",
+        }
+    }
+}
+
+/// A merge ready to be reviewed in the Merge Wiki view
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreparedMerge {
+    pub result: MergeResult,
+    /// The own page the changes are merged into, as it was when merging
+    pub mine: WikiPage,
+    pub source: MergeSource,
+}
+
+impl PreparedMerge {
+    /// Merge `theirs`, the body brought by `source`, into `mine`, both derived from `base`
+    pub fn new(base: &str, mine: WikiPage, theirs: &str, source: MergeSource) -> Self {
+        Self {
+            result: merge(base, &mine.body, theirs, source.label()),
+            mine,
+            source,
+        }
+    }
+}
+
+/// How to resolve a conflict
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    Mine,
+    Upstream,
+    Both,
+}
+
+/// A change of one side relative to the base: `base` lines are replaced by `lines`
+struct Hunk<'a> {
+    base: Range<usize>,
+    lines: &'a [&'a str],
+}
+
+/// Line-based three-way merge of `mine` and `upstream`, which both derive from `base`.
+///
+/// Changes made on only one side are applied. Overlapping or adjacent changes that differ are
+/// kept as conflicts, between markers, the closing one naming the other side with `label`.
+pub fn merge(base: &str, mine: &str, upstream: &str, label: &str) -> MergeResult {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let mine_lines: Vec<&str> = mine.split_inclusive('\n').collect();
+    let upstream_lines: Vec<&str> = upstream.split_inclusive('\n').collect();
+
+    let mine_hunks = hunks(&base_lines, &mine_lines);
+    let upstream_hunks = hunks(&base_lines, &upstream_lines);
+
+    let mut out = String::new();
+    let mut applied = 0;
+    let mut conflicts = 0;
+
+    let mut pos = 0;
+    let (mut m, mut u) = (0, 0);
+    while m < mine_hunks.len() || u < upstream_hunks.len() {
+        // Start a cluster with the hunk that comes first, then absorb all hunks touching it
+        let start_with_mine = match (mine_hunks.get(m), upstream_hunks.get(u)) {
+            (Some(mh), Some(uh)) => mh.base.start <= uh.base.start,
+            (Some(_), None) => true,
+            _ => false,
+        };
+        let (m_start, u_start) = (m, u);
+        let mut range = if start_with_mine {
+            m += 1;
+            mine_hunks[m_start].base.clone()
+        } else {
+            u += 1;
+            upstream_hunks[u_start].base.clone()
+        };
+        loop {
+            if let Some(hunk) = mine_hunks.get(m).filter(|h| touches(&h.base, &range)) {
+                range = range.start.min(hunk.base.start)..range.end.max(hunk.base.end);
+                m += 1;
+            } else if let Some(hunk) = upstream_hunks.get(u).filter(|h| touches(&h.base, &range)) {
+                range = range.start.min(hunk.base.start)..range.end.max(hunk.base.end);
+                u += 1;
+            } else {
+                break;
+            }
+        }
+
+        push_lines(&mut out, &base_lines[pos..range.start]);
+
+        let mine_cluster = &mine_hunks[m_start..m];
+        let upstream_cluster = &upstream_hunks[u_start..u];
+        let mine_text = apply(&base_lines, range.clone(), mine_cluster);
+        let upstream_text = apply(&base_lines, range.clone(), upstream_cluster);
+
+        if upstream_cluster.is_empty() || mine_text == upstream_text {
+            out.push_str(&mine_text);
+        } else if mine_cluster.is_empty() {
+            out.push_str(&upstream_text);
+            applied += 1;
+        } else {
+            push_conflict(&mut out, &mine_text, &upstream_text, label);
+            conflicts += 1;
+        }
+
+        pos = range.end;
+    }
+    push_lines(&mut out, &base_lines[pos..]);
+
+    MergeResult {
+        text: out,
+        applied,
+        conflicts,
+    }
+}
+
+/// Number of conflicts still marked in `text`
+pub fn conflict_count(text: &str) -> usize {
+    text.lines().filter(|line| *line == CONFLICT_START).count()
+}
+
+/// Replace the conflict at `index` by the chosen side
+pub fn resolve_conflict(text: &str, index: usize, resolution: Resolution) -> String {
+    enum Section {
+        Outside,
+        Mine,
+        Upstream,
+    }
+
+    let mut out = String::new();
+    let mut section = Section::Outside;
+    let mut current = 0;
+    let (mut mine, mut upstream) = (String::new(), String::new());
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_end_matches(['\r', '\n']);
+        match section {
+            Section::Outside if trimmed == CONFLICT_START && current == index => {
+                section = Section::Mine;
+            }
+            Section::Outside => {
+                if trimmed == CONFLICT_START {
+                    current += 1;
+                }
+                out.push_str(line);
+            }
+            Section::Mine if trimmed == CONFLICT_SEPARATOR => section = Section::Upstream,
+            Section::Mine => mine.push_str(line),
+            Section::Upstream if is_conflict_end(trimmed) => {
+                match resolution {
+                    Resolution::Mine => out.push_str(&mine),
+                    Resolution::Upstream => out.push_str(&upstream),
+                    Resolution::Both => {
+                        out.push_str(&mine);
+                        out.push_str(&upstream);
+                    }
+                }
+                current += 1;
+                section = Section::Outside;
+            }
+            Section::Upstream => upstream.push_str(line),
+        }
+    }
+
+    out
+}
+
+fn hunks<'a>(base: &[&str], other: &'a [&'a str]) -> Vec<Hunk<'a>> {
+    capture_diff_slices(Algorithm::Myers, base, other)
+        .into_iter()
+        .filter_map(|op| {
+            let (tag, base_range, other_range) = op.as_tag_tuple();
+            (tag != DiffTag::Equal).then(|| Hunk {
+                base: base_range,
+                lines: &other[other_range],
+            })
+        })
+        .collect()
+}
+
+/// Whether a hunk overlaps or is adjacent to a base range, so both must be merged together
+fn touches(hunk: &Range<usize>, range: &Range<usize>) -> bool {
+    hunk.start <= range.end && range.start <= hunk.end
+}
+
+/// The text of one side for the base `range`, given the hunks of that side within the range
+fn apply(base: &[&str], range: Range<usize>, hunks: &[Hunk]) -> String {
+    let mut out = String::new();
+    let mut pos = range.start;
+    for hunk in hunks {
+        push_lines(&mut out, &base[pos..hunk.base.start]);
+        push_lines(&mut out, hunk.lines);
+        pos = hunk.base.end;
+    }
+    push_lines(&mut out, &base[pos..range.end]);
+
+    out
+}
+
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+    }
+}
+
+fn push_conflict(out: &mut String, mine: &str, upstream: &str, label: &str) {
+    for (marker, text) in [(CONFLICT_START, mine), (CONFLICT_SEPARATOR, upstream)] {
+        ensure_newline(out);
+        out.push_str(marker);
+        out.push('\n');
+        out.push_str(text);
+    }
+    ensure_newline(out);
+    out.push_str(&format!("{CONFLICT_END} {label}"));
+    out.push('\n');
+}
+
+/// Whether a line closes a conflict, whatever the label of the other side
+fn is_conflict_end(line: &str) -> bool {
+    line.strip_prefix(CONFLICT_END)
+        .is_some_and(|label| label.is_empty() || label.starts_with(' '))
+}
+
+fn ensure_newline(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
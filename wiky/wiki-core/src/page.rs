@@ -0,0 +1,277 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Version of the page format written by this app.
+///
+/// Pages without front-matter are plain markdown written by older versions and get version `0`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Marks the start and the end of the TOML front-matter at the top of a page
+const FRONT_MATTER_DELIMITER: &str = "+++";
+
+/// Metadata stored in the front-matter of a wiki page.
+///
+/// Missing fields are read as their default, so a page edited by hand or by another client keeps
+/// its front-matter.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PageMeta {
+    #[serde(default)]
+    pub schema_version: u32,
+    #[serde(default)]
+    pub title: String,
+    /// Unix timestamp, in seconds
+    #[serde(default)]
+    pub created_at: u64,
+    /// Unix timestamp, in seconds
+    #[serde(default)]
+    pub updated_at: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forked_from: Option<ForkedFrom>,
+}
+
+/// The page version a fork was created from
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ForkedFrom {
+    pub author_pk: String,
+    pub page_id: String,
+    /// `updated_at` of the forked page
+    pub version: u64,
+    /// Hash of the forked body, see [`WikiPage::content_hash`]
+    #[serde(default)]
+    pub content_hash: String,
+    /// Unix timestamp of the fork, in seconds
+    #[serde(default)]
+    pub forked_at: u64,
+}
+
+impl ForkedFrom {
+    /// Provenance for a fork of `page`, taken now
+    pub fn new(author_pk: &str, page_id: &str, page: &WikiPage) -> Self {
+        Self {
+            author_pk: author_pk.to_string(),
+            page_id: page_id.to_string(),
+            version: page.meta.updated_at,
+            content_hash: page.content_hash(),
+            forked_at: now(),
+        }
+    }
+}
+
+/// A wiki page, as stored under `/pub/wiki.app/`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WikiPage {
+    pub meta: PageMeta,
+    /// Markdown content, without the front-matter
+    pub body: String,
+}
+
+impl WikiPage {
+    /// A new page, created now
+    pub fn new(body: String) -> Self {
+        let now = now();
+        let meta = PageMeta {
+            schema_version: SCHEMA_VERSION,
+            title: extract_title(&body).to_string(),
+            created_at: now,
+            updated_at: now,
+            ..Default::default()
+        };
+
+        Self { meta, body }
+    }
+
+    /// Parse a stored page.
+    ///
+    /// Plain markdown pages, or pages with unreadable front-matter, are read as body only and their
+    /// title is taken from the first line.
+    pub fn parse(raw: &str) -> Self {
+        if let Some((front_matter, body)) = split_front_matter(raw) {
+            match toml::from_str::<PageMeta>(front_matter) {
+                Ok(meta) => {
+                    return Self {
+                        meta,
+                        body: body.to_string(),
+                    }
+                }
+                Err(e) => log::warn!("Invalid front-matter, reading page as plain markdown: {e}"),
+            }
+        }
+
+        let meta = PageMeta {
+            title: extract_title(raw).to_string(),
+            ..Default::default()
+        };
+        Self {
+            meta,
+            body: raw.to_string(),
+        }
+    }
+
+    /// Serialize the page in the current format, front-matter followed by the markdown body
+    pub fn to_markdown(&self) -> Result<String> {
+        let front_matter = toml::to_string(&self.meta)?;
+
+        Ok(format!(
+            "{FRONT_MATTER_DELIMITER}\n{front_matter}{FRONT_MATTER_DELIMITER}\n{}",
+            self.body
+        ))
+    }
+
+    /// Hex-encoded SHA-256 of the body, identifying this version of the content
+    pub fn content_hash(&self) -> String {
+        Sha256::digest(self.body.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    pub fn title(&self) -> &str {
+        if self.meta.title.is_empty() {
+            extract_title(&self.body)
+        } else {
+            &self.meta.title
+        }
+    }
+
+    /// Replace the body and refresh the derived metadata.
+    ///
+    /// Pages read from the plain markdown format are upgraded to the current schema.
+    pub fn set_body(&mut self, body: String) {
+        let now = now();
+        if self.meta.created_at == 0 {
+            self.meta.created_at = now;
+        }
+        self.meta.updated_at = now;
+        self.meta.schema_version = SCHEMA_VERSION;
+        self.meta.title = extract_title(&body).to_string();
+        self.body = body;
+    }
+}
+
+/// Split a raw page into its front-matter and body, if it has front-matter
+fn split_front_matter(raw: &str) -> Option<(&str, &str)> {
+    let first_line = raw.lines().next()?;
+    if first_line.trim_end() != FRONT_MATTER_DELIMITER {
+        return None;
+    }
+
+    let rest = raw[first_line.len()..].trim_start_matches(['\r', '\n']);
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == FRONT_MATTER_DELIMITER {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+/// Parse a comma-separated list of tags, as typed in the editor
+pub fn parse_tags(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Current Unix timestamp, in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Human readable form of a Unix timestamp, in seconds
+pub fn format_timestamp(timestamp: u64) -> String {
+    if timestamp == 0 {
+        return "unknown".to_string();
+    }
+
+    let time = UNIX_EPOCH + Duration::from_secs(timestamp);
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+/// In this context, the title is the readable text on the 1st line
+pub fn extract_title(input: &str) -> &str {
+    // Get the first line by splitting on newlines and taking the first element
+    let first_line = input.lines().next().unwrap_or("");
+    first_line.trim_start_matches("# ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_matter_round_trips() {
+        let mut page = WikiPage::new("# Rust\n\nA systems language\n+++\nnot a delimiter".to_string());
+        page.meta.tags = vec!["language".to_string()];
+        page.meta.language = Some("en".to_string());
+        page.meta.forked_from = Some(ForkedFrom {
+            author_pk: "pk".to_string(),
+            page_id: "rust".to_string(),
+            version: 10,
+            content_hash: "hash".to_string(),
+            forked_at: 20,
+        });
+
+        let markdown = page.to_markdown().unwrap();
+        assert!(markdown.starts_with("+++\nschema_version = 1\n"));
+        assert_eq!(WikiPage::parse(&markdown), page);
+    }
+
+    #[test]
+    fn pages_without_front_matter_are_read_as_markdown() {
+        let page = WikiPage::parse("# Rust\n\nA systems language");
+
+        assert_eq!(page.meta.schema_version, 0);
+        assert_eq!(page.meta.updated_at, 0);
+        assert_eq!(page.title(), "Rust");
+        assert_eq!(page.body, "# Rust\n\nA systems language");
+    }
+
+    #[test]
+    fn malformed_front_matter_is_read_as_markdown() {
+        let raw = "+++\ntitle = \"Rust\ntags = 3\n+++\n# Rust";
+        let page = WikiPage::parse(raw);
+
+        // The title comes from the first line, as for plain markdown
+        assert_eq!(
+            page.meta,
+            PageMeta {
+                title: "+++".to_string(),
+                ..Default::default()
+            }
+        );
+        assert_eq!(page.body, raw);
+
+        // Never closed
+        let raw = "+++\ntitle = \"Rust\"\n# Rust";
+        assert_eq!(WikiPage::parse(raw).body, raw);
+    }
+
+    #[test]
+    fn missing_fields_of_the_front_matter_get_their_default() {
+        let page = WikiPage::parse("+++\ntitle = \"Rust\"\n+++\n# Rust");
+
+        assert_eq!(
+            page.meta,
+            PageMeta {
+                title: "Rust".to_string(),
+                ..Default::default()
+            }
+        );
+        assert_eq!(page.body, "# Rust");
+    }
+}
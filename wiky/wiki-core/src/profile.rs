@@ -0,0 +1,137 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use pubky::{AuthFlowKind, Capabilities, Pubky, PubkyAuthFlow, PubkyHttpClient, PubkySession};
+
+use crate::storage::is_unreachable;
+
+const PROFILE_DIR_NAME: &str = "pubky-wiki";
+const SESSION_FILE_NAME: &str = "session.sess";
+
+/// Local directory holding the state of the app that survives restarts
+pub fn profile_dir() -> Result<PathBuf> {
+    let dir = dirs::data_dir()
+        .ok_or_else(|| anyhow!("No data directory available on this system"))?
+        .join(PROFILE_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir)
+}
+
+fn session_file() -> Result<PathBuf> {
+    Ok(profile_dir()?.join(SESSION_FILE_NAME))
+}
+
+/// Store the session secret, so the next launch can skip the QR code
+pub fn save_session(session: &PubkySession) -> Result<()> {
+    write_secret_file(&session_file()?, &session.export_secret())
+}
+
+/// Write `secret` to `path`, readable and writable by the current user only
+fn write_secret_file(path: &Path, secret: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    // The mode only applies to new files, and a file left by an older version may be readable by others.
+    // It was just truncated, so the secret is written once the permissions are tightened.
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(secret.as_bytes())?;
+
+    Ok(())
+}
+
+/// Outcome of restoring the stored session
+pub enum RestoredSession {
+    Valid(Box<PubkySession>),
+    /// The homeserver could not be reached, so the session of `public_key` could not be validated
+    Unreachable { public_key: String },
+    /// There is no stored session, or it was revoked or expired
+    None,
+}
+
+/// Restore the stored session, if any, and validate it against the homeserver
+pub async fn restore_session(client: &PubkyHttpClient) -> RestoredSession {
+    let Ok(path) = session_file() else {
+        return RestoredSession::None;
+    };
+    if !path.exists() {
+        return RestoredSession::None;
+    }
+
+    match PubkySession::from_secret_file(&path, Some(client.clone())).await {
+        Ok(session) => RestoredSession::Valid(Box::new(session)),
+        Err(e) if is_unreachable(&e) => {
+            log::warn!("Homeserver is unreachable: {e}");
+            match stored_public_key(&path) {
+                Some(public_key) => RestoredSession::Unreachable { public_key },
+                None => RestoredSession::None,
+            }
+        }
+        Err(e) => {
+            log::warn!("Stored session could not be restored: {e}");
+            RestoredSession::None
+        }
+    }
+}
+
+/// Public key of the stored session, which is written as `<public key>:<secret>`
+fn stored_public_key(path: &Path) -> Option<String> {
+    let token = fs::read_to_string(path).ok()?;
+    let (public_key, _) = token.split_once(':')?;
+
+    Some(public_key.to_string())
+}
+
+/// Remove the stored session, so the next launch starts a new auth flow
+pub fn clear_session() -> Result<()> {
+    let path = session_file()?;
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+/// Start an auth flow granting write access to the wiki pages, and return it with its URL
+pub fn initialize_auth(pubky: &Pubky) -> Result<(PubkyAuthFlow, String)> {
+    let caps = Capabilities::builder().write("/pub/wiki.app/").finish();
+    let flow = pubky.start_auth_flow(&caps, AuthFlowKind::SignIn)?;
+    let auth_url = flow.authorization_url().to_string();
+
+    Ok((flow, auth_url))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn secret_files_are_only_readable_by_their_owner() {
+        let path = std::env::temp_dir().join(format!("pubky-wiki-{}.sess", uuid::Uuid::new_v4()));
+
+        write_secret_file(&path, "pk:first").unwrap();
+        assert_eq!(mode(&path), 0o600);
+
+        // A file created readable by others is tightened before the secret is written again
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_secret_file(&path, "pk:second").unwrap();
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "pk:second");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    page::{now, WikiPage},
+    profile,
+    storage::WikiStorage,
+};
+
+/// Folder holding the proposals a user made, relative to their homeserver
+const PROPOSALS_DIR: &str = "/pub/wiki.app/proposals/";
+
+/// Local list of the proposal URLs that were accepted or dismissed, one per line
+const HANDLED_PROPOSALS_FILE_NAME: &str = "handled_proposals";
+
+/// A request to the author of a page to take over the changes made in a fork of it.
+///
+/// Stored as TOML on the homeserver of the proposer, so the owner discovers it through their follows.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Proposal {
+    /// Author of the page the changes are proposed to
+    pub target_pk: String,
+    pub target_page_id: String,
+    /// Fork holding the proposed changes, on the homeserver of the proposer
+    pub fork_page_id: String,
+    /// `updated_at` of the proposed fork version, which is kept in the fork history
+    pub fork_version: u64,
+    /// `updated_at` of the target version the fork is based on
+    pub base_version: u64,
+    #[serde(default)]
+    pub message: String,
+    /// Unix timestamp, in seconds
+    pub created_at: u64,
+}
+
+impl Proposal {
+    /// A proposal for the current version of `fork`, taken now.
+    ///
+    /// Returns `None` if the page is not a fork, as there is nobody to propose the changes to.
+    pub fn new(fork_page_id: &str, fork: &WikiPage, message: &str) -> Option<Self> {
+        let forked_from = fork.meta.forked_from.as_ref()?;
+
+        Some(Self {
+            target_pk: forked_from.author_pk.clone(),
+            target_page_id: forked_from.page_id.clone(),
+            fork_page_id: fork_page_id.to_string(),
+            fork_version: fork.meta.updated_at,
+            base_version: forked_from.version,
+            message: message.trim().to_string(),
+            created_at: now(),
+        })
+    }
+}
+
+/// A proposal found on the homeserver of one of the follows
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReceivedProposal {
+    pub url: String,
+    pub author_pk: String,
+    pub proposal: Proposal,
+}
+
+/// Publish a proposal on the own homeserver, and return its path
+pub async fn save_proposal(storage: &impl WikiStorage, proposal: &Proposal) -> Result<String> {
+    let path = format!("{PROPOSALS_DIR}{}", Uuid::new_v4());
+    storage.put(&path, toml::to_string(proposal)?).await?;
+
+    log::info!("Saved proposal at path: {}", path);
+
+    Ok(path)
+}
+
+/// Proposals published by `author_pk` to pages of `target_pk`
+pub async fn list_proposals(
+    storage: &impl WikiStorage,
+    author_pk: &str,
+    target_pk: &str,
+) -> Result<Vec<ReceivedProposal>> {
+    let mut proposals = vec![];
+    for url in storage.list(author_pk, PROPOSALS_DIR).await? {
+        let name = url.split('/').next_back().unwrap_or(&url);
+        // One unreadable proposal doesn't hide the others
+        let file = match storage.get(author_pk, &format!("{PROPOSALS_DIR}{name}")).await {
+            Ok(file) => file,
+            Err(e) => {
+                log::warn!("Ignoring unavailable proposal {url}: {e}");
+                continue;
+            }
+        };
+        match toml::from_str::<Proposal>(&file.content) {
+            Ok(proposal) if proposal.target_pk == target_pk => proposals.push(ReceivedProposal {
+                url,
+                author_pk: author_pk.to_string(),
+                proposal,
+            }),
+            Ok(_) => {}
+            Err(e) => log::warn!("Ignoring invalid proposal {url}: {e}"),
+        }
+    }
+
+    Ok(proposals)
+}
+
+fn handled_proposals_file() -> Result<PathBuf> {
+    Ok(profile::profile_dir()?.join(HANDLED_PROPOSALS_FILE_NAME))
+}
+
+/// URLs of the proposals that were accepted or dismissed, which are no longer shown in the inbox
+pub fn handled_proposals() -> Result<HashSet<String>> {
+    read_handled_proposals(&handled_proposals_file()?)
+}
+
+/// Remember that a proposal was accepted or dismissed
+pub fn mark_proposal_handled(url: &str) -> Result<()> {
+    add_handled_proposal(&handled_proposals_file()?, url)
+}
+
+fn read_handled_proposals(path: &Path) -> Result<HashSet<String>> {
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+
+    Ok(fs::read_to_string(path)?.lines().map(str::to_string).collect())
+}
+
+fn add_handled_proposal(path: &Path, url: &str) -> Result<()> {
+    let mut handled = read_handled_proposals(path)?;
+    handled.insert(url.to_string());
+
+    let mut urls: Vec<String> = handled.into_iter().collect();
+    urls.sort();
+    fs::write(path, urls.join("\n"))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{memory::MemoryStorage, page::ForkedFrom};
+
+    const ALICE: &str = "alice";
+    const BOB: &str = "bob";
+
+    /// A proposal of a fork of a page of `target_pk`
+    fn proposal(target_pk: &str, message: &str) -> Proposal {
+        let upstream = WikiPage::new("# Rust".to_string());
+        let mut fork = WikiPage::new("# Rust\n\nA language".to_string());
+        fork.meta.forked_from = Some(ForkedFrom::new(target_pk, "rust", &upstream));
+
+        Proposal::new("rust", &fork, message).unwrap()
+    }
+
+    #[tokio::test]
+    async fn sent_proposals_are_listed_for_their_target() {
+        let bob = MemoryStorage::new(BOB);
+        let for_alice = proposal(ALICE, "  Explain what it is  ");
+        assert_eq!(for_alice.message, "Explain what it is");
+        let path = save_proposal(&bob, &for_alice).await.unwrap();
+        save_proposal(&bob, &proposal("carol", "")).await.unwrap();
+
+        let alice = bob.as_user(ALICE);
+        let received = list_proposals(&alice, BOB, ALICE).await.unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].author_pk, BOB);
+        assert_eq!(received[0].proposal, for_alice);
+        assert!(received[0].url.ends_with(&path));
+
+        assert_eq!(list_proposals(&alice, ALICE, ALICE).await.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn invalid_proposals_are_skipped() {
+        let bob = MemoryStorage::new(BOB);
+        bob.put(&format!("{PROPOSALS_DIR}broken"), "not = [toml".to_string()).await.unwrap();
+        save_proposal(&bob, &proposal(ALICE, "")).await.unwrap();
+
+        let received = list_proposals(&bob.as_user(ALICE), BOB, ALICE).await.unwrap();
+        assert_eq!(received.len(), 1);
+    }
+
+    #[test]
+    fn dismissed_proposals_are_remembered() {
+        let path = std::env::temp_dir().join(format!("pubky-wiki-{}-handled", Uuid::new_v4()));
+        assert_eq!(read_handled_proposals(&path).unwrap(), HashSet::new());
+
+        add_handled_proposal(&path, "pubky://bob/pub/wiki.app/proposals/2").unwrap();
+        add_handled_proposal(&path, "pubky://bob/pub/wiki.app/proposals/1").unwrap();
+        add_handled_proposal(&path, "pubky://bob/pub/wiki.app/proposals/2").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "pubky://bob/pub/wiki.app/proposals/1\npubky://bob/pub/wiki.app/proposals/2"
+        );
+        assert!(read_handled_proposals(&path).unwrap().contains("pubky://bob/pub/wiki.app/proposals/1"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use crate::{links::same_title, page::WikiPage};
+
+/// Matches in the title count this many times more than matches in the body
+const TITLE_WEIGHT: f64 = 3.0;
+
+/// Longest snippet shown under a search result, in characters
+const SNIPPET_LENGTH: usize = 120;
+
+/// Identifies a page by its author and page ID
+type PageKey = (String, String);
+
+/// Number of occurrences of a term in a page
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct TermCount {
+    title: u32,
+    body: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct IndexedPage {
+    title: String,
+    body: String,
+    /// Distinct terms of the page, to remove it from the index
+    terms: Vec<String>,
+}
+
+/// In-memory full-text index over the titles and bodies of wiki pages
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchIndex {
+    pages: HashMap<PageKey, IndexedPage>,
+    /// Inverted index, mapping each term to the pages containing it
+    terms: HashMap<String, HashMap<PageKey, TermCount>>,
+}
+
+/// A page matching a search query
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub user_pk: String,
+    pub page_id: String,
+    pub title: String,
+    /// Line of the body with the first match, if the body matches
+    pub snippet: Option<String>,
+    pub score: f64,
+}
+
+impl SearchIndex {
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Add a page to the index, replacing the previous version of it
+    pub fn insert(&mut self, user_pk: &str, page_id: &str, page: &WikiPage) {
+        self.remove(user_pk, page_id);
+
+        let key = (user_pk.to_string(), page_id.to_string());
+        let mut counts: HashMap<String, TermCount> = HashMap::new();
+        for term in tokenize(page.title()) {
+            counts.entry(term).or_default().title += 1;
+        }
+        for term in tokenize(&page.body) {
+            counts.entry(term).or_default().body += 1;
+        }
+
+        let terms = counts.keys().cloned().collect();
+        for (term, count) in counts {
+            self.terms.entry(term).or_default().insert(key.clone(), count);
+        }
+        self.pages.insert(
+            key,
+            IndexedPage {
+                title: page.title().to_string(),
+                body: page.body.clone(),
+                terms,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, user_pk: &str, page_id: &str) {
+        let key = (user_pk.to_string(), page_id.to_string());
+        let Some(page) = self.pages.remove(&key) else {
+            return;
+        };
+
+        for term in page.terms {
+            if let Some(postings) = self.terms.get_mut(&term) {
+                postings.remove(&key);
+                if postings.is_empty() {
+                    self.terms.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// ID of the indexed page of `user_pk` with this title
+    pub fn find_title(&self, user_pk: &str, title: &str) -> Option<String> {
+        self.pages
+            .iter()
+            .find(|((pk, _), page)| pk == user_pk && same_title(&page.title, title))
+            .map(|((_, page_id), _)| page_id.clone())
+    }
+
+    /// Pages matching all words of `query`, best matches first.
+    ///
+    /// The last word also matches longer terms starting with it, so results show up while typing.
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        let words = tokenize(query);
+        let Some((last, others)) = words.split_last() else {
+            return vec![];
+        };
+
+        let page_count = self.pages.len() as f64;
+        let mut scores: HashMap<&PageKey, (f64, usize)> = HashMap::new();
+
+        for (i, word) in others.iter().chain([last]).enumerate() {
+            let is_last = i == others.len();
+            let mut word_scores: HashMap<&PageKey, f64> = HashMap::new();
+
+            for (term, postings) in &self.terms {
+                let matches = if is_last {
+                    term.starts_with(word.as_str())
+                } else {
+                    term == word
+                };
+                if !matches {
+                    continue;
+                }
+
+                // Rare terms weigh more, and repeated occurrences weigh less and less
+                let idf = (1.0 + page_count / postings.len() as f64).ln();
+                for (key, count) in postings {
+                    let tf = TITLE_WEIGHT * saturate(count.title) + saturate(count.body);
+                    *word_scores.entry(key).or_default() += idf * tf;
+                }
+            }
+
+            for (key, score) in word_scores {
+                let entry = scores.entry(key).or_default();
+                entry.0 += score;
+                entry.1 += 1;
+            }
+        }
+
+        let mut hits: Vec<SearchHit> = scores
+            .into_iter()
+            .filter(|(_, (_, matched_words))| *matched_words == words.len())
+            .map(|(key, (score, _))| {
+                let page = &self.pages[key];
+                SearchHit {
+                    user_pk: key.0.clone(),
+                    page_id: key.1.clone(),
+                    title: page.title.clone(),
+                    snippet: snippet(&page.body, &words),
+                    score,
+                }
+            })
+            .collect();
+        // Ties are ordered by title, then by author and page, so the order doesn't change between searches
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.title.cmp(&b.title))
+                .then_with(|| (&a.user_pk, &a.page_id).cmp(&(&b.user_pk, &b.page_id)))
+        });
+
+        hits
+    }
+}
+
+/// Lowercase words of `text`, split on anything that is not a letter or a digit
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn saturate(count: u32) -> f64 {
+    let count = f64::from(count);
+    count / (count + 1.0)
+}
+
+/// First line of `body` containing one of `words`, shortened to fit under a result
+fn snippet(body: &str, words: &[String]) -> Option<String> {
+    let line = body.lines().find(|line| {
+        let line = line.to_lowercase();
+        words.iter().any(|word| line.contains(word.as_str()))
+    })?;
+
+    let line = line.trim();
+    if line.chars().count() <= SNIPPET_LENGTH {
+        return Some(line.to_string());
+    }
+    Some(format!("{}…", line.chars().take(SNIPPET_LENGTH).collect::<String>()))
+}
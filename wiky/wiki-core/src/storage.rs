@@ -0,0 +1,282 @@
+use std::{fmt, future::Future};
+
+use anyhow::Result;
+use pubky::{errors::RequestError, PubkySession, PublicStorage, StatusCode};
+use uuid::Uuid;
+
+use crate::page::WikiPage;
+
+/// Files of the homeservers, as seen by the signed in user: writes go to their own homeserver,
+/// reads can target any user.
+///
+/// Paths are absolute, like `/pub/wiki.app/{page_id}`.
+pub trait WikiStorage: Clone + Send + Sync + 'static {
+    /// Public key of the signed in user
+    fn public_key(&self) -> String;
+
+    /// Create or overwrite a file of the signed in user
+    fn put(&self, path: &str, content: String) -> impl Future<Output = Result<()>> + Send;
+
+    /// Create or overwrite a binary file of the signed in user, like an attachment
+    fn put_bytes(&self, path: &str, content: Vec<u8>) -> impl Future<Output = Result<()>> + Send;
+
+    /// Delete a file of the signed in user
+    fn delete(&self, path: &str) -> impl Future<Output = Result<()>> + Send;
+
+    /// Read a file of any user
+    fn get(&self, user_pk: &str, path: &str) -> impl Future<Output = Result<StoredFile>> + Send;
+
+    /// Read a binary file of any user
+    fn get_bytes(&self, user_pk: &str, path: &str) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    /// ETag of a file of any user, without reading it. `None` if the file doesn't exist.
+    fn etag(&self, user_pk: &str, path: &str) -> impl Future<Output = Result<Option<String>>> + Send;
+
+    /// Whether a file of any user exists, without reading it
+    fn exists(&self, user_pk: &str, path: &str) -> impl Future<Output = Result<bool>> + Send;
+
+    /// `pubky://` URLs of the files in a folder of any user, without descending into sub-folders
+    fn list(&self, user_pk: &str, folder: &str) -> impl Future<Output = Result<Vec<String>>> + Send;
+}
+
+/// Content of a file, with the ETag of the version read, if the homeserver gave one
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredFile {
+    pub content: String,
+    pub etag: Option<String>,
+}
+
+/// Storage failures the callers react to, other failures are passed on as they are
+#[derive(Debug)]
+pub enum StorageError {
+    NotFound(String),
+    /// The homeserver could not be reached at all, as opposed to answering with an error
+    Unreachable(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::NotFound(url) => write!(f, "{url} not found"),
+            StorageError::Unreachable(reason) => write!(f, "Homeserver is unreachable: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl StorageError {
+    pub fn is_not_found(error: &anyhow::Error) -> bool {
+        matches!(error.downcast_ref(), Some(StorageError::NotFound(_)))
+    }
+
+    pub fn is_unreachable(error: &anyhow::Error) -> bool {
+        matches!(error.downcast_ref(), Some(StorageError::Unreachable(_)))
+    }
+}
+
+/// [`WikiStorage`] backed by the homeservers, through the session of the signed in user
+#[derive(Clone)]
+pub struct PubkyStorage {
+    session: PubkySession,
+    pub_storage: PublicStorage,
+}
+
+impl PubkyStorage {
+    pub fn new(session: PubkySession, pub_storage: PublicStorage) -> Self {
+        Self {
+            session,
+            pub_storage,
+        }
+    }
+
+    pub fn session(&self) -> &PubkySession {
+        &self.session
+    }
+
+    pub fn into_session(self) -> PubkySession {
+        self.session
+    }
+}
+
+impl WikiStorage for PubkyStorage {
+    fn public_key(&self) -> String {
+        self.session.info().public_key().to_string()
+    }
+
+    async fn put(&self, path: &str, content: String) -> Result<()> {
+        self.session
+            .storage()
+            .put(path, content)
+            .await
+            .map_err(|e| storage_error(path, e))?;
+
+        Ok(())
+    }
+
+    async fn put_bytes(&self, path: &str, content: Vec<u8>) -> Result<()> {
+        self.session
+            .storage()
+            .put(path, content)
+            .await
+            .map_err(|e| storage_error(path, e))?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, path: &str) -> Result<()> {
+        self.session
+            .storage()
+            .delete(path)
+            .await
+            .map_err(|e| storage_error(path, e))?;
+
+        Ok(())
+    }
+
+    async fn get(&self, user_pk: &str, path: &str) -> Result<StoredFile> {
+        let url = format!("pubky://{user_pk}{path}");
+        let response = self
+            .pub_storage
+            .get(&url)
+            .await
+            .map_err(|e| storage_error(&url, e))?;
+
+        let etag = response
+            .headers()
+            .get("etag")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let content = response.text().await?;
+
+        Ok(StoredFile { content, etag })
+    }
+
+    async fn get_bytes(&self, user_pk: &str, path: &str) -> Result<Vec<u8>> {
+        let url = format!("pubky://{user_pk}{path}");
+        let response = self
+            .pub_storage
+            .get(&url)
+            .await
+            .map_err(|e| storage_error(&url, e))?;
+
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    async fn etag(&self, user_pk: &str, path: &str) -> Result<Option<String>> {
+        let url = format!("pubky://{user_pk}{path}");
+        let stats = self
+            .pub_storage
+            .stats(&url)
+            .await
+            .map_err(|e| storage_error(&url, e))?;
+
+        Ok(stats.and_then(|stats| stats.etag))
+    }
+
+    async fn exists(&self, user_pk: &str, path: &str) -> Result<bool> {
+        let url = format!("pubky://{user_pk}{path}");
+
+        self.pub_storage.exists(&url).await.map_err(|e| storage_error(&url, e))
+    }
+
+    async fn list(&self, user_pk: &str, folder: &str) -> Result<Vec<String>> {
+        let url = format!("pubky://{user_pk}{folder}");
+        log::info!("listing {url}");
+
+        let entries = self
+            .pub_storage
+            .list(&url)?
+            .shallow(true)
+            .send()
+            .await
+            .map_err(|e| storage_error(&url, e))?;
+
+        let mut result_list = vec![];
+        for entry in entries {
+            // Sub-folders are listed with a trailing slash
+            if entry.path.as_str().ends_with('/') {
+                continue;
+            }
+            result_list.push(entry.to_pubky_url());
+        }
+
+        Ok(result_list)
+    }
+}
+
+/// Tell apart the pubky errors the callers react to
+fn storage_error(url: &str, error: pubky::Error) -> anyhow::Error {
+    if is_not_found(&error) {
+        StorageError::NotFound(url.to_string()).into()
+    } else if is_unreachable(&error) {
+        StorageError::Unreachable(error.to_string()).into()
+    } else {
+        error.into()
+    }
+}
+
+/// A fresh ID for a new page
+pub fn new_page_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+pub async fn create_wiki_post(
+    storage: &impl WikiStorage,
+    page: &WikiPage,
+    filename: Option<&str>,
+) -> Result<String> {
+    let path = if let Some(fname) = filename {
+        format!("/pub/wiki.app/{}", fname)
+    } else {
+        format!("/pub/wiki.app/{}", new_page_id())
+    };
+
+    // Create the post with the provided content
+    storage.put(&path, page.to_markdown()?).await?;
+
+    log::info!("Created post at path: {}", path);
+
+    Ok(path)
+}
+
+pub async fn update_wiki_post(
+    storage: &impl WikiStorage,
+    page_id: &str,
+    page: &WikiPage,
+) -> Result<()> {
+    let path = format!("/pub/wiki.app/{}", page_id);
+
+    // Update the post with the provided content
+    storage.put(&path, page.to_markdown()?).await?;
+
+    log::info!("Updated post at path: {}", path);
+
+    Ok(())
+}
+
+pub async fn delete_wiki_post(storage: &impl WikiStorage, page_id: &str) -> Result<()> {
+    let path = format!("/pub/wiki.app/{}", page_id);
+
+    // Delete the post
+    storage.delete(&path).await?;
+
+    log::info!("Deleted post at path: {}", path);
+
+    Ok(())
+}
+
+/// Whether the homeserver could not be reached at all, as opposed to answering with an error
+pub fn is_unreachable(error: &pubky::Error) -> bool {
+    matches!(
+        error,
+        pubky::Error::Request(RequestError::Transport(_)) | pubky::Error::Pkarr(_)
+    )
+}
+
+pub fn is_not_found(error: &pubky::Error) -> bool {
+    matches!(
+        error,
+        pubky::Error::Request(RequestError::Server { status, .. }) if *status == StatusCode::NOT_FOUND
+    )
+}
@@ -0,0 +1,52 @@
+use wiki_core::{
+    attachment::{attachment_name, markdown_reference, MAX_ATTACHMENT_SIZE},
+    cache::PageCache,
+    memory::MemoryStorage,
+    page::WikiPage,
+    WikiClient,
+};
+
+const ALICE: &str = "alice";
+const BOB: &str = "bob";
+
+fn client(storage: &MemoryStorage) -> WikiClient<MemoryStorage> {
+    WikiClient::with_storage(storage.clone(), PageCache::in_memory().unwrap())
+}
+
+#[tokio::test]
+async fn attachments_are_uploaded_next_to_the_pages_and_fetched_by_others() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+    let bob = client(&storage.as_user(BOB));
+    let image = vec![0x89, b'P', b'N', b'G', 0xff, 0x00];
+
+    let url = alice.upload_attachment("Rust logo.png", image.clone()).await.unwrap();
+
+    assert!(url.starts_with("pubky://alice/pub/wiki.app/files/"));
+    assert_eq!(attachment_name(&url), "Rust_logo.png");
+    assert_eq!(bob.fetch_attachment(&url).await.unwrap(), image);
+
+    // Attachments are in a sub-folder, so they are not taken for pages
+    alice.create_page(&WikiPage::new(format!("# Rust\n\n![logo]({url})")), None).await.unwrap();
+    assert_eq!(alice.list_pages(ALICE).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn oversized_files_are_refused() {
+    let alice = client(&MemoryStorage::new(ALICE));
+
+    let error = alice
+        .upload_attachment("video.mp4", vec![0; MAX_ATTACHMENT_SIZE + 1])
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.to_string(), "Attachments can't be larger than 10 MB");
+}
+
+#[test]
+fn images_are_shown_inline_and_other_files_linked() {
+    let url = "pubky://alice/pub/wiki.app/files/id-file";
+
+    assert_eq!(markdown_reference(url, "logo.PNG"), format!("![logo.PNG]({url})"));
+    assert_eq!(markdown_reference(url, "notes [draft].pdf"), format!("[notes \\[draft\\].pdf]({url})"));
+}
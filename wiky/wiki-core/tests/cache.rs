@@ -0,0 +1,61 @@
+use wiki_core::{
+    cache::{CachedFile, Draft, PageCache},
+    history::Revision,
+    page::WikiPage,
+};
+
+fn draft(id: &str, body: &str, saved_at: u64) -> Draft {
+    let mut page = WikiPage::new(body.to_string());
+    page.meta.tags = vec!["lang".to_string()];
+    Draft {
+        id: id.to_string(),
+        page_id: Some("rust".to_string()),
+        base_version: Some(Revision {
+            timestamp: 1,
+            content_hash: "abc".to_string(),
+        }),
+        page,
+        saved_at,
+    }
+}
+
+#[test]
+fn drafts_are_kept_per_user_newest_first() {
+    let cache = PageCache::in_memory().unwrap();
+    cache.save_draft("alice", &draft("old", "# Rust", 1)).unwrap();
+    cache.save_draft("alice", &draft("new", "# Go", 2)).unwrap();
+    cache.save_draft("bob", &draft("other", "# Zig", 3)).unwrap();
+
+    // Saving again replaces the previous version
+    let updated = draft("old", "# Rust\n\nMore", 3);
+    cache.save_draft("alice", &updated).unwrap();
+    assert_eq!(cache.drafts("alice").unwrap(), vec![updated, draft("new", "# Go", 2)]);
+
+    cache.discard_draft("alice", "old").unwrap();
+    assert_eq!(cache.drafts("alice").unwrap(), vec![draft("new", "# Go", 2)]);
+    assert_eq!(cache.drafts("bob").unwrap().len(), 1);
+}
+
+fn file(size: usize, etag: &str) -> CachedFile {
+    CachedFile {
+        content: vec![0; size],
+        mime: Some("image/png".to_string()),
+        etag: Some(etag.to_string()),
+    }
+}
+
+#[test]
+fn files_are_kept_until_they_take_too_much_room() {
+    let cache = PageCache::in_memory().unwrap();
+    cache.put_file("pubky://alice/pub/wiki.app/files/old.png", &file(60, "\"1\"")).unwrap();
+    cache.put_file("pubky://alice/pub/wiki.app/files/new.png", &file(60, "\"2\"")).unwrap();
+    assert_eq!(
+        cache.get_file("pubky://alice/pub/wiki.app/files/new.png").unwrap(),
+        Some(file(60, "\"2\""))
+    );
+
+    // The least recently fetched files go first
+    cache.prune_files(100).unwrap();
+    assert_eq!(cache.get_file("pubky://alice/pub/wiki.app/files/old.png").unwrap(), None);
+    assert!(cache.get_file("pubky://alice/pub/wiki.app/files/new.png").unwrap().is_some());
+}
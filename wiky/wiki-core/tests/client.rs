@@ -0,0 +1,404 @@
+use wiki_core::{
+    cache::{PageCache, PendingWrite},
+    client::{SyncReport, UpdateConflict},
+    follow::{Follow, PROFILE_PATH},
+    history::Revision,
+    links::WikiLink,
+    memory::MemoryStorage,
+    page::WikiPage,
+    storage::{StorageError, WikiStorage},
+    WikiClient,
+};
+
+const ALICE: &str = "alice";
+const BOB: &str = "bob";
+const CAROL: &str = "carol";
+
+fn client(storage: &MemoryStorage) -> WikiClient<MemoryStorage> {
+    WikiClient::with_storage(storage.clone(), PageCache::in_memory().unwrap())
+}
+
+/// Create a page and return its ID
+async fn create(client: &WikiClient<MemoryStorage>, body: &str) -> String {
+    let path = client.create_page(&WikiPage::new(body.to_string()), None).await.unwrap();
+
+    path.strip_prefix("/pub/wiki.app/").unwrap().to_string()
+}
+
+/// A new version of `page`
+fn edited(page: &WikiPage, body: &str) -> WikiPage {
+    let mut page = page.clone();
+    page.set_body(body.to_string());
+
+    page
+}
+
+#[tokio::test]
+async fn created_page_is_listed_and_fetched() {
+    let alice = client(&MemoryStorage::new(ALICE));
+
+    let page_id = create(&alice, "# Rust\n\nA language").await;
+
+    assert_eq!(alice.list_pages(ALICE).await.unwrap(), vec![page_id.clone()]);
+    let page = alice.fetch_page(ALICE, &page_id).await.unwrap();
+    assert_eq!(page.title(), "Rust");
+    assert_eq!(page.body, "# Rust\n\nA language");
+    assert_eq!(alice.list_revisions(ALICE, &page_id).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn missing_pages_are_told_apart_from_other_failures() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+
+    let error = alice.fetch_page(ALICE, "missing").await.unwrap_err();
+    assert!(StorageError::is_not_found(&error));
+    assert!(error.to_string().starts_with("Error fetching path /pub/wiki.app/missing"));
+
+    storage.set_reachable(false);
+    let error = alice.fetch_page(ALICE, "missing").await.unwrap_err();
+    assert!(!StorageError::is_not_found(&error));
+}
+
+#[tokio::test]
+async fn edited_page_keeps_its_history() {
+    let alice = client(&MemoryStorage::new(ALICE));
+    let page_id = create(&alice, "# Rust\n\nA language").await;
+    let original = alice.fetch_page(ALICE, &page_id).await.unwrap();
+
+    let update = edited(&original, "# Rust\n\nA systems language");
+    alice.update_page(&page_id, &update, None).await.unwrap();
+
+    let page = alice.fetch_page(ALICE, &page_id).await.unwrap();
+    assert_eq!(page.body, "# Rust\n\nA systems language");
+
+    let revisions = alice.list_revisions(ALICE, &page_id).await.unwrap();
+    assert_eq!(revisions.len(), 2);
+    assert!(revisions.contains(&Revision::of(&update)));
+    let first = alice.fetch_revision(ALICE, &page_id, &Revision::of(&original)).await.unwrap();
+    assert_eq!(first.body, "# Rust\n\nA language");
+}
+
+#[tokio::test]
+async fn edits_saved_within_the_same_second_are_all_kept() {
+    let alice = client(&MemoryStorage::new(ALICE));
+    let page_id = create(&alice, "# Rust\n\nA language").await;
+    let original = alice.fetch_page(ALICE, &page_id).await.unwrap();
+
+    let mut first = original.clone();
+    first.set_body("# Rust\n\nA systems language".to_string());
+    first.meta.updated_at = original.meta.updated_at;
+    let mut second = first.clone();
+    second.set_body("# Rust\n\nA fast systems language".to_string());
+    second.meta.updated_at = original.meta.updated_at;
+    alice.update_page(&page_id, &first, None).await.unwrap();
+    alice.update_page(&page_id, &second, None).await.unwrap();
+
+    let revisions = alice.list_revisions(ALICE, &page_id).await.unwrap();
+    assert_eq!(revisions.len(), 3);
+    assert!(revisions.iter().all(|revision| revision.timestamp == original.meta.updated_at));
+    for page in [&original, &first, &second] {
+        let revision = alice.fetch_revision(ALICE, &page_id, &Revision::of(page)).await.unwrap();
+        assert_eq!(revision.body, page.body);
+    }
+}
+
+#[tokio::test]
+async fn revisions_saved_under_their_timestamp_alone_are_still_found() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+    let page = WikiPage::new("# Rust\n\nA language".to_string());
+    let path = format!("/pub/wiki.app/revisions/rust/{}", page.meta.updated_at);
+    storage.put(&path, page.to_markdown().unwrap()).await.unwrap();
+
+    let revisions = alice.list_revisions(ALICE, "rust").await.unwrap();
+    assert_eq!(revisions, vec![Revision::at(page.meta.updated_at)]);
+    let revision = alice.fetch_revision(ALICE, "rust", &Revision::of(&page)).await.unwrap();
+    assert_eq!(revision.body, page.body);
+}
+
+#[tokio::test]
+async fn concurrent_edits_are_merged_instead_of_overwritten() {
+    let storage = MemoryStorage::new(ALICE);
+    let laptop = client(&storage);
+    let desktop = client(&storage);
+    let page_id = create(&laptop, "# Rust\n\nA language\n\nBy Mozilla").await;
+    let base = laptop.fetch_page(ALICE, &page_id).await.unwrap();
+    let version = Some(Revision::of(&base));
+
+    let theirs = edited(&base, "# Rust\n\nA systems language\n\nBy Mozilla");
+    desktop.update_page(&page_id, &theirs, version.clone()).await.unwrap();
+
+    let mine = edited(&base, "# Rust\n\nA language\n\nBy the Rust Foundation");
+    let error = laptop.update_page(&page_id, &mine, version).await.unwrap_err();
+    let conflict = error.downcast::<UpdateConflict>().unwrap();
+    assert_eq!(conflict.current.body, theirs.body);
+    assert_eq!(laptop.fetch_page(ALICE, &page_id).await.unwrap().body, theirs.body);
+
+    let merge = laptop.prepare_conflict_merge(&page_id, &mine, conflict).await;
+    assert_eq!(merge.result.conflicts, 0);
+    assert_eq!(merge.result.text, "# Rust\n\nA systems language\n\nBy the Rust Foundation");
+}
+
+#[tokio::test]
+async fn deleted_page_is_gone_with_its_history() {
+    let alice = client(&MemoryStorage::new(ALICE));
+    let page_id = create(&alice, "# Rust").await;
+
+    alice.delete_page(&page_id).await.unwrap();
+
+    assert!(alice.list_pages(ALICE).await.unwrap().is_empty());
+    assert!(alice.list_revisions(ALICE, &page_id).await.unwrap().is_empty());
+    assert!(alice.fetch_page(ALICE, &page_id).await.is_err());
+    assert_eq!(alice.cache().get(ALICE, &page_id).unwrap(), None);
+}
+
+#[tokio::test]
+async fn pages_are_not_written_outside_of_the_page_files() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+    let page_id = create(&alice, "# Rust").await;
+    let page = WikiPage::new("# Go".to_string());
+
+    for page_id in ["", "foo/bar", "revisions", "proposals", "files"] {
+        assert!(alice.create_page(&page, Some(page_id)).await.is_err(), "{page_id:?}");
+        assert!(alice.update_page(page_id, &page, None).await.is_err(), "{page_id:?}");
+        assert!(alice.delete_page(page_id).await.is_err(), "{page_id:?}");
+    }
+
+    assert_eq!(alice.list_pages(ALICE).await.unwrap(), vec![page_id.clone()]);
+    assert_eq!(alice.list_revisions(ALICE, &page_id).await.unwrap().len(), 1);
+    assert!(storage.list(ALICE, "/pub/wiki.app/foo/").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn fork_copies_the_page_and_records_its_origin() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+    let bob = client(&storage.as_user(BOB));
+    let page_id = create(&alice, "# Rust\n\nA language").await;
+
+    let fork = bob.fork_page(ALICE, &page_id).await.unwrap();
+
+    assert_eq!(bob.list_pages(BOB).await.unwrap(), vec![page_id.clone()]);
+    assert_eq!(bob.fetch_page(BOB, &page_id).await.unwrap(), fork);
+
+    let upstream = alice.fetch_page(ALICE, &page_id).await.unwrap();
+    let forked_from = fork.meta.forked_from.unwrap();
+    assert_eq!(forked_from.author_pk, ALICE);
+    assert_eq!(forked_from.page_id, page_id);
+    assert_eq!(forked_from.version, upstream.meta.updated_at);
+    assert_eq!(forked_from.content_hash, upstream.content_hash());
+}
+
+#[tokio::test]
+async fn forks_are_discovered_among_the_followed_users() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+    let page_id = create(&alice, "# Rust").await;
+
+    client(&storage.as_user(BOB)).fork_page(ALICE, &page_id).await.unwrap();
+    client(&storage.as_user(CAROL)).fork_page(ALICE, &page_id).await.unwrap();
+    storage.follow(BOB).unwrap();
+    // Followed, without a version of the page
+    storage.follow("dave").unwrap();
+
+    assert_eq!(alice.follows().await, vec![BOB.to_string(), "dave".to_string()]);
+    assert_eq!(
+        alice.discover_forks(&page_id).await,
+        vec![WikiLink::page_url(ALICE, &page_id), WikiLink::page_url(BOB, &page_id)]
+    );
+}
+
+#[tokio::test]
+async fn upstream_changes_are_merged_into_a_fork() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+    let bob = client(&storage.as_user(BOB));
+    let page_id = create(&alice, "# Rust\n\nA language\n\nBy Mozilla").await;
+    let fork = bob.fork_page(ALICE, &page_id).await.unwrap();
+
+    let upstream = alice.fetch_page(ALICE, &page_id).await.unwrap();
+    let upstream = edited(&upstream, "# Rust\n\nA systems language\n\nBy Mozilla");
+    alice.update_page(&page_id, &upstream, None).await.unwrap();
+    let mine = edited(&fork, "# Rust\n\nA language\n\nBy the Rust Foundation");
+    bob.update_page(&page_id, &mine, None).await.unwrap();
+
+    let merge = bob.prepare_merge(&page_id, &mine).await.unwrap();
+
+    assert_eq!(merge.result.conflicts, 0);
+    assert_eq!(merge.result.text, "# Rust\n\nA systems language\n\nBy the Rust Foundation");
+}
+
+#[tokio::test]
+async fn changed_pages_are_refetched() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+    let bob = client(&storage.as_user(BOB));
+    let page_id = create(&bob, "# Rust").await;
+    assert_eq!(alice.fetch_page(BOB, &page_id).await.unwrap().body, "# Rust");
+
+    let page = bob.fetch_page(BOB, &page_id).await.unwrap();
+    bob.update_page(&page_id, &edited(&page, "# Rust\n\nA language"), None).await.unwrap();
+
+    assert_eq!(alice.fetch_page(BOB, &page_id).await.unwrap().body, "# Rust\n\nA language");
+}
+
+#[tokio::test]
+async fn cached_pages_are_served_while_unreachable() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+    let bob = client(&storage.as_user(BOB));
+    let cached_id = create(&bob, "# Cached").await;
+    let uncached_id = create(&bob, "# Uncached").await;
+    alice.fetch_page(BOB, &cached_id).await.unwrap();
+
+    storage.set_reachable(false);
+
+    assert_eq!(alice.fetch_page(BOB, &cached_id).await.unwrap().title(), "Cached");
+    assert!(alice.fetch_page(BOB, &uncached_id).await.is_err());
+}
+
+#[tokio::test]
+async fn offline_writes_are_synced() {
+    let alice = client(&MemoryStorage::new(ALICE));
+    let page = WikiPage::new("# Written offline".to_string());
+    let write = PendingWrite::Create {
+        page_id: "offline".to_string(),
+        page: page.clone(),
+    };
+    alice.cache().queue(ALICE, &write).unwrap();
+
+    assert_eq!(alice.sync_pending_writes().await.unwrap().synced, 1);
+
+    assert_eq!(alice.list_pages(ALICE).await.unwrap(), vec!["offline".to_string()]);
+    assert!(alice.cache().pending(ALICE).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn refused_offline_writes_do_not_hold_up_the_queue() {
+    let alice = client(&MemoryStorage::new(ALICE));
+    // The page was deleted elsewhere, so the update can't be checked against its base
+    let gone = WikiPage::new("# Gone".to_string());
+    let update = PendingWrite::Update {
+        page_id: "gone".to_string(),
+        page: gone.clone(),
+        base_version: Some(Revision::of(&gone)),
+    };
+    let create = PendingWrite::Create {
+        page_id: "offline".to_string(),
+        page: WikiPage::new("# Written offline".to_string()),
+    };
+    alice.cache().queue(ALICE, &update).unwrap();
+    alice.cache().queue(ALICE, &create).unwrap();
+
+    let report = alice.sync_pending_writes().await.unwrap();
+
+    assert_eq!(report.synced, 1);
+    assert_eq!(report.failed.len(), 1);
+    assert!(report.failed[0].starts_with("gone: "));
+    assert_eq!(alice.list_pages(ALICE).await.unwrap(), vec!["offline".to_string()]);
+    assert!(alice.cache().pending(ALICE).unwrap().is_empty());
+    let drafts = alice.cache().drafts(ALICE).unwrap();
+    assert_eq!(drafts.len(), 1);
+    assert_eq!(drafts[0].page.body, gone.body);
+}
+
+#[tokio::test]
+async fn offline_writes_stay_queued_while_unreachable() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+    let write = PendingWrite::Create {
+        page_id: "offline".to_string(),
+        page: WikiPage::new("# Written offline".to_string()),
+    };
+    alice.cache().queue(ALICE, &write).unwrap();
+
+    storage.set_reachable(false);
+    let report = alice.sync_pending_writes().await.unwrap();
+    assert_eq!(report, SyncReport::default());
+    assert_eq!(alice.cache().pending(ALICE).unwrap().len(), 1);
+
+    storage.set_reachable(true);
+    assert_eq!(alice.sync_pending_writes().await.unwrap().synced, 1);
+    assert!(alice.cache().pending(ALICE).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn offline_updates_of_pages_saved_elsewhere_are_merged_on_sync() {
+    let storage = MemoryStorage::new(ALICE);
+    let laptop = client(&storage);
+    let desktop = client(&storage);
+    let page_id = create(&laptop, "# Rust\n\nA language\n\nBy Mozilla").await;
+    let base = laptop.fetch_page(ALICE, &page_id).await.unwrap();
+
+    let mine = edited(&base, "# Rust\n\nA language\n\nBy the Rust Foundation");
+    let write = PendingWrite::Update {
+        page_id: page_id.clone(),
+        page: mine.clone(),
+        base_version: Some(Revision::of(&base)),
+    };
+    laptop.cache().queue(ALICE, &write).unwrap();
+    let theirs = edited(&base, "# Rust\n\nA systems language\n\nBy Mozilla");
+    desktop.update_page(&page_id, &theirs, Some(Revision::of(&base))).await.unwrap();
+
+    let report = laptop.sync_pending_writes().await.unwrap();
+    assert_eq!(report.synced, 0);
+    let [conflict] = &report.conflicts[..] else {
+        panic!("Expected a single conflict");
+    };
+    assert_eq!(conflict.page_id, page_id);
+    assert_eq!(conflict.merge.result.text, "# Rust\n\nA systems language\n\nBy the Rust Foundation");
+
+    // The version saved elsewhere is kept, and the offline changes are kept in a draft
+    assert_eq!(laptop.fetch_page(ALICE, &page_id).await.unwrap().body, theirs.body);
+    assert!(laptop.cache().pending(ALICE).unwrap().is_empty());
+    let drafts = laptop.cache().drafts(ALICE).unwrap();
+    assert_eq!(drafts.len(), 1);
+    assert_eq!(drafts[0].id, conflict.draft_id);
+    assert_eq!(drafts[0].page.body, mine.body);
+}
+
+#[tokio::test]
+async fn followed_users_are_listed_with_their_profile_names() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+    storage.as_user(BOB).put(PROFILE_PATH, r#"{"name": "Bob"}"#.to_string()).await.unwrap();
+    storage.follow(BOB).unwrap();
+    storage.follow(CAROL).unwrap();
+
+    let follows = alice.followed_users().await;
+
+    assert_eq!(
+        follows,
+        vec![
+            Follow {
+                user_pk: BOB.to_string(),
+                name: Some("Bob".to_string()),
+            },
+            Follow {
+                user_pk: CAROL.to_string(),
+                name: None,
+            },
+        ]
+    );
+    assert!(follows[0].matches_alias("bob"));
+    assert!(follows[1].matches_alias("car"));
+}
+
+#[tokio::test]
+async fn indexes_cover_the_pages_of_the_follows() {
+    let storage = MemoryStorage::new(ALICE);
+    let alice = client(&storage);
+    let bob = client(&storage.as_user(BOB));
+    let page_id = create(&alice, "# Rust\n\nA language").await;
+    let fork = bob.fork_page(ALICE, &page_id).await.unwrap();
+    bob.update_page(&page_id, &edited(&fork, "# Rust\n\nSee [[Cargo]]"), None).await.unwrap();
+    let cargo_id = create(&bob, "# Cargo").await;
+    storage.follow(BOB).unwrap();
+
+    let (search, links) = alice.build_indexes().await;
+
+    assert_eq!(search.len(), 3);
+    assert_eq!(links.backlinks(BOB, &cargo_id)[0].page_id, page_id);
+}
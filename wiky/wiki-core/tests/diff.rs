@@ -0,0 +1,95 @@
+use wiki_core::diff::{line_stats, side_by_side, DiffRow, Segment};
+
+fn segment(text: &str, changed: bool) -> Segment {
+    Segment {
+        text: text.to_string(),
+        changed,
+    }
+}
+
+fn same(line: &str) -> DiffRow {
+    DiffRow {
+        left: Some(vec![segment(line, false)]),
+        right: Some(vec![segment(line, false)]),
+    }
+}
+
+#[test]
+fn inserted_lines_only_show_on_the_right() {
+    assert_eq!(
+        side_by_side("a\nc\n", "a\nb\nc\n"),
+        vec![
+            same("a"),
+            DiffRow {
+                left: None,
+                right: Some(vec![segment("b", true)]),
+            },
+            same("c"),
+        ]
+    );
+    assert_eq!(line_stats("a\nc\n", "a\nb\nc\n"), (1, 0));
+}
+
+#[test]
+fn deleted_lines_only_show_on_the_left() {
+    assert_eq!(
+        side_by_side("a\nb\r\nc", "a\nc"),
+        vec![
+            same("a"),
+            DiffRow {
+                left: Some(vec![segment("b", true)]),
+                right: None,
+            },
+            same("c"),
+        ]
+    );
+    assert_eq!(line_stats("a\nb\r\nc", "a\nc"), (0, 1));
+}
+
+#[test]
+fn replaced_lines_are_paired_up_and_the_rest_shown_alone() {
+    assert_eq!(
+        side_by_side("title\none\ntwo\n", "title\nuno\n"),
+        vec![
+            same("title"),
+            DiffRow {
+                left: Some(vec![segment("one", true)]),
+                right: Some(vec![segment("uno", true)]),
+            },
+            DiffRow {
+                left: Some(vec![segment("two", true)]),
+                right: None,
+            },
+        ]
+    );
+    assert_eq!(line_stats("title\none\ntwo\n", "title\nuno\n"), (1, 2));
+}
+
+#[test]
+fn changed_words_are_highlighted_within_modified_lines() {
+    assert_eq!(
+        side_by_side("Rust is a fast language\n", "Rust is a safe and fast language\n"),
+        vec![DiffRow {
+            left: Some(vec![segment("Rust is a fast language", false)]),
+            right: Some(vec![
+                segment("Rust is a ", false),
+                segment("safe and ", true),
+                segment("fast language", false),
+            ]),
+        }]
+    );
+    assert_eq!(
+        side_by_side("The old page\n", "The new page\n"),
+        vec![DiffRow {
+            left: Some(vec![segment("The ", false), segment("old", true), segment(" page", false)]),
+            right: Some(vec![segment("The ", false), segment("new", true), segment(" page", false)]),
+        }]
+    );
+}
+
+#[test]
+fn identical_texts_have_no_changes() {
+    assert_eq!(side_by_side("a\nb", "a\nb"), vec![same("a"), same("b")]);
+    assert_eq!(line_stats("a\nb", "a\nb"), (0, 0));
+    assert_eq!(side_by_side("", ""), vec![]);
+}
@@ -0,0 +1,34 @@
+use wiki_core::format::{apply_format, insert_text, Format};
+
+fn format(text: &str, selection: std::ops::Range<usize>, format: Format) -> (String, String) {
+    let mut text = text.to_string();
+    let selection = apply_format(&mut text, selection, format);
+    let selected = text.chars().skip(selection.start).take(selection.len()).collect();
+    (text, selected)
+}
+
+#[test]
+fn selections_are_wrapped_or_replaced_by_a_placeholder() {
+    assert_eq!(format("Ünïcode is fast", 11..15, Format::Bold), ("Ünïcode is **fast**".into(), "fast".into()));
+    assert_eq!(format("Rust", 4..4, Format::Italic), ("Rust*italic text*".into(), "italic text".into()));
+    assert_eq!(format("see docs", 4..8, Format::Link), ("see [docs](url)".into(), "url".into()));
+    assert_eq!(
+        format("a\nlet x = 1;\nb", 4..5, Format::CodeBlock),
+        ("a\n```\nlet x = 1;\n```\nb".into(), "let x = 1;".into())
+    );
+}
+
+#[test]
+fn line_formats_apply_to_every_selected_line() {
+    assert_eq!(format("## Intro\ntext", 3..3, Format::Heading(1)).0, "# Intro\ntext");
+    assert_eq!(format("one\ntwo\nthree", 1..5, Format::NumberedList).0, "1. one\n2. two\nthree");
+    assert_eq!(format("one\ntwo", 5..5, Format::BulletList).0, "one\n- two");
+    assert!(format("Intro\n", 6..6, Format::Table).0.starts_with("Intro\n\n| Column | Column |\n"));
+}
+
+#[test]
+fn inserted_text_leaves_the_cursor_after_it() {
+    let mut text = "See here".to_string();
+    assert_eq!(insert_text(&mut text, 4..8, "[Rust](wiki:Rust)"), 21..21);
+    assert_eq!(text, "See [Rust](wiki:Rust)");
+}
@@ -0,0 +1,74 @@
+use wiki_core::{
+    follow::Follow,
+    graph::{Backlink, LinkGraph},
+    links::WikiLink,
+    page::WikiPage,
+};
+
+const ALICE: &str = "alice";
+const BOB: &str = "bob";
+
+fn graph(pages: &[(&str, &str, &str)]) -> LinkGraph {
+    let mut graph = LinkGraph::default();
+    graph.set_follows(vec![Follow {
+        user_pk: BOB.to_string(),
+        name: Some("Bob".to_string()),
+    }]);
+    for (user_pk, page_id, body) in pages {
+        graph.insert(user_pk, page_id, &WikiPage::new(body.to_string()));
+    }
+
+    graph
+}
+
+fn backlink(user_pk: &str, page_id: &str, title: &str, via_fork_of: Option<&str>) -> Backlink {
+    Backlink {
+        user_pk: user_pk.to_string(),
+        page_id: page_id.to_string(),
+        title: title.to_string(),
+        via_fork_of: via_fork_of.map(str::to_string),
+    }
+}
+
+#[test]
+fn backlinks_follow_every_link_form() {
+    let rust_url = WikiLink::page_url(ALICE, "rust");
+    let graph = graph(&[
+        (ALICE, "rust", "# Rust"),
+        (ALICE, "cargo", &format!("# Cargo\n\nBuilds [Rust]({rust_url}) code")),
+        (ALICE, "clippy", "# Clippy\n\nLints [[Rust]]"),
+        (ALICE, "serde", "# Serde\n\nFor [Rust](wiki:Rust) and `[[Go]]`"),
+        (BOB, "go", "# Go\n\nUnlike [[@alice/Rust]] or [[Go]]"),
+        (BOB, "tokio", "# Tokio\n\n[Docs](https://tokio.rs)"),
+    ]);
+
+    assert_eq!(
+        graph.backlinks(ALICE, "rust"),
+        vec![
+            backlink(ALICE, "cargo", "Cargo", None),
+            backlink(ALICE, "clippy", "Clippy", None),
+            backlink(ALICE, "serde", "Serde", None),
+        ]
+    );
+    // Links to the own page are left out, as is the alias, which is not a follow
+    assert!(graph.backlinks(BOB, "go").is_empty());
+}
+
+#[test]
+fn backlinks_include_links_to_other_versions_and_from_forks() {
+    let graph = graph(&[
+        (ALICE, "rust", "# Rust"),
+        (BOB, "rust", "# Rust\n\nForked"),
+        (BOB, "cargo", "# Cargo\n\nBuilds [[Rust]]"),
+        (ALICE, "go", "# Go\n\nUnlike [[@Bob/Rust]]"),
+    ]);
+
+    assert_eq!(
+        graph.backlinks(ALICE, "rust"),
+        vec![
+            backlink(BOB, "cargo", "Cargo", Some(BOB)),
+            backlink(ALICE, "go", "Go", Some(BOB)),
+        ]
+    );
+    assert_eq!(graph.links_of(&(ALICE.to_string(), "go".to_string())), vec![(BOB.to_string(), "rust".to_string())]);
+}
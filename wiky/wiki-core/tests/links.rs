@@ -0,0 +1,114 @@
+use wiki_core::links::{expand_title_links, heading_offset, same_heading, TitleLink, WikiLink};
+
+const PK: &str = "77femca644769gt9gwkzsg6g4hxmpc9s6ciqapce9by89e4yhpso";
+const PAGE_ID: &str = "64597e9a-f0be-4408-a99e-9ddda72e578e";
+
+fn page(heading: Option<&str>) -> Option<WikiLink> {
+    Some(WikiLink::Page {
+        user_pk: PK.to_string(),
+        page_id: PAGE_ID.to_string(),
+        heading: heading.map(str::to_string),
+    })
+}
+
+#[test]
+fn page_links_are_parsed_in_every_form() {
+    assert_eq!(WikiLink::parse(&format!("pubky://{PK}/pub/wiki.app/{PAGE_ID}")), page(None));
+    assert_eq!(WikiLink::parse(&format!("wiki:{PK}/{PAGE_ID}#History")), page(Some("History")));
+    assert_eq!(WikiLink::parse(&format!("{PK}/{PAGE_ID}")), page(None));
+    assert_eq!(WikiLink::parse(&WikiLink::page_url(PK, PAGE_ID)), page(None));
+}
+
+#[test]
+fn attachment_links_are_parsed() {
+    let path = format!("/pub/wiki.app/files/{PAGE_ID}-logo.png");
+
+    assert_eq!(
+        WikiLink::parse(&WikiLink::attachment_url(PK, &path)),
+        Some(WikiLink::Attachment {
+            user_pk: PK.to_string(),
+            path,
+        })
+    );
+    assert_eq!(WikiLink::parse(&format!("pubky://{PK}/pub/wiki.app/files/")), None);
+}
+
+#[test]
+fn title_links_round_trip() {
+    let url = WikiLink::title_url("C/C++ (history)");
+    assert_eq!(url, "wiki:C%2FC++%20%28history%29");
+
+    assert_eq!(
+        WikiLink::parse(&format!("{url}#Early%20days")),
+        Some(WikiLink::Title {
+            title: "C/C++ (history)".to_string(),
+            heading: Some("Early days".to_string()),
+        })
+    );
+}
+
+#[test]
+fn web_links_are_external() {
+    for url in ["https://example.com/a/b", "HTTP://example.com", "mailto:alice@example.com"] {
+        assert_eq!(WikiLink::parse(url), Some(WikiLink::External(url.to_string())));
+    }
+}
+
+#[test]
+fn other_links_are_rejected() {
+    assert_eq!(WikiLink::parse("images/rust.png"), None);
+    assert_eq!(WikiLink::parse(&format!("pubky://{PK}/pub/wiki.app/revisions/{PAGE_ID}/1")), None);
+    assert_eq!(WikiLink::parse(&format!("pubky://{PK}/pub/other.app/{PAGE_ID}")), None);
+    assert_eq!(WikiLink::parse("wiki:"), None);
+    assert_eq!(WikiLink::parse(&format!("wiki:{PK}/revisions")), None);
+}
+
+#[test]
+fn double_bracket_links_are_expanded_outside_of_code() {
+    let body = "See [[Rust]], [[Go|the Go page]] and [[@bob/Lugano]].\n`[[Code]]`\n```\n[[Fenced]]\n```\n[[Missing]]";
+
+    let mut links = vec![];
+    let expanded = expand_title_links(body, |link| {
+        links.push(link.clone());
+        (link.title != "Missing").then(|| format!("wiki:{}", link.title))
+    });
+
+    assert_eq!(
+        expanded,
+        "See [Rust](<wiki:Rust>), [the Go page](<wiki:Go>) and [Lugano](<wiki:Lugano>).\n`[[Code]]`\n```\n[[Fenced]]\n```\n[Missing ✨](<wiki-new:Missing>)"
+    );
+    assert_eq!(
+        links[2],
+        TitleLink {
+            alias: Some("bob".to_string()),
+            title: "Lugano".to_string(),
+            label: None,
+        }
+    );
+    assert_eq!(
+        WikiLink::parse("wiki-new:Missing"),
+        Some(WikiLink::NewPage {
+            title: "Missing".to_string()
+        })
+    );
+}
+
+#[test]
+fn top_level_headings_are_found() {
+    let body = "Intro\n\n- ## Nested\n\n```\n# Not a heading\n```\n\n# Getting `Started`\n\nSetup\n-----\nDone";
+
+    assert_eq!(heading_offset(body, "getting-started"), body.find("# Getting"));
+    assert_eq!(heading_offset(body, "Setup"), body.find("Setup"));
+    assert_eq!(heading_offset(body, "nested"), None);
+    assert_eq!(heading_offset(body, "not-a-heading"), None);
+    assert_eq!(heading_offset("# Title\n# Title", "title"), Some(0));
+}
+
+#[test]
+fn headings_match_their_anchors() {
+    assert!(same_heading("Getting Started", "getting-started"));
+    assert!(same_heading("Getting Started", "Getting Started"));
+    assert!(same_heading("What's new?", "whats-new"));
+    assert!(!same_heading("Getting Started", "started"));
+    assert!(!same_heading("?", "?"));
+}
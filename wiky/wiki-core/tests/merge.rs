@@ -0,0 +1,84 @@
+use wiki_core::{
+    merge::{conflict_count, merge, resolve_conflict, MergeSource, PreparedMerge, Resolution},
+    page::WikiPage,
+};
+
+const BASE: &str = "# Rust\n\nA language\n\nBy Mozilla\n";
+
+#[test]
+fn changes_of_one_side_are_applied() {
+    let mine = "# Rust\n\nA systems language\n\nBy Mozilla\n";
+    let upstream = "# Rust\n\nA language\n\nBy the Rust Foundation\n";
+
+    let result = merge(BASE, mine, upstream, "upstream");
+    assert_eq!(result.text, "# Rust\n\nA systems language\n\nBy the Rust Foundation\n");
+    assert_eq!((result.applied, result.conflicts), (1, 0));
+
+    // The same change on both sides is no conflict
+    let result = merge(BASE, mine, mine, "upstream");
+    assert_eq!(result.text, mine);
+    assert_eq!((result.applied, result.conflicts), (0, 0));
+}
+
+#[test]
+fn overlapping_changes_are_kept_between_markers() {
+    let mine = "# Rust\n\nA fast language\n\nBy Mozilla\n";
+    let upstream = "# Rust\n\nA safe language\n\nBy Mozilla\n";
+
+    let result = merge(BASE, mine, upstream, "upstream");
+    assert_eq!(
+        result.text,
+        "# Rust\n\n<<<<<<< mine\nA fast language\n=======\nA safe language\n>>>>>>> upstream\n\nBy Mozilla\n"
+    );
+    assert_eq!((result.applied, result.conflicts), (0, 1));
+    assert_eq!(conflict_count(&result.text), 1);
+}
+
+#[test]
+fn conflicts_name_the_other_side() {
+    let mine = "# Rust\n\nA fast language\n\nBy me\n";
+    let theirs = "# Rust\n\nA safe language\n\nBy Mozilla\n";
+    let source = MergeSource::Concurrent {
+        base: Box::new(WikiPage::new(BASE.to_string())),
+        current: Box::new(WikiPage::new(theirs.to_string())),
+    };
+
+    let merge = PreparedMerge::new(BASE, WikiPage::new(mine.to_string()), theirs, source);
+    assert!(merge.result.text.contains("\n>>>>>>> concurrent\n"));
+    assert!(!merge.result.text.contains("upstream"));
+    assert_eq!(
+        resolve_conflict(&merge.result.text, 0, Resolution::Upstream),
+        "# Rust\n\nA safe language\n\nBy me\n"
+    );
+}
+
+#[test]
+fn conflicts_are_resolved_one_at_a_time() {
+    let base = "a\nb\nc\nd\ne\n";
+    let mine = "a\nB1\nc\nD1\ne\n";
+    let upstream = "a\nB2\nc\nD2\ne\n";
+    let text = merge(base, mine, upstream, "proposal").text;
+    assert_eq!(conflict_count(&text), 2);
+
+    let first_mine = resolve_conflict(&text, 0, Resolution::Mine);
+    assert_eq!(conflict_count(&first_mine), 1);
+    assert!(first_mine.starts_with("a\nB1\nc\n<<<<<<< mine\nD1\n=======\nD2\n>>>>>>> proposal\n"));
+
+    assert_eq!(resolve_conflict(&first_mine, 0, Resolution::Upstream), "a\nB1\nc\nD2\ne\n");
+    assert_eq!(
+        resolve_conflict(&text, 1, Resolution::Both),
+        "a\n<<<<<<< mine\nB1\n=======\nB2\n>>>>>>> proposal\nc\nD1\nD2\ne\n"
+    );
+}
+
+#[test]
+fn resolved_text_has_no_conflicts_left() {
+    let text = merge("a\n", "b\n", "c\n", "upstream").text;
+
+    assert_eq!(resolve_conflict(&text, 0, Resolution::Mine), "b\n");
+    assert_eq!(resolve_conflict(&text, 0, Resolution::Upstream), "c\n");
+    assert_eq!(resolve_conflict(&text, 0, Resolution::Both), "b\nc\n");
+    assert_eq!(conflict_count(&resolve_conflict(&text, 0, Resolution::Both)), 0);
+    // Markers closing with another label, or none, are recognized too
+    assert_eq!(resolve_conflict("<<<<<<< mine\nb\n=======\nc\n>>>>>>>\n", 0, Resolution::Upstream), "c\n");
+}
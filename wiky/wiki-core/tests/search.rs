@@ -0,0 +1,78 @@
+use wiki_core::{page::WikiPage, search::SearchIndex};
+
+fn index(pages: &[(&str, &str, &str)]) -> SearchIndex {
+    let mut index = SearchIndex::default();
+    for (user_pk, page_id, body) in pages {
+        index.insert(user_pk, page_id, &WikiPage::new(body.to_string()));
+    }
+
+    index
+}
+
+/// Author and ID of the hits, best first
+fn ranking(index: &SearchIndex, query: &str) -> Vec<(String, String)> {
+    index
+        .search(query)
+        .into_iter()
+        .map(|hit| (hit.user_pk, hit.page_id))
+        .collect()
+}
+
+fn key(user_pk: &str, page_id: &str) -> (String, String) {
+    (user_pk.to_string(), page_id.to_string())
+}
+
+#[test]
+fn title_matches_rank_above_body_matches() {
+    let index = index(&[
+        ("alice", "lang", "# Languages\n\nRust, Rust and more Rust, to name one"),
+        ("alice", "rust", "# Rust\n\nA language"),
+        ("alice", "go", "# Go\n\nA language"),
+    ]);
+
+    assert_eq!(ranking(&index, "rust"), vec![key("alice", "rust"), key("alice", "lang")]);
+    let hits = index.search("rust");
+    assert!(hits[0].score > hits[1].score);
+    assert_eq!(hits[1].snippet.as_deref(), Some("Rust, Rust and more Rust, to name one"));
+}
+
+#[test]
+fn rare_terms_weigh_more() {
+    let index = index(&[
+        ("alice", "a", "# Notes\n\nA book"),
+        ("alice", "b", "# Notes\n\nThe borrow checker"),
+        ("alice", "c", "# Notes\n\nAnother book"),
+        ("alice", "d", "# Notes\n\nA third book"),
+    ]);
+
+    // "b" matches both words, "borrow" is only in one page
+    assert_eq!(
+        ranking(&index, "b"),
+        vec![key("alice", "b"), key("alice", "a"), key("alice", "c"), key("alice", "d")]
+    );
+}
+
+#[test]
+fn ties_are_ordered_by_title_then_by_page() {
+    let index = index(&[
+        ("carol", "2", "# Beta\n\nA language"),
+        ("bob", "1", "# Alpha\n\nA language"),
+        ("alice", "9", "# Beta\n\nA language"),
+        ("alice", "3", "# Beta\n\nA language"),
+    ]);
+
+    let expected = vec![key("bob", "1"), key("alice", "3"), key("alice", "9"), key("carol", "2")];
+    for _ in 0..5 {
+        assert_eq!(ranking(&index, "language"), expected);
+    }
+}
+
+#[test]
+fn the_last_word_matches_as_a_prefix() {
+    let index = index(&[("alice", "rust", "# Rust\n\nOwnership and borrowing")]);
+
+    assert_eq!(ranking(&index, "own"), vec![key("alice", "rust")]);
+    assert_eq!(ranking(&index, "own rust"), vec![]);
+    assert_eq!(ranking(&index, "ownership bor"), vec![key("alice", "rust")]);
+    assert_eq!(ranking(&index, ""), vec![]);
+}